@@ -1,43 +1,51 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use livox_lidar_rs::lidar_frame::frames::{ControlFrame, SampleCtrlReq, WriteFlashReq};
+use livox_lidar_rs::lidar_frame::frames::{
+    ControlFrame, IpConfigReq, IpMode, Request, WriteFlashReq, HEARTBEAT_REQ,
+};
 
 fn control_frame_serialize_deserialize_benchmark(c: &mut Criterion) {
-    // let read_from = ControlFrame::new(
-    //     0x00,
-    //     IpConfigReq::new(
-    //         0x00,
-    //         [192, 168, 1, 150],
-    //         [255, 255, 255, 0],
-    //         [192, 168, 1, 1],
-    //     ),
-    // );
-    // let read_from = ControlFrame::new(0x00, WriteFlashReq::new(true, false, 8));
-    // let test_buffer = read_from.serialize().unwrap();
-
-    // c.bench_function("control_frame_serialize", |b| {
-    //     b.iter(|| {
-    //         criterion::black_box({
-    //             read_from.serialize().unwrap();
-    //         });
-    //     })
-    // });
-
-    // c.bench_function("control_frame_deserialize", |b| {
-    //     b.iter(|| {
-    //         criterion::black_box({
-    //             ControlFrame::<SampleCtrlReq>::deserialize(&test_buffer).unwrap();
-    //         });
-    //     })
-    // });
-
-    // c.bench_function("control_frame_serialize&deserialize", |b| {
-    //     b.iter(|| {
-    //         criterion::black_box({
-    //             let buffer = read_from.serialize().unwrap();
-    //             ControlFrame::<SampleCtrlReq>::deserialize(&buffer).unwrap();
-    //         });
-    //     })
-    // });
+    let ip_config_req = IpConfigReq::new(
+        IpMode::Static,
+        [192, 168, 1, 150],
+        [255, 255, 255, 0],
+        [192, 168, 1, 1],
+    );
+    let read_from = ControlFrame::new(0x00, &ip_config_req);
+    let test_buffer = read_from.serialize().unwrap();
+
+    c.bench_function("control_frame_serialize", |b| {
+        b.iter(|| criterion::black_box(read_from.serialize().unwrap()))
+    });
+
+    c.bench_function("control_frame_serialize_into", |b| {
+        let mut buffer = [0; 1024];
+        b.iter(|| criterion::black_box(read_from.serialize_into(&mut buffer).unwrap()))
+    });
+
+    c.bench_function("control_frame_deserialize", |b| {
+        b.iter(|| criterion::black_box(ControlFrame::<Request>::deserialize(&test_buffer).unwrap()))
+    });
+
+    c.bench_function("control_frame_serialize&deserialize", |b| {
+        let mut buffer = [0; 1024];
+        b.iter(|| {
+            let len = read_from.serialize_into(&mut buffer).unwrap();
+            criterion::black_box(ControlFrame::<Request>::deserialize(&buffer[..len]).unwrap())
+        })
+    });
+
+    let heartbeat = ControlFrame::new(0x00, &HEARTBEAT_REQ);
+    c.bench_function("heartbeat_serialize_into", |b| {
+        let mut buffer = [0; 1024];
+        b.iter(|| criterion::black_box(heartbeat.serialize_into(&mut buffer).unwrap()))
+    });
+
+    let write_flash_req = WriteFlashReq::new(true, false, 8).unwrap();
+    let write_flash = ControlFrame::new(0x00, &write_flash_req);
+    c.bench_function("write_flash_serialize_into", |b| {
+        let mut buffer = [0; 1024];
+        b.iter(|| criterion::black_box(write_flash.serialize_into(&mut buffer).unwrap()))
+    });
 }
 
 criterion_group!(benches, control_frame_serialize_deserialize_benchmark);
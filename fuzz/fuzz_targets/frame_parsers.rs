@@ -0,0 +1,29 @@
+//! every parser of `lidar_frame::frames` fed with arbitrary bytes, run with
+//! `cargo +nightly fuzz run frame_parsers` from the repository root
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use livox_lidar_rs::lidar_frame::frames::{
+    deserialize_ack, deserialize_broadcast, deserialize_data, deserialize_frame, deserialize_msg,
+    ControlFrame,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = deserialize_frame(data);
+    let _ = deserialize_ack(data);
+    let _ = deserialize_msg(data);
+    let _ = deserialize_broadcast(data);
+    if let Ok(packet) = deserialize_data(data) {
+        let _ = packet.points.len();
+        let _ = packet.imu_samples().count();
+    }
+
+    // whatever is accepted as a command survives a round trip
+    if let Ok((seq_num, req)) = ControlFrame::deserialize(data) {
+        let serialized = req.serialize(seq_num).unwrap();
+        assert_eq!(
+            ControlFrame::deserialize(&serialized).unwrap(),
+            (seq_num, req)
+        );
+    }
+});
@@ -3,91 +3,474 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
-use syn::{Data, DeriveInput};
+use syn::{Data, DataStruct, DeriveInput, Fields};
+
+/// struct fields of a derive input, error pointing at enum or union otherwise
+fn struct_data<'a>(ast: &'a DeriveInput, derive: &str) -> syn::Result<&'a DataStruct> {
+    match &ast.data {
+        Data::Struct(ds) => Ok(ds),
+        Data::Enum(de) => Err(syn::Error::new_spanned(
+            de.enum_token,
+            format!("Trait {} derive must be use on struct", derive),
+        )),
+        Data::Union(du) => Err(syn::Error::new_spanned(
+            du.union_token,
+            format!("Trait {} derive must be use on struct", derive),
+        )),
+    }
+}
+
+/// whether type is a path ending with `name`, such as `u8` or `frames::Cmd`
+fn is_type(ty: &syn::Type, name: &str) -> bool {
+    match ty {
+        syn::Type::Path(ty) => {
+            ty.qself.is_none()
+                && ty
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|seg| seg.ident == name && seg.arguments.is_none())
+        }
+        syn::Type::Group(ty) => is_type(&ty.elem, name),
+        syn::Type::Paren(ty) => is_type(&ty.elem, name),
+        _ => false,
+    }
+}
+
+/// expand derive, turning errors into `compile_error!` at their span
+fn expand(
+    ast: syn::Result<DeriveInput>,
+    derive: fn(&DeriveInput) -> syn::Result<TokenStream2>,
+) -> TokenStream {
+    ast.and_then(|ast| derive(&ast))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
 #[proc_macro_derive(CheckStatus)]
 pub fn check_status_derive(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse(input).unwrap();
+    expand(syn::parse(input), check_status)
+}
+
+fn check_status(ast: &DeriveInput) -> syn::Result<TokenStream2> {
     let name = &ast.ident;
-    let Data::Struct(ds) = ast.data else {
-        panic!("Trait CheckStatus derive must be use on struct");
-    };
-    if !ds
+    let ds = struct_data(ast, "CheckStatus")?;
+    let Some(field) = ds
         .fields
         .iter()
-        .any(|f| f.ident.as_ref().is_some_and(|name| name == "ret_code"))
-    {
-        panic!("Trait CheckStatus needs struct field 'ret_code'");
+        .find(|f| f.ident.as_ref().is_some_and(|name| name == "ret_code"))
+    else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "Trait CheckStatus needs struct field 'ret_code'",
+        ));
+    };
+    if !is_type(&field.ty, "u8") {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "Trait CheckStatus needs field 'ret_code' of type u8",
+        ));
     }
-    let gen = quote! {
+    Ok(quote! {
         impl CheckStatus for #name {
-            fn check_status(&self) -> Result<()> {
+            fn check_status(&self, cmd: Cmd) -> Result<()> {
                 if self.ret_code == 0u8 {
                     Ok(())
                 }
                 else {
-                    Err(anyhow!("{} failed on checking status code ❌", stringify!(#name)))
+                    Err(LivoxError::DeviceRejected { cmd, ret_code: self.ret_code })
                 }
             }
         }
-    };
-    gen.into()
+    })
 }
 
 #[proc_macro_derive(Len)]
 pub fn len_derive(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse(input).unwrap();
+    expand(syn::parse(input), len)
+}
+
+fn len(ast: &DeriveInput) -> syn::Result<TokenStream2> {
     let id = &ast.ident;
-    let Data::Struct(ds) = ast.data else {
-        panic!("Trait Len derive must be use on struct");
-    };
+    let ds = struct_data(ast, "Len")?;
     let size_sum = TokenStream2::from_iter(ds.fields.iter().map(|f| {
         let ty = &f.ty;
         quote! {
-            std::mem::size_of::<#ty>(),
+            + std::mem::size_of::<#ty>()
         }
     }));
-    let gen = quote! {
+    Ok(quote! {
         impl Len for #id {
-            fn len() -> u16 {
-                [#size_sum].iter().fold(0,|s,x|x+s) as u16
-            }
+            const LEN: u16 = (0 #size_sum) as u16;
         }
-    };
-    gen.into()
+    })
 }
 
 #[proc_macro_derive(GetCmd)]
 pub fn cmd_derive(input: TokenStream) -> TokenStream {
-    let ast: DeriveInput = syn::parse(input).unwrap();
+    expand(syn::parse(input), get_cmd)
+}
+
+fn get_cmd(ast: &DeriveInput) -> syn::Result<TokenStream2> {
     let id = &ast.ident;
-    let Data::Struct(ds) = ast.data else {
-        panic!("Trait GetCmd derive must be use on struct");
+    let ds = struct_data(ast, "GetCmd")?;
+
+    // named field 'cmd', or first field of a tuple struct
+    let (field, member) = match &ds.fields {
+        Fields::Named(fields) => {
+            let Some(field) = fields
+                .named
+                .iter()
+                .find(|f| f.ident.as_ref().is_some_and(|name| name == "cmd"))
+            else {
+                return Err(syn::Error::new_spanned(
+                    id,
+                    "Trait GetCmd needs struct field 'cmd'",
+                ));
+            };
+            let name = &field.ident;
+            (field, quote!(#name))
+        }
+        Fields::Unnamed(fields) if !fields.unnamed.is_empty() => {
+            let idx = syn::Index::from(0);
+            (&fields.unnamed[0], quote!(#idx))
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                id,
+                "Trait GetCmd needs struct field 'cmd' or a tuple struct starting with Cmd",
+            ))
+        }
     };
+    if !is_type(&field.ty, "Cmd") {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "Trait GetCmd needs command field of type Cmd",
+        ));
+    }
 
-    let Some(field) = ds.fields.into_iter().enumerate().find_map(|(idx, f)|{
-        if let Some(field_id) = f.ident {
-            if field_id == "cmd" {
-                Some(quote!(#field_id))
-            }
-            else{
-                None
+    Ok(quote! {
+        impl GetCmd for #id {
+            fn cmd(&self) -> Cmd {
+                self.#member
             }
         }
-        else {
-            let idx_str = syn::Index::from(idx);
-            Some(quote!(#idx_str))
-        }
-    }) else {
-        panic!("Trait GetCmd needs struct field 'cmd'");
+    })
+}
+
+/// Command set, command id and response type given in `#[livox(...)]` on the struct
+struct CommandAttrs {
+    cmd_set: syn::Expr,
+    cmd_id: syn::Expr,
+    response: syn::Type,
+    /// data segment varies in length, so no `Len` is generated
+    variable_len: bool,
+}
+
+fn command_attrs(ast: &DeriveInput) -> syn::Result<CommandAttrs> {
+    let (mut cmd_set, mut cmd_id, mut response) = (None, None, None);
+    let mut variable_len = false;
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("livox"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("cmd_set") {
+                cmd_set = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("cmd_id") {
+                cmd_id = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("response") {
+                response = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("variable_len") {
+                variable_len = true;
+            } else {
+                return Err(
+                    meta.error("expected `cmd_set`, `cmd_id`, `response` or `variable_len`")
+                );
+            }
+            Ok(())
+        })?;
+    }
+    let missing = |name| {
+        syn::Error::new_spanned(
+            &ast.ident,
+            format!("LivoxCommand needs `#[livox({} = ...)]`", name),
+        )
     };
-   
-    let gen = quote! {
+    Ok(CommandAttrs {
+        cmd_set: cmd_set.ok_or_else(|| missing("cmd_set"))?,
+        cmd_id: cmd_id.ok_or_else(|| missing("cmd_id"))?,
+        response: response.ok_or_else(|| missing("response"))?,
+        variable_len,
+    })
+}
+
+/// Key of a field sent as key, length and value, given in `#[livox(key = ...)]`
+fn field_key(field: &syn::Field) -> syn::Result<Option<syn::Expr>> {
+    let mut key = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("livox"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                key = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `key`"))
+            }
+        })?;
+    }
+    Ok(key)
+}
+
+/// Command frame data segment: constant command, then plain fields in order,
+/// then key-value fields, which lidar may send back in any order
+#[proc_macro_derive(LivoxCommand, attributes(livox))]
+pub fn livox_command_derive(input: TokenStream) -> TokenStream {
+    expand(syn::parse(input), livox_command)
+}
+
+fn livox_command(ast: &DeriveInput) -> syn::Result<TokenStream2> {
+    let id = &ast.ident;
+    let ds = struct_data(ast, "LivoxCommand")?;
+    let CommandAttrs {
+        cmd_set,
+        cmd_id,
+        response,
+        variable_len,
+    } = command_attrs(ast)?;
+    let codec = segment_codec(
+        id,
+        ds,
+        Some(quote!(<#id as LivoxCommand>::CMD)),
+        variable_len,
+    )?;
+
+    Ok(quote! {
+        impl LivoxCommand for #id {
+            const CMD: Cmd = Cmd::new(#cmd_set, #cmd_id);
+            type Response = #response;
+        }
+
         impl GetCmd for #id {
             fn cmd(&self) -> Cmd {
-                self.#field
+                <Self as LivoxCommand>::CMD
             }
         }
+
+        #codec
+    })
+}
+
+/// Data segment without command, such as a response: plain fields in order,
+/// then key-value fields given in `#[livox(key = ...)]`, in any order
+#[proc_macro_derive(KeyValue, attributes(livox))]
+pub fn key_value_derive(input: TokenStream) -> TokenStream {
+    expand(syn::parse(input), key_value)
+}
+
+fn key_value(ast: &DeriveInput) -> syn::Result<TokenStream2> {
+    let ds = struct_data(ast, "KeyValue")?;
+    segment_codec(&ast.ident, ds, None, false)
+}
+
+/// `Len`, `Serialize` and `Deserialize` of a data segment, led by `cmd` if any
+fn segment_codec(
+    id: &syn::Ident,
+    ds: &DataStruct,
+    cmd: Option<TokenStream2>,
+    variable_len: bool,
+) -> syn::Result<TokenStream2> {
+    let mut plain = Vec::new();
+    let mut keyed = Vec::new();
+    for (idx, f) in ds.fields.iter().enumerate() {
+        let member = match &f.ident {
+            Some(name) => quote!(#name),
+            None => {
+                let idx = syn::Index::from(idx);
+                quote!(#idx)
+            }
+        };
+        if cmd.is_some() && is_type(&f.ty, "Cmd") {
+            return Err(syn::Error::new_spanned(
+                f,
+                "command is given by `#[livox(cmd_set = ..., cmd_id = ...)]`, drop the Cmd field",
+            ));
+        }
+        let binding = quote::format_ident!("__field{}", idx);
+        let field = (member, binding, &f.ty);
+        match field_key(f)? {
+            Some(key) => keyed.push((field, key)),
+            None if keyed.is_empty() => plain.push(field),
+            None => {
+                return Err(syn::Error::new_spanned(
+                    f,
+                    "plain fields must come before key-value fields",
+                ))
+            }
+        }
+    }
+
+    let cmd_len = match cmd {
+        Some(_) => quote!(Cmd::LEN as usize),
+        None => quote!(0usize),
     };
-    gen.into()
-}
\ No newline at end of file
+    let plain_len = plain
+        .iter()
+        .map(|(_, _, ty)| quote!(+ std::mem::size_of::<#ty>()));
+    let keyed_len = keyed.iter().map(
+        |((_, _, ty), _)| quote!(+ 2 * std::mem::size_of::<u16>() + std::mem::size_of::<#ty>()),
+    );
+    let element_num = usize::from(cmd.is_some()) + plain.len() + 3 * keyed.len();
+    let impl_len = (!variable_len).then(|| {
+        quote! {
+            impl Len for #id {
+                const LEN: u16 = (#cmd_len #(#plain_len)* #(#keyed_len)*) as u16;
+            }
+        }
+    });
+
+    let serialize_cmd = cmd
+        .as_ref()
+        .map(|cmd| quote!(tuple.serialize_element(&#cmd)?;));
+    let serialize_plain = plain
+        .iter()
+        .map(|(member, _, _)| quote!(tuple.serialize_element(&self.#member)?;));
+    let serialize_keyed = keyed.iter().map(|((member, _, ty), key)| {
+        quote! {
+            tuple.serialize_element(&((#key) as u16))?;
+            tuple.serialize_element(&(std::mem::size_of::<#ty>() as u16))?;
+            tuple.serialize_element(&self.#member)?;
+        }
+    });
+
+    let deserialize_cmd = cmd.as_ref().map(|cmd| {
+        quote! {
+            let cmd: Cmd = next_element(&mut seq, &mut index, &self)?;
+            if cmd != #cmd {
+                return Err(serde::de::Error::custom(format!(
+                    "Expected command {:?}, found {:?}",
+                    #cmd,
+                    cmd
+                )));
+            }
+        }
+    });
+    let deserialize_plain = plain.iter().map(|(_, binding, ty)| {
+        quote! {
+            let #binding: #ty = next_element(&mut seq, &mut index, &self)?;
+        }
+    });
+    let declare_keyed = keyed
+        .iter()
+        .map(|((_, binding, ty), _)| quote!(let mut #binding: Option<#ty> = None;));
+    let match_keyed = keyed.iter().map(|((_, binding, ty), key)| {
+        quote! {
+            if key == ((#key) as u16)
+                && len as usize == std::mem::size_of::<#ty>()
+                && #binding.is_none()
+            {
+                #binding = Some(next_element(&mut seq, &mut index, &self)?);
+                continue;
+            }
+        }
+    });
+    let unwrap_keyed = keyed.iter().map(|((_, binding, _), key)| {
+        quote! {
+            let Some(#binding) = #binding else {
+                return Err(serde::de::Error::custom(format!(
+                    "Missing key {}", (#key) as u16
+                )));
+            };
+        }
+    });
+    let keyed_num = keyed.len();
+    let read_keyed = (!keyed.is_empty()).then(|| {
+        quote! {
+            for _ in 0..#keyed_num {
+                let key: u16 = next_element(&mut seq, &mut index, &self)?;
+                let len: u16 = next_element(&mut seq, &mut index, &self)?;
+                #(#match_keyed)*
+                return Err(serde::de::Error::custom(format!(
+                    "Invalid key {} of length {}",
+                    key, len
+                )));
+            }
+        }
+    });
+    let bindings = plain
+        .iter()
+        .chain(keyed.iter().map(|(field, _)| field))
+        .map(|(member, binding, _)| quote!(#member: #binding));
+    let construct = quote!(#id { #(#bindings),* });
+
+    Ok(quote! {
+        #impl_len
+
+        impl serde::Serialize for #id {
+            fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::SerializeTuple;
+
+                let mut tuple = serializer.serialize_tuple(#element_num)?;
+                #serialize_cmd
+                #(#serialize_plain)*
+                #(#serialize_keyed)*
+                tuple.end()
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for #id {
+            fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct SegmentVisitor;
+
+                fn next_element<'de, A, T>(
+                    seq: &mut A,
+                    index: &mut usize,
+                    expected: &SegmentVisitor,
+                ) -> std::result::Result<T, A::Error>
+                where
+                    A: serde::de::SeqAccess<'de>,
+                    T: serde::Deserialize<'de>,
+                {
+                    let element = seq
+                        .next_element()?
+                        .ok_or_else(|| serde::de::Error::invalid_length(*index, expected))?;
+                    *index += 1;
+                    Ok(element)
+                }
+
+                impl<'de> serde::de::Visitor<'de> for SegmentVisitor {
+                    type Value = #id;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        write!(formatter, "{} elements of {}", #element_num, stringify!(#id))
+                    }
+
+                    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<#id, A::Error>
+                    where
+                        A: serde::de::SeqAccess<'de>,
+                    {
+                        let mut index = 0usize;
+                        #deserialize_cmd
+                        #(#deserialize_plain)*
+                        #(#declare_keyed)*
+                        #read_keyed
+                        #(#unwrap_keyed)*
+                        Ok(#construct)
+                    }
+                }
+
+                deserializer.deserialize_tuple(#element_num, SegmentVisitor)
+            }
+        }
+    })
+}
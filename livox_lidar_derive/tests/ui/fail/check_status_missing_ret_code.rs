@@ -0,0 +1,8 @@
+use livox_lidar_derive::CheckStatus;
+
+#[derive(CheckStatus)]
+struct Resp {
+    status: u8,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use livox_lidar_derive::CheckStatus;
+
+#[derive(CheckStatus)]
+struct Resp {
+    ret_code: u16,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use livox_lidar_derive::GetCmd;
+use livox_lidar_rs::lidar_frame::frames::Cmd;
+
+#[derive(GetCmd)]
+struct Req {
+    command: Cmd,
+}
+
+fn main() {}
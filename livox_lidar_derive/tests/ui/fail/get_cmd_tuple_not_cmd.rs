@@ -0,0 +1,7 @@
+use livox_lidar_derive::GetCmd;
+use livox_lidar_rs::lidar_frame::frames::Cmd;
+
+#[derive(GetCmd)]
+struct Req(u8, Cmd);
+
+fn main() {}
@@ -0,0 +1,8 @@
+use livox_lidar_derive::Len;
+
+#[derive(Len)]
+enum Mode {
+    Normal,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use livox_lidar_derive::LivoxCommand;
+use livox_lidar_rs::lidar_frame::frames::Cmd;
+
+#[derive(LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x20, response = CommonResp)]
+struct Req {
+    cmd: Cmd,
+    mode: u8,
+}
+
+fn main() {}
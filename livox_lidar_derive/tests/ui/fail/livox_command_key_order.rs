@@ -0,0 +1,11 @@
+use livox_lidar_derive::LivoxCommand;
+
+#[derive(LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x20, response = CommonResp)]
+struct Req {
+    #[livox(key = 0x01)]
+    on: bool,
+    mode: u8,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use livox_lidar_derive::LivoxCommand;
+
+#[derive(LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x20)]
+struct Req {
+    mode: u8,
+}
+
+fn main() {}
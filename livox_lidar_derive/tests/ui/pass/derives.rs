@@ -0,0 +1,78 @@
+use livox_lidar_derive::{CheckStatus, GetCmd, KeyValue, Len, LivoxCommand};
+use livox_lidar_rs::error::{LivoxError, Result};
+use livox_lidar_rs::lidar_frame::frames::{self, CheckStatus, Cmd, GetCmd, Len, LivoxCommand};
+
+#[derive(CheckStatus, Len, serde::Deserialize)]
+struct Resp {
+    ret_code: u8,
+    value: u16,
+}
+
+#[derive(GetCmd)]
+struct Named {
+    value: u8,
+    cmd: Cmd,
+}
+
+#[derive(GetCmd)]
+struct Tuple(frames::Cmd, u8);
+
+#[derive(LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x20, response = Resp)]
+struct Command {
+    mode: u8,
+    #[livox(key = 0x01)]
+    on: bool,
+}
+
+#[derive(LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x21, response = Resp)]
+struct Unit;
+
+#[derive(KeyValue, CheckStatus)]
+struct KeyedResp {
+    ret_code: u8,
+    #[livox(key = 0x02)]
+    mode: u16,
+}
+
+fn main() {
+    assert_eq!(Resp::LEN, 3);
+    assert!(Resp {
+        ret_code: 0,
+        value: 0
+    }
+    .check_status(Command::CMD)
+    .is_ok());
+    assert!(matches!(
+        Resp {
+            ret_code: 1,
+            value: 0
+        }
+        .check_status(Command::CMD),
+        Err(LivoxError::DeviceRejected { ret_code: 1, .. })
+    ));
+    let _: Result<()> = Ok(());
+
+    assert_eq!(
+        Named {
+            value: 0,
+            cmd: Cmd::new(0, 1)
+        }
+        .cmd(),
+        Cmd::new(0, 1)
+    );
+    assert_eq!(Tuple(Cmd::new(0, 2), 0).cmd(), Cmd::new(0, 2));
+
+    assert_eq!(Command { mode: 0, on: true }.cmd(), Cmd::new(0x01, 0x20));
+    assert_eq!(Command::LEN, 2 + 1 + 4 + 1);
+    assert_eq!(Unit.cmd(), Cmd::new(0x01, 0x21));
+    assert_eq!(Unit::LEN, 2);
+    assert_eq!(KeyedResp::LEN, 1 + 4 + 2);
+    assert!(KeyedResp {
+        ret_code: 0,
+        mode: 1
+    }
+    .check_status(Unit::CMD)
+    .is_ok());
+}
@@ -0,0 +1,272 @@
+use crate::cloud::{CloudPoint, PointInterval};
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::frames::{PointPacket, Points};
+use crate::receiver::PointReceiver;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How packets are integrated into frames, durations are measured in lidar time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Integration {
+    /// consecutive frames, each spanning this long
+    TimeWindow(Duration),
+    /// consecutive frames, each holding this many points
+    PointCount(usize),
+    /// a frame of the points received within the last `window` every `step`,
+    /// older points decay out of the frame
+    Sliding { window: Duration, step: Duration },
+}
+
+/// Points integrated from consecutive packets
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// lidar time of the first packet in frame
+    pub start: u64,
+    /// lidar time of the last packet in frame
+    pub end: u64,
+    pub points: Vec<CloudPoint>,
+}
+
+/// Integrates parsed packets into frames, since a single packet of a
+/// non-repetitive scan covers too little of the field of view to be useful
+#[derive(Debug)]
+pub struct FrameAccumulator {
+    integration: Integration,
+    /// timestamp and returns of every packet not yet emitted, or still in sliding window
+    packets: VecDeque<(u64, Vec<CloudPoint>)>,
+    /// start of the current time window, or time the last sliding frame was emitted
+    mark: Option<u64>,
+    point_interval: PointInterval,
+}
+
+impl FrameAccumulator {
+    pub fn new(integration: Integration) -> Result<Self> {
+        let valid = match integration {
+            Integration::TimeWindow(window) => !window.is_zero(),
+            Integration::PointCount(count) => count > 0,
+            Integration::Sliding { window, .. } => !window.is_zero(),
+        };
+        if !valid {
+            return Err(LivoxError::InvalidArgument(format!(
+                "Empty frame integration: {:?}",
+                integration
+            )));
+        }
+        Ok(FrameAccumulator {
+            integration,
+            packets: VecDeque::new(),
+            mark: None,
+            point_interval: PointInterval::default(),
+        })
+    }
+
+    /// integrate packet, returning frames it completed, IMU packets are ignored
+    pub fn push(&mut self, packet: &PointPacket<'_>) -> Vec<Frame> {
+        if let Points::Imu(_) = packet.points {
+            return Vec::new();
+        }
+        let timestamp = packet
+            .header
+            .lidar_time()
+            .unwrap_or(packet.header.timestamp);
+        let points = packet.cloud_points(self.point_interval.update(packet));
+        let mut frames = Vec::new();
+
+        // lidar clock went backwards, after a reboot or a time sync
+        if self
+            .packets
+            .back()
+            .is_some_and(|(last, _)| timestamp < *last)
+        {
+            match self.integration {
+                Integration::Sliding { .. } => self.packets.clear(),
+                _ => frames.extend(self.take(usize::MAX)),
+            }
+            self.mark = None;
+        }
+
+        match self.integration {
+            Integration::TimeWindow(window) => {
+                let window = window.as_nanos() as u64;
+                let mark = *self.mark.get_or_insert(timestamp);
+                if timestamp - mark >= window {
+                    frames.extend(self.take(usize::MAX));
+                    // windows without any packet are skipped
+                    self.mark = Some(timestamp - (timestamp - mark) % window);
+                }
+                self.packets.push_back((timestamp, points));
+            }
+            Integration::PointCount(count) => {
+                self.packets.push_back((timestamp, points));
+                while self.len() >= count {
+                    frames.extend(self.take(count));
+                }
+            }
+            Integration::Sliding { window, step } => {
+                self.packets.push_back((timestamp, points));
+                let window = window.as_nanos() as u64;
+                while self
+                    .packets
+                    .front()
+                    .is_some_and(|(start, _)| timestamp - start >= window)
+                {
+                    self.packets.pop_front();
+                }
+                let mark = *self.mark.get_or_insert(timestamp);
+                if timestamp - mark >= step.as_nanos() as u64 {
+                    frames.extend(self.current());
+                    self.mark = Some(timestamp);
+                }
+            }
+        }
+        frames
+    }
+
+    /// points collected so far, the last `window` of points for a sliding window
+    pub fn current(&self) -> Option<Frame> {
+        let (start, _) = self.packets.front()?;
+        let (end, _) = self.packets.back()?;
+        Some(Frame {
+            start: *start,
+            end: *end,
+            points: self
+                .packets
+                .iter()
+                .flat_map(|(_, points)| points.iter().copied())
+                .collect(),
+        })
+    }
+
+    /// integrate every packet received by `receiver`, calling `on_frame` with each frame completed
+    pub fn attach<F>(mut self, receiver: &PointReceiver, mut on_frame: F)
+    where
+        F: FnMut(&Frame) + Send + 'static,
+    {
+        receiver.subscribe(move |packet| self.push(packet).iter().for_each(&mut on_frame));
+    }
+
+    fn len(&self) -> usize {
+        self.packets.iter().map(|(_, points)| points.len()).sum()
+    }
+
+    /// remove up to `count` points from the front as a frame, None if nothing collected
+    fn take(&mut self, count: usize) -> Option<Frame> {
+        let (start, _) = *self.packets.front()?;
+        let mut frame = Frame {
+            start,
+            end: start,
+            points: Vec::new(),
+        };
+        while let Some((timestamp, points)) = self.packets.front_mut() {
+            let wanted = count - frame.points.len();
+            if wanted == 0 {
+                break;
+            }
+            frame.end = *timestamp;
+            if points.len() > wanted {
+                frame.points.extend(points.drain(..wanted));
+                break;
+            }
+            frame.points.append(points);
+            self.packets.pop_front();
+        }
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lidar_frame::frames::{
+        deserialize_data, serialize_data, CartesianPoint, DataFrame, ImuPoint,
+    };
+
+    /// header of packet sent 10 ms after the one of previous `index`
+    fn header(index: u64) -> DataFrame {
+        DataFrame {
+            version: 5,
+            slot_id: 1,
+            lidar_id: 1,
+            reserved: 0,
+            status_code: 0,
+            timestamp_type: 0,
+            data_type: 0,
+            timestamp: index * 10_000_000,
+        }
+    }
+
+    fn datagram(index: u64, count: usize) -> Vec<u8> {
+        let point = CartesianPoint {
+            x: 1000,
+            y: 0,
+            z: 0,
+            reflectivity: index as u8,
+            tag: 0,
+        };
+        serialize_data(&header(index), &vec![point; count]).unwrap()
+    }
+
+    fn push(accumulator: &mut FrameAccumulator, index: u64, count: usize) -> Vec<Frame> {
+        accumulator.push(&deserialize_data(&datagram(index, count)).unwrap())
+    }
+
+    #[test]
+    fn test_time_window_and_point_count() {
+        let mut accumulator =
+            FrameAccumulator::new(Integration::TimeWindow(Duration::from_millis(100))).unwrap();
+        let frames: Vec<_> = (0..25)
+            .flat_map(|index| push(&mut accumulator, index, 10))
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].start, frames[0].end), (0, 90_000_000));
+        assert_eq!(frames[1].points.len(), 100);
+        assert_eq!(
+            accumulator.current().map(|frame| frame.start),
+            Some(200_000_000)
+        );
+
+        // packets are split between frames of a fixed point count
+        let mut accumulator = FrameAccumulator::new(Integration::PointCount(25)).unwrap();
+        assert!(push(&mut accumulator, 0, 10).is_empty());
+        let imu = ImuPoint {
+            gyro_x: 0.0,
+            gyro_y: 0.0,
+            gyro_z: 0.0,
+            acc_x: 0.0,
+            acc_y: 0.0,
+            acc_z: 1.0,
+        };
+        let imu = serialize_data(&header(1), &[imu]).unwrap();
+        assert!(accumulator
+            .push(&deserialize_data(&imu).unwrap())
+            .is_empty());
+        let frames = push(&mut accumulator, 1, 60);
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].start, frames[0].end), (0, 10_000_000));
+        assert_eq!(frames[1].points.len(), 25);
+        assert_eq!(accumulator.current().unwrap().points.len(), 20);
+        assert!(FrameAccumulator::new(Integration::PointCount(0)).is_err());
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let mut accumulator = FrameAccumulator::new(Integration::Sliding {
+            window: Duration::from_millis(200),
+            step: Duration::from_millis(50),
+        })
+        .unwrap();
+        let frames: Vec<_> = (0..=30)
+            .flat_map(|index| push(&mut accumulator, index, 10))
+            .collect();
+        assert_eq!(frames.len(), 6);
+        let last = frames.last().unwrap();
+        assert_eq!((last.start, last.end), (110_000_000, 300_000_000));
+        assert_eq!(last.points.len(), 200);
+        assert_eq!(last.points[0].reflectivity, 11);
+
+        // lidar clock going backwards restarts the window
+        assert!(push(&mut accumulator, 1, 10).is_empty());
+        assert_eq!(accumulator.current().unwrap().points.len(), 10);
+    }
+}
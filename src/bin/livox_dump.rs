@@ -0,0 +1,160 @@
+//! Dump a few seconds of point cloud into a PCD or PLY file, from a live lidar or a recording
+//!
+//! usage: livox_dump <seconds> <output.pcd|output.ply> [config.toml|recording.lvx|capture.pcap] [--ascii]
+use env_logger::{Builder, Target};
+use livox_lidar_rs::cloud::{CloudPoint, PointInterval};
+use livox_lidar_rs::connection::ConnectionSupervisor;
+use livox_lidar_rs::export::{save, CloudFormat, Encoding};
+use livox_lidar_rs::lidar_frame::cfg::LidarConfig;
+use livox_lidar_rs::lidar_frame::frames::{deserialize_data, packet_len, PointPacket, Points};
+use livox_lidar_rs::lvx::LvxReader;
+use livox_lidar_rs::pcap::PcapReader;
+use livox_lidar_rs::receiver::PointReceiver;
+use log::{info, warn};
+use std::net::UdpSocket;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+const USAGE: &str = "usage: livox_dump <seconds> <output.pcd|output.ply> [config.toml|recording.lvx|capture.pcap] [--ascii]";
+
+/// Points collected until they span the requested duration of lidar time
+struct Dump {
+    duration: u64,
+    started: Option<u64>,
+    points: Vec<CloudPoint>,
+    point_interval: PointInterval,
+}
+
+impl Dump {
+    fn new(duration: Duration) -> Self {
+        Dump {
+            duration: duration.as_nanos() as u64,
+            started: None,
+            points: Vec::new(),
+            point_interval: PointInterval::default(),
+        }
+    }
+
+    /// add points of packet, true once enough were collected
+    fn push(&mut self, packet: &PointPacket<'_>) -> bool {
+        if let Points::Imu(_) = packet.points {
+            return false;
+        }
+        let timestamp = packet
+            .header
+            .lidar_time()
+            .unwrap_or(packet.header.timestamp);
+        let started = *self.started.get_or_insert(timestamp);
+        if timestamp.saturating_sub(started) >= self.duration {
+            return true;
+        }
+        let point_interval = self.point_interval.update(packet);
+        packet.extend_cloud(&mut self.points, point_interval);
+        false
+    }
+
+    /// add datagram of a recording, anything but a point cloud packet is skipped
+    fn push_datagram(&mut self, datagram: &[u8]) -> bool {
+        match deserialize_data(datagram) {
+            Ok(packet) if packet_len(packet.header.data_type) == Some(datagram.len()) => {
+                self.push(&packet)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn dump_lvx(path: &str, dump: &mut Dump) -> anyhow::Result<()> {
+    let mut reader = LvxReader::open(path)?;
+    while let Some(packet) = reader.next_packet()? {
+        if dump.push_datagram(&packet.datagram) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn dump_pcap(path: &str, dump: &mut Dump) -> anyhow::Result<()> {
+    let mut reader = PcapReader::open(path)?;
+    while let Some(datagram) = reader.next_datagram()? {
+        if dump.push_datagram(&datagram.payload) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn dump_live(config: LidarConfig, dump: &mut Dump) -> anyhow::Result<()> {
+    let data_socket = UdpSocket::bind(config.data_addr())?;
+    let supervisor = ConnectionSupervisor::connect(config)?;
+    let point_receiver = PointReceiver::launch(data_socket)?;
+    let (point_sender, points) = mpsc::channel();
+    point_receiver.subscribe(move |packet| {
+        let _ = point_sender.send((packet.header, packet.points.as_bytes().to_vec()));
+    });
+    supervisor.start_sampling();
+
+    let (sigint_sender, sigint_receiver) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = sigint_sender.send(());
+    })?;
+    while sigint_receiver.try_recv().is_err() {
+        let Ok((header, bytes)) = points.recv_timeout(Duration::from_millis(100)) else {
+            continue;
+        };
+        let packet = PointPacket {
+            header,
+            points: Points::new(header.data_type, &bytes)?,
+        };
+        if dump.push(&packet) {
+            break;
+        }
+    }
+
+    if let Err(e) = supervisor.terminate() {
+        warn!("error occurred when terminating supervisor: {}", e);
+    }
+    Ok(point_receiver.terminate()?)
+}
+
+fn main() -> anyhow::Result<()> {
+    Builder::from_default_env().target(Target::Stdout).init();
+
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let encoding = match args.iter().position(|arg| arg == "--ascii") {
+        Some(index) => {
+            args.remove(index);
+            Encoding::Ascii
+        }
+        None => Encoding::Binary,
+    };
+    let (Some(seconds), Some(output)) = (args.first(), args.get(1)) else {
+        return Err(anyhow::anyhow!(USAGE));
+    };
+    // negative, NaN or overflowing seconds make no duration
+    let Some(duration) = seconds
+        .parse()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+    else {
+        return Err(anyhow::anyhow!(USAGE));
+    };
+    let format = CloudFormat::from_path(output, encoding)?;
+
+    let mut dump = Dump::new(duration);
+    let source = args.get(2);
+    let extension = source
+        .and_then(|source| Path::new(source).extension())
+        .and_then(|extension| extension.to_str());
+    match (source, extension) {
+        (Some(path), Some("lvx")) => dump_lvx(path, &mut dump)?,
+        (Some(path), Some("pcap" | "pcapng")) => dump_pcap(path, &mut dump)?,
+        (Some(path), _) => dump_live(LidarConfig::from_toml_file(path)?, &mut dump)?,
+        (None, _) => dump_live(LidarConfig::default(), &mut dump)?,
+    }
+
+    save(output, &dump.points, format)?;
+    info!("dumped {} points into {} ✅", dump.points.len(), output);
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! Simulated lidar on loopback, run the driver with `host_ip = "127.0.0.1"` to connect to it
+use env_logger::{Builder, Target};
+use livox_lidar_rs::simulator::{Simulator, SimulatorConfig};
+use log::info;
+use std::sync::mpsc;
+
+fn main() -> anyhow::Result<()> {
+    Builder::from_default_env().target(Target::Stdout).init();
+
+    info!("livox lidar simulator in Rust 🚀");
+
+    // optional broadcast code as the first argument
+    let mut config = SimulatorConfig::default();
+    if let Some(broadcast_code) = std::env::args().nth(1) {
+        config.broadcast_code = broadcast_code;
+    }
+
+    let simulator = Simulator::launch(config)?;
+
+    let (sigint_sender, sigint_receiver) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = sigint_sender.send(());
+    })?;
+    sigint_receiver.recv()?;
+
+    info!("received SIGINT, simulator terminating...");
+    Ok(simulator.terminate()?)
+}
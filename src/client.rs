@@ -0,0 +1,532 @@
+use crate::error::{LivoxError, Result};
+use crate::event::{EventBus, LidarEvent};
+use crate::health::LidarHealth;
+use crate::lidar_frame::cfg::{Extrinsics, LidarConfig};
+use crate::lidar_frame::frames::{
+    decode_response, deserialize_frame, ChangeCoordinateReq, Cmd, CmdType, CommonResp,
+    ControlFrame, CoordinateType, DeviceInfoResp, FanControlReq, GetFanStateResp,
+    GetImuPushFrequencyResp, GetReturnModeResp, HeartbeatResp, ImuPushFrequency, IpInfoResp,
+    LidarMode, LivoxCommand, ModeSwitchReq, RainFogSuppressionReq, ReadFlashResp,
+    ReadOuterParametersResp, RebootReq, ReturnMode, SetImuPushFrequency, SetReturnMode,
+    WriteFlashReq, WriteFlashResp, WriteOuterParameters, ABNORMAL_STATUS_CMD, DEVICE_INFO_REQ,
+    DISCONNECT_REQ, GET_FAN_STATE, GET_IMU_PUSH_FREQUENCY, GET_RETURN_MODE, HEARTBEAT_REQ,
+    IP_INFO_REQ, READ_FLASH_REQ, READ_OUTER_PARAMETERS, SAMPLE_END_REQ, SAMPLE_START_REQ,
+};
+use crate::pcap::Capture;
+use crate::receiver::WorkerHandle;
+use crate::time_sync::utc_sync_req;
+use log::{debug, info, log_enabled, warn};
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Largest control datagram exchanged with lidar
+const FRAME_BUF_LEN: usize = 1024;
+
+/// Command waiting for response, response body is copied in once received
+struct Pending {
+    cmd: Cmd,
+    resp: Option<(usize, [u8; FRAME_BUF_LEN])>,
+}
+
+/// Commands waiting for response keyed by sequence number, slots are reused
+/// so that a command round trip does not touch the heap once warmed up
+#[derive(Default)]
+struct InFlight {
+    pending: Mutex<HashMap<u16, Pending>>,
+    ready: Condvar,
+}
+
+/// How long to wait for a response and how many times to retransmit on timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPolicy {
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+/// Client driving a single lidar through its command port
+pub struct LivoxClient {
+    config: LidarConfig,
+    lidar_addr: SocketAddr,
+    local_addr: SocketAddr,
+    control_socket: UdpSocket,
+    /// every datagram on control socket is written to it if set
+    capture: Arc<Mutex<Option<Capture>>>,
+    seq_ref: Mutex<u16>,
+    in_flight: Arc<InFlight>,
+    policies: Mutex<HashMap<Cmd, CommandPolicy>>,
+    events: EventBus,
+    term_sender: mpsc::Sender<()>,
+    handle: Mutex<Option<WorkerHandle>>,
+}
+
+/// hand response over to the command waiting on its sequence number
+fn dispatch_ack(in_flight: &InFlight, seq_num: u16, cmd: Cmd, frame: &[u8]) {
+    if log_enabled!(log::Level::Debug) {
+        debug!("command response on: {:?}, seq: {}", cmd, seq_num);
+    }
+
+    match in_flight.pending.lock().unwrap().get_mut(&seq_num) {
+        Some(pending) if pending.cmd == cmd => {
+            let mut body = [0; FRAME_BUF_LEN];
+            body[..frame.len()].copy_from_slice(frame);
+            pending.resp = Some((frame.len(), body));
+            in_flight.ready.notify_all();
+        }
+        Some(Pending { cmd: expected, .. }) => {
+            if log_enabled!(log::Level::Warn) {
+                warn!(
+                    "response seq {} on {:?} does not match command {:?}, dropped",
+                    seq_num, cmd, expected
+                );
+            }
+        }
+        None => {
+            if log_enabled!(log::Level::Warn) {
+                warn!("unexpected response seq {} on {:?}, dropped", seq_num, cmd);
+            }
+        }
+    }
+}
+
+/// decode message pushed by lidar and publish it as event
+fn dispatch_msg(events: &EventBus, cmd: Cmd, frame: &[u8]) {
+    if log_enabled!(log::Level::Debug) {
+        debug!("message pushed on: {:?}", cmd);
+    }
+
+    let event = match cmd {
+        ABNORMAL_STATUS_CMD => match frame.try_into() {
+            Ok(status_code) => LidarEvent::AbnormalStatus(LidarHealth::from_status_code(
+                u32::from_le_bytes(status_code),
+            )),
+            Err(_) => {
+                if log_enabled!(log::Level::Warn) {
+                    warn!("abnormal status message of {} bytes, dropped", frame.len());
+                }
+                return;
+            }
+        },
+        _ => LidarEvent::Message {
+            cmd,
+            payload: frame.to_vec(),
+        },
+    };
+    if log_enabled!(log::Level::Warn) {
+        if let LidarEvent::AbnormalStatus(health) = event {
+            warn!("lidar reported abnormal status: {:?}", health);
+        }
+    }
+    events.publish(event);
+}
+
+impl LivoxClient {
+    /// bind command port given in config and connect to lidar at `lidar_addr`
+    pub fn connect(config: LidarConfig, lidar_addr: SocketAddr) -> Result<Self> {
+        let control_socket = UdpSocket::bind(config.cmd_addr())?;
+        Self::new(config, lidar_addr, control_socket)
+    }
+
+    /// connect to lidar at `lidar_addr` through an already bound control socket
+    pub fn new(
+        config: LidarConfig,
+        lidar_addr: SocketAddr,
+        control_socket: UdpSocket,
+    ) -> Result<Self> {
+        Self::with_event_bus(config, lidar_addr, control_socket, EventBus::new())
+    }
+
+    /// same as [`LivoxClient::new`], publishing pushed messages to an existing event bus
+    pub fn with_event_bus(
+        config: LidarConfig,
+        lidar_addr: SocketAddr,
+        control_socket: UdpSocket,
+        events: EventBus,
+    ) -> Result<Self> {
+        control_socket.set_read_timeout(Some(Duration::from_millis(1000)))?;
+        debug!("set control socket read timeout to 1 seconds");
+
+        if let Err(e) = control_socket.connect(lidar_addr) {
+            if log_enabled!(log::Level::Warn) {
+                warn!("error occurred when connecting to lidar: {}", e);
+            }
+        }
+        let (tx, rx) = mpsc::channel();
+        let duplicated_control_socket = control_socket.try_clone()?;
+        let local_addr = control_socket.local_addr()?;
+
+        let capture: Arc<Mutex<Option<Capture>>> = Arc::new(Mutex::new(None));
+        let duplicated_capture = capture.clone();
+
+        let in_flight = Arc::new(InFlight::default());
+        let duplicated_in_flight = in_flight.clone();
+        let duplicated_events = events.clone();
+
+        // start command response receiver, receiving all response in this thread,
+        // sending ACK to the command waiting on its sequence number, and MSG to event subscribers
+        let handle: WorkerHandle = thread::spawn(move || {
+            let mut buffer = [0; FRAME_BUF_LEN];
+            loop {
+                if rx.try_recv().is_ok() {
+                    info!("received sig_term, command response receiver exiting...");
+                    return Ok(());
+                }
+                debug!("command response receiver: no sig_term received, continue...");
+                match control_socket.recv(&mut buffer) {
+                    Ok(size) => {
+                        if let Some(capture) = capture.lock().unwrap().as_ref() {
+                            capture.record(lidar_addr, local_addr, &buffer[..size]);
+                        }
+                        match deserialize_frame(&buffer[..size]) {
+                            Ok((CmdType::Ack, seq_num, cmd, frame)) => {
+                                dispatch_ack(&in_flight, seq_num, cmd, frame)
+                            }
+                            Ok((CmdType::Msg, _, cmd, frame)) => dispatch_msg(&events, cmd, frame),
+                            Ok((CmdType::Cmd, seq_num, cmd, _)) => {
+                                if log_enabled!(log::Level::Warn) {
+                                    warn!(
+                                        "unexpected command seq {} on {:?}, dropped",
+                                        seq_num, cmd
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                if log_enabled!(log::Level::Warn) {
+                                    warn!("error occurred when deserializing response: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => {}
+                    Err(e) => {
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("error occurred when receiving response: {}", e);
+                        }
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            config,
+            lidar_addr,
+            local_addr,
+            control_socket: duplicated_control_socket,
+            capture: duplicated_capture,
+            seq_ref: Mutex::new(0),
+            in_flight: duplicated_in_flight,
+            policies: Mutex::new(HashMap::new()),
+            events: duplicated_events,
+            term_sender: tx,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// execute certain command and return its response,
+    /// retransmitting with the same sequence number on timeout
+    pub fn command_execute<T>(&self, req: T) -> Result<T::Response>
+    where
+        T: LivoxCommand,
+    {
+        let policy = self.command_policy(req.cmd());
+
+        let mut seq = self.seq_ref.lock().unwrap();
+        let seq_num = *seq;
+        *seq = seq.checked_add(1).unwrap_or_default();
+        drop(seq);
+
+        let mut buffer = [0; FRAME_BUF_LEN];
+        let len = ControlFrame::new(seq_num, &req).serialize_into(&mut buffer)?;
+        let frame = &buffer[..len];
+
+        self.in_flight.pending.lock().unwrap().insert(
+            seq_num,
+            Pending {
+                cmd: req.cmd(),
+                resp: None,
+            },
+        );
+
+        let mes = (0..=policy.retries).find_map(|attempt| {
+            self.record(frame);
+            if let Err(e) = self.control_socket.send(frame) {
+                if log_enabled!(log::Level::Warn) {
+                    warn!("error occurred when sending command {:?}: {}", req.cmd(), e);
+                }
+            } else if log_enabled!(log::Level::Debug) {
+                debug!(
+                    "sent command: {:?}, seq: {}, attempt: {}",
+                    req.cmd(),
+                    seq_num,
+                    attempt
+                );
+            }
+            let pending = self.in_flight.pending.lock().unwrap();
+            let (mut pending, _) = self
+                .in_flight
+                .ready
+                .wait_timeout_while(pending, policy.timeout, |pending| {
+                    pending.get(&seq_num).is_some_and(|p| p.resp.is_none())
+                })
+                .unwrap();
+            pending.get_mut(&seq_num).and_then(|p| p.resp.take())
+        });
+        self.in_flight.pending.lock().unwrap().remove(&seq_num);
+
+        let Some(mes) = mes else {
+            return Err(LivoxError::Timeout {
+                cmd: req.cmd(),
+                attempts: policy.retries + 1,
+            });
+        };
+        let (len, body) = mes;
+        let resp: T::Response = decode_response(req.cmd(), &body[..len])?;
+
+        if log_enabled!(log::Level::Debug) {
+            debug!("command handled successfully ✅");
+        }
+
+        Ok(resp)
+    }
+
+    /// capture every datagram sent to or received from lidar from now on
+    pub fn set_capture(&self, capture: Capture) {
+        *self.capture.lock().unwrap() = Some(capture);
+    }
+
+    /// capture datagram about to be sent to lidar
+    fn record(&self, datagram: &[u8]) {
+        if let Some(capture) = self.capture.lock().unwrap().as_ref() {
+            capture.record(self.local_addr, self.lidar_addr, datagram);
+        }
+    }
+
+    /// timeout and retries of command, falls back to values given in config
+    pub fn command_policy(&self, cmd: Cmd) -> CommandPolicy {
+        self.policies
+            .lock()
+            .unwrap()
+            .get(&cmd)
+            .copied()
+            .unwrap_or(CommandPolicy {
+                timeout: self.config.command_timeout(),
+                retries: self.config.command_retries,
+            })
+    }
+
+    /// override timeout and retries of certain command
+    pub fn set_command_policy(&self, cmd: Cmd, policy: CommandPolicy) {
+        self.policies.lock().unwrap().insert(cmd, policy);
+    }
+
+    /// subscribe to messages pushed by lidar
+    pub fn subscribe_events(&self) -> mpsc::Receiver<LidarEvent> {
+        self.events.subscribe()
+    }
+
+    /// event bus messages pushed by lidar are published to, shared with other event sources
+    pub fn event_bus(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// configuration this client was created with
+    pub fn config(&self) -> &LidarConfig {
+        &self.config
+    }
+
+    /// connect to lidar, telling it where to send data
+    pub fn handshake(&self) -> Result<CommonResp> {
+        self.command_execute(self.config.handshake_req())
+    }
+
+    /// apply coordinate type, return mode and extrinsics given in config
+    pub fn apply_config(&self) -> Result<()> {
+        self.set_coordinate(self.config.coordinate_type)?;
+        self.set_return_mode(self.config.return_mode)?;
+        if let Some(extrinsics) = self.config.extrinsics {
+            self.set_extrinsics(extrinsics)?;
+        }
+        Ok(())
+    }
+
+    /// write slot id to flash unless it is already there, true if written,
+    /// lidar reports the new slot id only once rebooted
+    pub fn assign_slot_id(&self, slot_id: u8) -> Result<bool> {
+        let flash = self.read_flash()?;
+        if flash.slot_id == slot_id {
+            return Ok(false);
+        }
+        self.write_flash(WriteFlashReq::new(
+            flash.high_sensitivity,
+            flash.repetitive_scan,
+            slot_id,
+        )?)?;
+        Ok(true)
+    }
+
+    pub fn device_info(&self) -> Result<DeviceInfoResp> {
+        self.command_execute(DEVICE_INFO_REQ)
+    }
+
+    pub fn heartbeat(&self) -> Result<HeartbeatResp> {
+        self.command_execute(HEARTBEAT_REQ)
+    }
+
+    pub fn start_sampling(&self) -> Result<CommonResp> {
+        self.command_execute(SAMPLE_START_REQ)
+    }
+
+    pub fn stop_sampling(&self) -> Result<CommonResp> {
+        self.command_execute(SAMPLE_END_REQ)
+    }
+
+    pub fn set_coordinate(&self, coordinate_type: CoordinateType) -> Result<CommonResp> {
+        self.command_execute(ChangeCoordinateReq::new(coordinate_type))
+    }
+
+    pub fn set_mode(&self, mode: LidarMode) -> Result<CommonResp> {
+        self.command_execute(ModeSwitchReq::new(mode))
+    }
+
+    pub fn set_extrinsics(&self, e: Extrinsics) -> Result<CommonResp> {
+        self.command_execute(WriteOuterParameters::new(
+            e.roll, e.pitch, e.yaw, e.x, e.y, e.z,
+        ))
+    }
+
+    pub fn read_extrinsics(&self) -> Result<ReadOuterParametersResp> {
+        self.command_execute(READ_OUTER_PARAMETERS)
+    }
+
+    pub fn set_return_mode(&self, mode: ReturnMode) -> Result<CommonResp> {
+        self.command_execute(SetReturnMode::new(mode))
+    }
+
+    pub fn get_return_mode(&self) -> Result<GetReturnModeResp> {
+        self.command_execute(GET_RETURN_MODE)
+    }
+
+    pub fn set_rain_fog_suppression(&self, on: bool) -> Result<CommonResp> {
+        self.command_execute(RainFogSuppressionReq::new(on))
+    }
+
+    pub fn set_fan(&self, on: bool) -> Result<CommonResp> {
+        self.command_execute(FanControlReq::new(on))
+    }
+
+    pub fn fan_state(&self) -> Result<GetFanStateResp> {
+        self.command_execute(GET_FAN_STATE)
+    }
+
+    pub fn set_imu_push_frequency(&self, frequency: ImuPushFrequency) -> Result<CommonResp> {
+        self.command_execute(SetImuPushFrequency::new(frequency))
+    }
+
+    pub fn imu_push_frequency(&self) -> Result<GetImuPushFrequencyResp> {
+        self.command_execute(GET_IMU_PUSH_FREQUENCY)
+    }
+
+    /// write high sensitivity, scan pattern and slot id, kept across reboots
+    pub fn write_flash(&self, req: WriteFlashReq) -> Result<WriteFlashResp> {
+        self.command_execute(req)
+    }
+
+    /// read high sensitivity, scan pattern and slot id
+    pub fn read_flash(&self) -> Result<ReadFlashResp> {
+        self.command_execute(READ_FLASH_REQ)
+    }
+
+    /// set lidar clock to UTC `time`, lidar applies it on the next PPS edge
+    pub fn sync_utc(&self, time: SystemTime) -> Result<CommonResp> {
+        self.command_execute(utc_sync_req(time)?)
+    }
+
+    pub fn ip_info(&self) -> Result<IpInfoResp> {
+        self.command_execute(IP_INFO_REQ)
+    }
+
+    /// reboot lidar after `timeout` milliseconds
+    pub fn reboot(&self, timeout: u16) -> Result<CommonResp> {
+        self.command_execute(RebootReq::new(timeout))
+    }
+
+    pub fn disconnect(&self) -> Result<CommonResp> {
+        self.command_execute(DISCONNECT_REQ)
+    }
+
+    /// address of lidar this client sends commands to
+    pub fn lidar_addr(&self) -> SocketAddr {
+        self.lidar_addr
+    }
+
+    /// stop command response receiver and wait for it to exit,
+    /// no command can be executed afterwards
+    pub fn terminate(&self) -> Result<()> {
+        self.term_sender.send(()).map_err(|e| {
+            LivoxError::Closed(format!(
+                "failed to send sig_term to command processor: {}",
+                e
+            ))
+        })?;
+        match self.handle.lock().unwrap().take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| LivoxError::WorkerPanicked("command response receiver"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lidar_frame::frames::{AbnormalStatusMsg, SampleCtrlReq};
+
+    #[test]
+    fn test_command_correlation() {
+        let lidar_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let control_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = LidarConfig {
+            command_timeout_ms: 100,
+            command_retries: 1,
+            ..Default::default()
+        };
+        let client =
+            LivoxClient::new(config, lidar_socket.local_addr().unwrap(), control_socket).unwrap();
+
+        // fake lidar answering a stale sequence number first, then the right one,
+        // a successful sample control response shares its layout with SAMPLE_END_REQ
+        let fake_lidar = thread::spawn(move || {
+            let mut buffer = [0; 1024];
+            let (size, host) = lidar_socket.recv_from(&mut buffer).unwrap();
+            let (_, seq_num, _, _) = deserialize_frame(&buffer[..size]).unwrap();
+            for seq in [seq_num.wrapping_add(7), seq_num] {
+                let frame = ControlFrame::ack(seq, &SAMPLE_END_REQ).serialize().unwrap();
+                lidar_socket.send_to(&frame, host).unwrap();
+            }
+            let msg = ControlFrame::msg(0, &AbnormalStatusMsg::new(0x40)).serialize();
+            lidar_socket.send_to(&msg.unwrap(), host).unwrap();
+        });
+
+        let events = client.subscribe_events();
+        assert_eq!(client.stop_sampling().unwrap().ret_code, 0);
+        fake_lidar.join().unwrap();
+        assert_eq!(
+            events.recv_timeout(Duration::from_secs(1)).unwrap(),
+            LidarEvent::AbnormalStatus(LidarHealth::from_status_code(0x40))
+        );
+
+        // nobody answers this time
+        assert!(matches!(
+            client.stop_sampling(),
+            Err(LivoxError::Timeout { cmd, attempts: 2 }) if cmd == SampleCtrlReq::CMD
+        ));
+        client.terminate().unwrap();
+    }
+}
@@ -0,0 +1,338 @@
+use crate::lidar_frame::frames::{
+    CartesianPoint, DataFrame, DualCartesianPoint, DualSphericalPoint, Point, PointPacket,
+    PointSlice, Points, SphericalPoint, StandardCartesianPoint, StandardSphericalPoint,
+    TripleCartesianPoint, TripleSphericalPoint,
+};
+use crate::transform::spherical_to_cartesian;
+use std::time::Duration;
+
+/// Single return of a lidar, in meters whatever layout it was sent in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloudPoint {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub reflectivity: u8,
+    /// 0 for standard data types, which carry no tag
+    pub tag: u8,
+    /// nanoseconds in clock of lidar, packet time plus offset of the point in packet,
+    /// see [`DataFrame::lidar_time`]
+    pub timestamp: u64,
+}
+
+impl CloudPoint {
+    fn cartesian(x: i32, y: i32, z: i32, reflectivity: u8, tag: u8) -> Option<Self> {
+        // lidar sends zeros for directions without return
+        if x == 0 && y == 0 && z == 0 {
+            return None;
+        }
+        Some(CloudPoint {
+            x: x as f32 / 1000.0,
+            y: y as f32 / 1000.0,
+            z: z as f32 / 1000.0,
+            reflectivity,
+            tag,
+            timestamp: 0,
+        })
+    }
+
+    fn spherical(depth: u32, zenith: u16, azimuth: u16, reflectivity: u8, tag: u8) -> Option<Self> {
+        if depth == 0 {
+            return None;
+        }
+        let [x, y, z] = spherical_to_cartesian(depth, zenith, azimuth);
+        Some(CloudPoint {
+            x: (x / 1000.0) as f32,
+            y: (y / 1000.0) as f32,
+            z: (z / 1000.0) as f32,
+            reflectivity,
+            tag,
+            timestamp: 0,
+        })
+    }
+}
+
+/// Point layout holding one or more returns of the same firing
+trait Returns: Point {
+    fn returns(&self) -> [Option<CloudPoint>; 3];
+}
+
+impl Returns for StandardCartesianPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::cartesian(self.x, self.y, self.z, self.reflectivity, 0),
+            None,
+            None,
+        ]
+    }
+}
+
+impl Returns for StandardSphericalPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::spherical(self.depth, self.zenith, self.azimuth, self.reflectivity, 0),
+            None,
+            None,
+        ]
+    }
+}
+
+impl Returns for CartesianPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::cartesian(self.x, self.y, self.z, self.reflectivity, self.tag),
+            None,
+            None,
+        ]
+    }
+}
+
+impl Returns for SphericalPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::spherical(
+                self.depth,
+                self.zenith,
+                self.azimuth,
+                self.reflectivity,
+                self.tag,
+            ),
+            None,
+            None,
+        ]
+    }
+}
+
+impl Returns for DualCartesianPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::cartesian(self.x1, self.y1, self.z1, self.reflectivity1, self.tag1),
+            CloudPoint::cartesian(self.x2, self.y2, self.z2, self.reflectivity2, self.tag2),
+            None,
+        ]
+    }
+}
+
+impl Returns for DualSphericalPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        let (zenith, azimuth) = (self.zenith, self.azimuth);
+        [
+            CloudPoint::spherical(self.depth1, zenith, azimuth, self.reflectivity1, self.tag1),
+            CloudPoint::spherical(self.depth2, zenith, azimuth, self.reflectivity2, self.tag2),
+            None,
+        ]
+    }
+}
+
+impl Returns for TripleCartesianPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        [
+            CloudPoint::cartesian(self.x1, self.y1, self.z1, self.reflectivity1, self.tag1),
+            CloudPoint::cartesian(self.x2, self.y2, self.z2, self.reflectivity2, self.tag2),
+            CloudPoint::cartesian(self.x3, self.y3, self.z3, self.reflectivity3, self.tag3),
+        ]
+    }
+}
+
+impl Returns for TripleSphericalPoint {
+    fn returns(&self) -> [Option<CloudPoint>; 3] {
+        let (zenith, azimuth) = (self.zenith, self.azimuth);
+        [
+            CloudPoint::spherical(self.depth1, zenith, azimuth, self.reflectivity1, self.tag1),
+            CloudPoint::spherical(self.depth2, zenith, azimuth, self.reflectivity2, self.tag2),
+            CloudPoint::spherical(self.depth3, zenith, azimuth, self.reflectivity3, self.tag3),
+        ]
+    }
+}
+
+/// Time between consecutive points of a lidar, measured on the lidar time between its
+/// packets, since lidar models send the same data type at different rates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PointInterval {
+    /// data type, lidar time and number of points of the last packet
+    last: Option<(u8, u64, usize)>,
+    /// shortest gap per point measured on the data type of the last packet,
+    /// packets lost in between only lengthen the gap
+    nanos: Option<u64>,
+}
+
+impl PointInterval {
+    /// measure gap to the previous packet, return interval between points of `packet`,
+    /// zero until a packet of the same data type came before
+    pub fn update(&mut self, packet: &PointPacket<'_>) -> Duration {
+        let header = &packet.header;
+        let timestamp = header.lidar_time().unwrap_or(header.timestamp);
+        if let Some((data_type, last, points)) = self.last {
+            if data_type != header.data_type {
+                self.nanos = None;
+            } else if timestamp > last && points > 0 {
+                // a clock going backwards, or jumping ahead on time sync, is no gap to measure
+                let nanos = (timestamp - last) / points as u64;
+                self.nanos = Some(self.nanos.map_or(nanos, |shortest| shortest.min(nanos)));
+            }
+        }
+        self.last = Some((header.data_type, timestamp, packet.points.len()));
+        Duration::from_nanos(self.nanos.unwrap_or(0))
+    }
+}
+
+/// append every return of points, stamped by their position in packet
+fn extend<P: Returns>(
+    cloud: &mut Vec<CloudPoint>,
+    header: &DataFrame,
+    points: &PointSlice<'_, P>,
+    point_interval: u64,
+) {
+    let start = header.lidar_time().unwrap_or(header.timestamp);
+    for (index, point) in points.iter().enumerate() {
+        let timestamp = start.saturating_add((index as u64).saturating_mul(point_interval));
+        cloud.extend(
+            point
+                .returns()
+                .into_iter()
+                .flatten()
+                .map(|point| CloudPoint { timestamp, ..point }),
+        );
+    }
+}
+
+impl PointPacket<'_> {
+    /// append every return in packet to `cloud`, skipping directions without return,
+    /// nothing for IMU packets, points are stamped `point_interval` apart, see [`PointInterval`]
+    pub fn extend_cloud(&self, cloud: &mut Vec<CloudPoint>, point_interval: Duration) {
+        let header = &self.header;
+        let interval = point_interval.as_nanos() as u64;
+        match &self.points {
+            Points::StandardCartesian(points) => extend(cloud, header, points, interval),
+            Points::StandardSpherical(points) => extend(cloud, header, points, interval),
+            Points::Cartesian(points) => extend(cloud, header, points, interval),
+            Points::Spherical(points) => extend(cloud, header, points, interval),
+            Points::DualCartesian(points) => extend(cloud, header, points, interval),
+            Points::DualSpherical(points) => extend(cloud, header, points, interval),
+            Points::TripleCartesian(points) => extend(cloud, header, points, interval),
+            Points::TripleSpherical(points) => extend(cloud, header, points, interval),
+            Points::Imu(_) => {}
+        }
+    }
+
+    /// every return in packet, see [`PointPacket::extend_cloud`]
+    pub fn cloud_points(&self, point_interval: Duration) -> Vec<CloudPoint> {
+        let mut cloud = Vec::with_capacity(self.points.len());
+        self.extend_cloud(&mut cloud, point_interval);
+        cloud
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lidar_frame::frames::{deserialize_data, serialize_data};
+
+    #[test]
+    fn test_cloud_points() {
+        let header = DataFrame {
+            version: 5,
+            slot_id: 1,
+            lidar_id: 1,
+            reserved: 0,
+            status_code: 0,
+            timestamp_type: 0,
+            data_type: 0,
+            timestamp: 1_000_000,
+        };
+        let points = [
+            DualSphericalPoint {
+                zenith: 9000,
+                azimuth: 9000,
+                depth1: 2000,
+                reflectivity1: 10,
+                tag1: 0x10,
+                depth2: 0,
+                reflectivity2: 0,
+                tag2: 0,
+            },
+            DualSphericalPoint {
+                zenith: 0,
+                azimuth: 0,
+                depth1: 1000,
+                reflectivity1: 20,
+                tag1: 0,
+                depth2: 3000,
+                reflectivity2: 30,
+                tag2: 0x01,
+            },
+        ];
+        let datagram = serialize_data(&header, &points).unwrap();
+        let cloud = deserialize_data(&datagram)
+            .unwrap()
+            .cloud_points(Duration::from_micros(10));
+
+        // second return of the first firing is missing
+        assert_eq!(cloud.len(), 3);
+        assert!(cloud[0].x.abs() < 1e-6 && (cloud[0].y - 2.0).abs() < 1e-6);
+        assert_eq!((cloud[0].reflectivity, cloud[0].tag), (10, 0x10));
+        assert_eq!(cloud[0].timestamp, 1_000_000);
+        assert_eq!((cloud[1].z, cloud[2].z), (1.0, 3.0));
+        assert_eq!(cloud[2].timestamp, 1_000_000 + 10_000);
+    }
+
+    #[test]
+    fn test_point_interval() {
+        let header = |timestamp| DataFrame {
+            version: 5,
+            slot_id: 1,
+            lidar_id: 1,
+            reserved: 0,
+            status_code: 0,
+            timestamp_type: 0,
+            data_type: 0,
+            timestamp,
+        };
+        let cartesian = |timestamp| {
+            let point = CartesianPoint {
+                x: 1000,
+                y: 0,
+                z: 0,
+                reflectivity: 0,
+                tag: 0,
+            };
+            serialize_data(
+                &header(timestamp),
+                &[point; CartesianPoint::POINTS_PER_PACKET],
+            )
+            .unwrap()
+        };
+        let spherical = |timestamp| {
+            let point = SphericalPoint {
+                depth: 1000,
+                zenith: 0,
+                azimuth: 0,
+                reflectivity: 0,
+                tag: 0,
+            };
+            serialize_data(
+                &header(timestamp),
+                &[point; SphericalPoint::POINTS_PER_PACKET],
+            )
+            .unwrap()
+        };
+        let mut interval = PointInterval::default();
+        let mut update = |datagram: Vec<u8>| interval.update(&deserialize_data(&datagram).unwrap());
+
+        // a Mid-70 sends 100 000 extended points per second, 96 of them in each packet
+        assert_eq!(update(cartesian(0)), Duration::ZERO);
+        assert_eq!(update(cartesian(960_000)), Duration::from_micros(10));
+        // lost packet and clock going backwards
+        assert_eq!(update(cartesian(2_880_000)), Duration::from_micros(10));
+        assert_eq!(update(cartesian(0)), Duration::from_micros(10));
+        // another data type is measured anew
+        assert_eq!(update(spherical(400_000)), Duration::ZERO);
+
+        let cloud = deserialize_data(&cartesian(u64::MAX - 1))
+            .unwrap()
+            .cloud_points(Duration::from_micros(10));
+        assert_eq!(cloud[0].timestamp, u64::MAX - 1);
+        assert_eq!(cloud[95].timestamp, u64::MAX);
+    }
+}
@@ -0,0 +1,429 @@
+use crate::client::{CommandPolicy, LivoxClient};
+use crate::discovery::{discover_on, Discover, DiscoveredDevice};
+use crate::error::{LivoxError, Result};
+use crate::event::{EventBus, LidarEvent};
+use crate::lidar_frame::cfg::LidarConfig;
+use crate::lidar_frame::frames::{GetCmd, HEARTBEAT_REQ};
+use crate::pcap::Capture;
+use crate::receiver::WorkerHandle;
+use log::{debug, info, log_enabled, warn};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Working state reported by lidar in heartbeat response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkState {
+    Initializing,
+    Normal,
+    PowerSaving,
+    Standby,
+    Error,
+    Unknown(u8),
+}
+
+impl From<u8> for WorkState {
+    fn from(work_state: u8) -> Self {
+        match work_state {
+            0x00 => WorkState::Initializing,
+            0x01 => WorkState::Normal,
+            0x02 => WorkState::PowerSaving,
+            0x03 => WorkState::Standby,
+            0x04 => WorkState::Error,
+            _ => WorkState::Unknown(work_state),
+        }
+    }
+}
+
+/// State of the connection kept by [`ConnectionSupervisor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// listening for broadcast of a matching lidar
+    Discovering,
+    /// handshaking with and configuring a discovered lidar
+    Handshaking,
+    /// heartbeats answered, lidar not sampling
+    Connected(WorkState),
+    /// heartbeats answered, lidar sampling
+    Sampling,
+    /// too many heartbeats missed in a row
+    Lost,
+    /// handshaking again with the last known lidar
+    Reconnecting,
+}
+
+/// how long to listen for broadcast in each discovery round
+const DISCOVERY_WINDOW: Duration = Duration::from_millis(1000);
+
+/// State shared between supervisor handle and its thread
+struct Shared {
+    state: Mutex<ConnectionState>,
+    client: Mutex<Option<Arc<LivoxClient>>>,
+    /// lidar found by the latest discovery
+    device: Mutex<Option<DiscoveredDevice>>,
+    /// handed to every client created from now on
+    capture: Mutex<Option<Capture>>,
+    /// whether user asked lidar to sample, restored after every reconnection
+    sampling: AtomicBool,
+    events: EventBus,
+}
+
+impl Shared {
+    fn state(&self) -> ConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    /// move to `state`, publishing an event if it differs from the current one
+    fn transition(&self, state: ConnectionState) {
+        let previous = std::mem::replace(&mut *self.state.lock().unwrap(), state);
+        if previous != state {
+            info!("lidar connection: {:?} -> {:?}", previous, state);
+            self.events.publish(LidarEvent::StateChanged(state));
+        }
+    }
+
+    fn client(&self) -> Option<Arc<LivoxClient>> {
+        self.client.lock().unwrap().clone()
+    }
+
+    /// drop current client and stop its response receiver
+    fn release_client(&self) {
+        if let Some(client) = self.client.lock().unwrap().take() {
+            if let Err(e) = client.terminate() {
+                if log_enabled!(log::Level::Warn) {
+                    warn!("error occurred when terminating client: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps a lidar connected: discovers it, handshakes, watches heartbeats,
+/// and reconnects and restarts sampling when lidar reboots or the link drops
+pub struct ConnectionSupervisor {
+    shared: Arc<Shared>,
+    term_sender: mpsc::Sender<()>,
+    handle: WorkerHandle,
+}
+
+impl ConnectionSupervisor {
+    /// bind sockets given in config and start supervising
+    pub fn connect(config: LidarConfig) -> Result<Self> {
+        let broadcast_socket = UdpSocket::bind(config.broadcast_addr())?;
+        let control_socket = UdpSocket::bind(config.cmd_addr())?;
+        Self::launch(config, broadcast_socket, control_socket)
+    }
+
+    /// start supervising through already bound broadcast and control sockets
+    pub fn launch(
+        config: LidarConfig,
+        broadcast_socket: UdpSocket,
+        control_socket: UdpSocket,
+    ) -> Result<Self> {
+        let discover: Discover = Box::new(move |window| discover_on(&broadcast_socket, window));
+        Self::launch_with(config, discover, control_socket)
+    }
+
+    /// start supervising through already bound control socket, finding lidar with `discover`
+    /// instead of a broadcast socket of its own
+    pub fn launch_with(
+        config: LidarConfig,
+        mut discover: Discover,
+        control_socket: UdpSocket,
+    ) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(ConnectionState::Discovering),
+            client: Mutex::new(None),
+            device: Mutex::new(None),
+            capture: Mutex::new(None),
+            sampling: AtomicBool::new(false),
+            events: EventBus::new(),
+        });
+        let duplicated_shared = shared.clone();
+        let (tx, rx) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let result = supervise(
+                &config,
+                &mut discover,
+                &control_socket,
+                &duplicated_shared,
+                &rx,
+            );
+            shutdown(&duplicated_shared);
+            result
+        });
+
+        Ok(Self {
+            shared,
+            term_sender: tx,
+            handle,
+        })
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.shared.state()
+    }
+
+    /// client of the current connection, gone once connection is lost
+    pub fn client(&self) -> Option<Arc<LivoxClient>> {
+        self.shared.client()
+    }
+
+    /// lidar found by the latest discovery, None until one is found
+    pub fn device(&self) -> Option<DiscoveredDevice> {
+        self.shared.device.lock().unwrap().clone()
+    }
+
+    /// capture every datagram on control socket from the next connection on,
+    /// set it right after launch to capture the first handshake
+    pub fn set_capture(&self, capture: Capture) {
+        *self.shared.capture.lock().unwrap() = Some(capture);
+    }
+
+    /// subscribe to state changes and messages pushed by lidar
+    pub fn subscribe_events(&self) -> mpsc::Receiver<LidarEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// event bus state changes are published to, shared with other event sources
+    pub fn event_bus(&self) -> &EventBus {
+        &self.shared.events
+    }
+
+    /// ask lidar to sample as soon as connected, and again after every reconnection
+    pub fn start_sampling(&self) {
+        self.shared.sampling.store(true, Ordering::SeqCst);
+    }
+
+    /// ask lidar to stop sampling
+    pub fn stop_sampling(&self) {
+        self.shared.sampling.store(false, Ordering::SeqCst);
+    }
+
+    /// stop sampling, disconnect from lidar and wait for supervisor to exit
+    pub fn terminate(self) -> Result<()> {
+        self.term_sender.send(()).map_err(|e| {
+            LivoxError::Closed(format!("failed to send sig_term to supervisor: {}", e))
+        })?;
+        self.handle
+            .join()
+            .map_err(|_| LivoxError::WorkerPanicked("connection supervisor"))?
+    }
+}
+
+/// drive connection state until sig_term received
+fn supervise(
+    config: &LidarConfig,
+    discover: &mut Discover,
+    control_socket: &UdpSocket,
+    shared: &Shared,
+    term_receiver: &mpsc::Receiver<()>,
+) -> Result<()> {
+    let mut lidar_addr: Option<SocketAddr> = None;
+    let mut missed_heartbeats = 0;
+    // time host UTC was last pushed to lidar, pushed again right after every connection
+    let mut utc_synced: Option<Instant> = None;
+
+    loop {
+        if term_receiver.try_recv().is_ok() {
+            info!("supervisor received sig_term, exiting...");
+            return Ok(());
+        }
+
+        match shared.state() {
+            ConnectionState::Discovering => {
+                let devices = match discover(DISCOVERY_WINDOW) {
+                    Ok(devices) => devices,
+                    Err(e) => {
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("error occurred when discovering lidar: {}", e);
+                        }
+                        // try again next round, waking up early on sig_term
+                        match term_receiver.recv_timeout(DISCOVERY_WINDOW) {
+                            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                            _ => {
+                                info!("supervisor received sig_term, exiting...");
+                                return Ok(());
+                            }
+                        }
+                    }
+                };
+                let device = devices.into_iter().find(|device| {
+                    config
+                        .broadcast_code
+                        .as_ref()
+                        .is_none_or(|code| *code == device.broadcast_code)
+                });
+                if let Some(device) = device {
+                    info!(
+                        "found lidar {} at {} ✅",
+                        device.broadcast_code, device.addr
+                    );
+                    lidar_addr = Some(device.addr);
+                    *shared.device.lock().unwrap() = Some(device);
+                    shared.transition(ConnectionState::Handshaking);
+                }
+            }
+            ConnectionState::Handshaking | ConnectionState::Reconnecting => {
+                let Some(addr) = lidar_addr else {
+                    shared.transition(ConnectionState::Discovering);
+                    continue;
+                };
+                match establish(config, addr, control_socket, shared) {
+                    Ok(state) => {
+                        missed_heartbeats = 0;
+                        utc_synced = None;
+                        shared.transition(state);
+                    }
+                    Err(e) => {
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("failed to connect to lidar at {}: {}", addr, e);
+                        }
+                        shared.release_client();
+                        shared.transition(ConnectionState::Discovering);
+                    }
+                }
+            }
+            state @ (ConnectionState::Connected(_) | ConnectionState::Sampling) => {
+                let Some(client) = shared.client() else {
+                    shared.transition(ConnectionState::Lost);
+                    continue;
+                };
+
+                let sampling = shared.sampling.load(Ordering::SeqCst);
+                if sampling && state != ConnectionState::Sampling {
+                    match client.start_sampling() {
+                        Ok(_) => shared.transition(ConnectionState::Sampling),
+                        Err(e) => {
+                            if log_enabled!(log::Level::Warn) {
+                                warn!("error occurred when starting sampling: {}", e);
+                            }
+                        }
+                    }
+                } else if !sampling && state == ConnectionState::Sampling {
+                    match client.stop_sampling() {
+                        Ok(_) => shared.transition(ConnectionState::Connected(WorkState::Normal)),
+                        Err(e) => {
+                            if log_enabled!(log::Level::Warn) {
+                                warn!("error occurred when stopping sampling: {}", e);
+                            }
+                        }
+                    }
+                }
+
+                if let Some(interval) = config.utc_sync_interval() {
+                    if utc_synced.is_none_or(|synced| synced.elapsed() >= interval) {
+                        utc_synced = Some(Instant::now());
+                        if let Err(e) = client.sync_utc(SystemTime::now()) {
+                            if log_enabled!(log::Level::Warn) {
+                                warn!("error occurred when syncing lidar time: {}", e);
+                            }
+                        }
+                    }
+                }
+
+                // wait for next heartbeat, waking up early on sig_term
+                match term_receiver.recv_timeout(config.heartbeat_interval()) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    _ => {
+                        info!("supervisor received sig_term, exiting...");
+                        return Ok(());
+                    }
+                }
+
+                match client.heartbeat() {
+                    Ok(resp) => {
+                        missed_heartbeats = 0;
+                        if log_enabled!(log::Level::Debug) {
+                            debug!("heartbeat: {:?}", resp);
+                        }
+                        if let ConnectionState::Connected(_) = shared.state() {
+                            shared.transition(ConnectionState::Connected(resp.work_state.into()));
+                        }
+                    }
+                    // lidar answering at all means it is still there
+                    Err(e @ LivoxError::DeviceRejected { .. }) => {
+                        missed_heartbeats = 0;
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("heartbeat rejected: {}", e);
+                        }
+                    }
+                    Err(e) => {
+                        missed_heartbeats += 1;
+                        if log_enabled!(log::Level::Warn) {
+                            warn!(
+                                "heartbeat missed ({}/{}): {}",
+                                missed_heartbeats, config.max_missed_heartbeats, e
+                            );
+                        }
+                        if missed_heartbeats >= config.max_missed_heartbeats {
+                            shared.transition(ConnectionState::Lost);
+                        }
+                    }
+                }
+            }
+            ConnectionState::Lost => {
+                shared.release_client();
+                shared.transition(ConnectionState::Reconnecting);
+            }
+        }
+    }
+}
+
+/// handshake with and configure lidar at `addr`, return state of the fresh connection
+fn establish(
+    config: &LidarConfig,
+    addr: SocketAddr,
+    control_socket: &UdpSocket,
+    shared: &Shared,
+) -> Result<ConnectionState> {
+    shared.release_client();
+    let client = Arc::new(LivoxClient::with_event_bus(
+        config.clone(),
+        addr,
+        control_socket.try_clone()?,
+        shared.events.clone(),
+    )?);
+    // a heartbeat is either answered before the next one is due or counted as missed
+    client.set_command_policy(
+        HEARTBEAT_REQ.cmd(),
+        CommandPolicy {
+            timeout: config.heartbeat_interval(),
+            retries: 0,
+        },
+    );
+    if let Some(capture) = shared.capture.lock().unwrap().as_ref() {
+        client.set_capture(capture.clone());
+    }
+    *shared.client.lock().unwrap() = Some(client.clone());
+
+    debug!("trying handshake...");
+    client.handshake()?;
+    debug!("handshake success ✅");
+    client.apply_config()?;
+    debug!("lidar configured ✅");
+    let heartbeat = client.heartbeat()?;
+    info!("success connected to lidar ✅");
+
+    Ok(ConnectionState::Connected(heartbeat.work_state.into()))
+}
+
+/// stop sampling and disconnect on the way out, lidar may already be gone
+fn shutdown(shared: &Shared) {
+    if let Some(client) = shared.client() {
+        if shared.state() == ConnectionState::Sampling {
+            match client.stop_sampling() {
+                Ok(_) => info!("success end sampling ✅"),
+                Err(e) => warn!("error occurred when ending sampling: {}", e),
+            }
+        }
+        match client.disconnect() {
+            Ok(_) => info!("success disconnect ✅"),
+            Err(e) => warn!("error occurred when disconnecting: {}", e),
+        }
+    }
+    shared.release_client();
+}
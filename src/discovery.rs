@@ -0,0 +1,176 @@
+use crate::error::Result;
+use crate::lidar_frame::cfg::BROADCAST_PORT;
+use crate::lidar_frame::frames::{deserialize_broadcast, DeviceType};
+use crate::receiver::{terminate, WorkerHandle, POLL_INTERVAL};
+use log::{debug, info, log_enabled, warn};
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Source of lidars seen within a discovery window, such as a broadcast socket
+pub type Discover = Box<dyn FnMut(Duration) -> Result<Vec<DiscoveredDevice>> + Send>;
+
+/// Lidar found by listening to broadcast frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredDevice {
+    /// broadcast code, also the serial number printed on device
+    pub broadcast_code: String,
+    pub dev_type: DeviceType,
+    /// address broadcast is sent from, commands are sent to this address
+    pub addr: SocketAddr,
+}
+
+/// listen on broadcast port for `timeout`, return every lidar seen during the window
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredDevice>> {
+    let broadcast_socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], BROADCAST_PORT)))?;
+    debug!("start listening broadcast on 0.0.0.0:{}...", BROADCAST_PORT);
+    discover_on(&broadcast_socket, timeout)
+}
+
+/// listen on given socket for `timeout`, return every lidar seen during the window
+pub fn discover_on(
+    broadcast_socket: &UdpSocket,
+    timeout: Duration,
+) -> Result<Vec<DiscoveredDevice>> {
+    let deadline = Instant::now() + timeout;
+    let mut devices: Vec<DiscoveredDevice> = Vec::new();
+    let mut buffer = [0; 1024];
+
+    while let Some(remaining) = deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+    {
+        broadcast_socket.set_read_timeout(Some(remaining))?;
+        let (size, addr) = match broadcast_socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match deserialize_broadcast(&buffer[..size]) {
+            Ok(broadcast) => {
+                let device = DiscoveredDevice {
+                    broadcast_code: broadcast.broadcast_code_str(),
+                    dev_type: broadcast.dev_type,
+                    addr,
+                };
+                if !devices
+                    .iter()
+                    .any(|known| known.broadcast_code == device.broadcast_code)
+                {
+                    if log_enabled!(log::Level::Debug) {
+                        debug!("received broadcast from {:?}", device);
+                    }
+                    devices.push(device);
+                }
+            }
+            Err(e) => {
+                if log_enabled!(log::Level::Warn) {
+                    warn!(
+                        "error occurred when deserializing broadcast from {}: {}",
+                        addr, e
+                    );
+                }
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// Every lidar heard by a broadcast listener and when its broadcast was last received
+type SeenDevices = Arc<Mutex<HashMap<String, (DiscoveredDevice, Instant)>>>;
+
+/// Lidars seen on a broadcast port shared by several supervisors, since a datagram
+/// received by one of them would be missed by the others
+pub struct BroadcastListener {
+    devices: SeenDevices,
+    handle: WorkerHandle,
+    term_sender: mpsc::Sender<()>,
+}
+
+impl BroadcastListener {
+    /// start listening on broadcast socket, the latest address of every lidar is kept
+    pub fn launch(broadcast_socket: UdpSocket) -> Result<Self> {
+        let devices: SeenDevices = Arc::new(Mutex::new(HashMap::new()));
+        let duplicated_devices = devices.clone();
+        let (tx, rx) = mpsc::channel();
+
+        let handle: WorkerHandle = thread::spawn(move || loop {
+            if rx.try_recv().is_ok() {
+                info!("received sig_term, broadcast listener exiting...");
+                return Ok(());
+            }
+            match discover_on(&broadcast_socket, POLL_INTERVAL) {
+                Ok(found) => {
+                    let now = Instant::now();
+                    let mut devices = duplicated_devices.lock().unwrap();
+                    for device in found {
+                        devices.insert(device.broadcast_code.clone(), (device, now));
+                    }
+                }
+                Err(e) => {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("error occurred when listening broadcast: {}", e);
+                    }
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+        });
+        debug!("broadcast listener started ✅");
+
+        Ok(Self {
+            devices,
+            handle,
+            term_sender: tx,
+        })
+    }
+
+    /// every lidar which broadcast within the last `window`
+    pub fn devices(&self, window: Duration) -> Vec<DiscoveredDevice> {
+        self.devices
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(_, seen)| seen.elapsed() <= window)
+            .map(|(device, _)| device.clone())
+            .collect()
+    }
+
+    /// discovery of the lidar with `broadcast_code`, for [`ConnectionSupervisor::launch_with`];
+    /// lidar is only found if it broadcast within the window, a connected or lost lidar
+    /// stays silent and must not be mistaken for a live one
+    ///
+    /// [`ConnectionSupervisor::launch_with`]: crate::connection::ConnectionSupervisor::launch_with
+    pub fn discover(&self, broadcast_code: &str) -> Discover {
+        let devices = self.devices.clone();
+        let broadcast_code = broadcast_code.to_string();
+        Box::new(move |window| {
+            let deadline = Instant::now() + window;
+            loop {
+                if let Some((device, seen)) = devices.lock().unwrap().get(&broadcast_code) {
+                    if seen.elapsed() <= window {
+                        return Ok(vec![device.clone()]);
+                    }
+                }
+                if Instant::now() >= deadline {
+                    return Ok(Vec::new());
+                }
+                thread::sleep(POLL_INTERVAL.min(window));
+            }
+        })
+    }
+
+    /// stop listening and wait for listener thread to exit
+    pub fn terminate(self) -> Result<()> {
+        terminate("broadcast listener", self.handle, self.term_sender)
+    }
+}
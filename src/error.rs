@@ -0,0 +1,60 @@
+use crate::lidar_frame::frames::Cmd;
+use std::fmt;
+
+pub type Result<T, E = LivoxError> = std::result::Result<T, E>;
+
+/// Checksum of a control frame, CRC16 covers the header and CRC32 the whole frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcKind {
+    Header,
+    Frame,
+}
+
+impl fmt::Display for CrcKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrcKind::Header => f.write_str("CRC16 of header"),
+            CrcKind::Frame => f.write_str("CRC32 of frame"),
+        }
+    }
+}
+
+/// Error returned throughout the library, callers branch on its kind,
+/// e.g. retrying a command on `Timeout` but not on `DeviceRejected`
+#[derive(Debug, thiserror::Error)]
+pub enum LivoxError {
+    #[error("{kind} mismatch, received 0x{received:X}, calculated 0x{calculated:X}")]
+    CrcMismatch {
+        kind: CrcKind,
+        received: u32,
+        calculated: u32,
+    },
+    #[error("length mismatch, expected {expected} bytes, found {found}")]
+    LengthMismatch { expected: usize, found: usize },
+    #[error("command {cmd:?} unanswered after {attempts} attempts")]
+    Timeout { cmd: Cmd, attempts: u32 },
+    #[error("command {cmd:?} rejected by lidar with return code {ret_code}")]
+    DeviceRejected { cmd: Cmd, ret_code: u8 },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// bytes received or read from a file do not make a valid frame, packet or record
+    #[error("{0}")]
+    Decode(String),
+    #[error("{0}")]
+    Encode(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Config(String),
+    /// peer, recording or worker is no longer there to take the request
+    #[error("{0}")]
+    Closed(String),
+    #[error("{0} panicked")]
+    WorkerPanicked(&'static str),
+}
+
+impl From<bincode::Error> for LivoxError {
+    fn from(e: bincode::Error) -> Self {
+        LivoxError::Decode(e.to_string())
+    }
+}
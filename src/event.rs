@@ -0,0 +1,43 @@
+use crate::connection::ConnectionState;
+use crate::health::LidarHealth;
+use crate::lidar_frame::frames::Cmd;
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Asynchronous event reported by lidar or driver
+#[derive(Debug, Clone, PartialEq)]
+pub enum LidarEvent {
+    /// lidar pushed an abnormal status message
+    AbnormalStatus(LidarHealth),
+    /// status code carried by data packets changed
+    HealthChanged(LidarHealth),
+    /// connection to lidar moved to another state
+    StateChanged(ConnectionState),
+    /// lidar pushed a message this driver does not decode
+    Message { cmd: Cmd, payload: Vec<u8> },
+}
+
+/// Fan-out of events to every subscriber, subscribers which hung up are dropped
+#[derive(Debug, Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<LidarEvent>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// subscribe to events published from now on
+    pub fn subscribe(&self) -> mpsc::Receiver<LidarEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub fn publish(&self, event: LidarEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::cloud::CloudPoint;
+use crate::error::{LivoxError, Result};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// How point records are stored after the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    /// little endian
+    Binary,
+}
+
+/// File format understood by PCL tools and CloudCompare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloudFormat {
+    Pcd(Encoding),
+    Ply(Encoding),
+}
+
+impl CloudFormat {
+    /// format named by extension of `path`, `.pcd` or `.ply`
+    pub fn from_path(path: impl AsRef<Path>, encoding: Encoding) -> Result<Self> {
+        let extension = path.as_ref().extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("pcd") => Ok(CloudFormat::Pcd(encoding)),
+            Some("ply") => Ok(CloudFormat::Ply(encoding)),
+            _ => Err(LivoxError::InvalidArgument(format!(
+                "Unknown point cloud format of {}, expected .pcd or .ply",
+                path.as_ref().display()
+            ))),
+        }
+    }
+}
+
+/// timestamp of point in seconds, as stored in both formats which lack 64 bit integers
+fn seconds(point: &CloudPoint) -> f64 {
+    point.timestamp as f64 / 1e9
+}
+
+fn write_binary(writer: &mut impl Write, point: &CloudPoint) -> Result<()> {
+    writer.write_all(&point.x.to_le_bytes())?;
+    writer.write_all(&point.y.to_le_bytes())?;
+    writer.write_all(&point.z.to_le_bytes())?;
+    writer.write_all(&[point.reflectivity, point.tag])?;
+    writer.write_all(&seconds(point).to_le_bytes())?;
+    Ok(())
+}
+
+fn write_ascii(writer: &mut impl Write, point: &CloudPoint) -> Result<()> {
+    writeln!(
+        writer,
+        "{} {} {} {} {} {:.9}",
+        point.x,
+        point.y,
+        point.z,
+        point.reflectivity,
+        point.tag,
+        seconds(point)
+    )?;
+    Ok(())
+}
+
+/// write points as PCD v0.7, reflectivity is stored as `intensity` the way PCL names it
+pub fn write_pcd(mut writer: impl Write, points: &[CloudPoint], encoding: Encoding) -> Result<()> {
+    writeln!(writer, "# .PCD v0.7 - Point Cloud Data file format")?;
+    writeln!(writer, "VERSION 0.7")?;
+    writeln!(writer, "FIELDS x y z intensity tag timestamp")?;
+    writeln!(writer, "SIZE 4 4 4 1 1 8")?;
+    writeln!(writer, "TYPE F F F U U F")?;
+    writeln!(writer, "COUNT 1 1 1 1 1 1")?;
+    writeln!(writer, "WIDTH {}", points.len())?;
+    writeln!(writer, "HEIGHT 1")?;
+    writeln!(writer, "VIEWPOINT 0 0 0 1 0 0 0")?;
+    writeln!(writer, "POINTS {}", points.len())?;
+    match encoding {
+        Encoding::Ascii => {
+            writeln!(writer, "DATA ascii")?;
+            points
+                .iter()
+                .try_for_each(|point| write_ascii(&mut writer, point))?;
+        }
+        Encoding::Binary => {
+            writeln!(writer, "DATA binary")?;
+            points
+                .iter()
+                .try_for_each(|point| write_binary(&mut writer, point))?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// write points as PLY 1.0 vertices, reflectivity is stored as `intensity`
+/// the way CloudCompare names it
+pub fn write_ply(mut writer: impl Write, points: &[CloudPoint], encoding: Encoding) -> Result<()> {
+    writeln!(writer, "ply")?;
+    match encoding {
+        Encoding::Ascii => writeln!(writer, "format ascii 1.0")?,
+        Encoding::Binary => writeln!(writer, "format binary_little_endian 1.0")?,
+    }
+    writeln!(
+        writer,
+        "comment exported by livox_lidar_rs, timestamp in seconds"
+    )?;
+    writeln!(writer, "element vertex {}", points.len())?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    writeln!(writer, "property uchar intensity")?;
+    writeln!(writer, "property uchar tag")?;
+    writeln!(writer, "property double timestamp")?;
+    writeln!(writer, "end_header")?;
+    points.iter().try_for_each(|point| match encoding {
+        Encoding::Ascii => write_ascii(&mut writer, point),
+        Encoding::Binary => write_binary(&mut writer, point),
+    })?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// write points into file at `path`, overwriting any existing one
+pub fn save(path: impl AsRef<Path>, points: &[CloudPoint], format: CloudFormat) -> Result<()> {
+    let file = File::create(path.as_ref()).map_err(|e| {
+        LivoxError::Io(std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to create point cloud file {}: {}",
+                path.as_ref().display(),
+                e
+            ),
+        ))
+    })?;
+    let writer = BufWriter::new(file);
+    match format {
+        CloudFormat::Pcd(encoding) => write_pcd(writer, points, encoding),
+        CloudFormat::Ply(encoding) => write_ply(writer, points, encoding),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let points = [CloudPoint {
+            x: 1.5,
+            y: -2.0,
+            z: 0.25,
+            reflectivity: 100,
+            tag: 0x10,
+            timestamp: 1_500_000_000,
+        }];
+
+        let mut pcd = Vec::new();
+        write_pcd(&mut pcd, &points, Encoding::Ascii).unwrap();
+        let pcd = String::from_utf8(pcd).unwrap();
+        assert!(pcd.contains("\nPOINTS 1\nDATA ascii\n"));
+        assert!(pcd.ends_with("\n1.5 -2 0.25 100 16 1.500000000\n"));
+
+        let mut pcd = Vec::new();
+        write_pcd(&mut pcd, &points, Encoding::Binary).unwrap();
+        let header_len = pcd.windows(12).position(|w| w == b"DATA binary\n").unwrap() + 12;
+        let data = &pcd[header_len..];
+        assert_eq!(data.len(), 22);
+        assert_eq!(&data[12..14], &[100, 0x10]);
+        assert_eq!(f64::from_le_bytes(data[14..].try_into().unwrap()), 1.5);
+
+        let mut ply = Vec::new();
+        write_ply(&mut ply, &points, Encoding::Binary).unwrap();
+        let header_len = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+        assert_eq!(ply.len() - header_len, 22);
+        assert!(String::from_utf8_lossy(&ply[..header_len]).contains("element vertex 1\n"));
+
+        assert_eq!(
+            CloudFormat::from_path("scan.PLY", Encoding::Ascii).unwrap(),
+            CloudFormat::Ply(Encoding::Ascii)
+        );
+        assert!(CloudFormat::from_path("scan.las", Encoding::Ascii).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::event::{EventBus, LidarEvent};
+use crate::receiver::PointReceiver;
+use log::{log_enabled, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Severity shared by temperature, voltage, motor and system status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusLevel {
+    Normal,
+    Warning,
+    Error,
+}
+
+impl StatusLevel {
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            0 => StatusLevel::Normal,
+            1 => StatusLevel::Warning,
+            _ => StatusLevel::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowStatus {
+    Clean,
+    DirtyOrBlocked,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FirmwareStatus {
+    Ok,
+    /// firmware is abnormal and needs upgrade
+    Abnormal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignalStatus {
+    NoSignal,
+    Ok,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceStatus {
+    Normal,
+    /// device is approaching end of service life
+    EndOfServiceLife,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FanStatus {
+    Normal,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeSyncStatus {
+    NotStarted,
+    Ptp,
+    Gps,
+    Pps,
+    /// the highest priority synchronization signal is abnormal
+    Abnormal,
+    Reserved(u8),
+}
+
+impl TimeSyncStatus {
+    fn from_bits(bits: u32) -> Self {
+        match bits {
+            0 => TimeSyncStatus::NotStarted,
+            1 => TimeSyncStatus::Ptp,
+            2 => TimeSyncStatus::Gps,
+            3 => TimeSyncStatus::Pps,
+            4 => TimeSyncStatus::Abnormal,
+            _ => TimeSyncStatus::Reserved(bits as u8),
+        }
+    }
+}
+
+/// Health report decoded from the 32-bit status code carried by every data packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LidarHealth {
+    pub status_code: u32,
+    pub temperature: StatusLevel,
+    pub voltage: StatusLevel,
+    pub motor: StatusLevel,
+    pub window: WindowStatus,
+    pub firmware: FirmwareStatus,
+    pub pps: SignalStatus,
+    pub device: DeviceStatus,
+    pub fan: FanStatus,
+    /// heating itself because of low temperature
+    pub self_heating: bool,
+    pub ptp: SignalStatus,
+    pub time_sync: TimeSyncStatus,
+    pub system: StatusLevel,
+}
+
+impl LidarHealth {
+    pub fn from_status_code(status_code: u32) -> Self {
+        let bits = |offset: u32, width: u32| (status_code >> offset) & ((1 << width) - 1);
+        let signal = |bit| match bit {
+            0 => SignalStatus::NoSignal,
+            _ => SignalStatus::Ok,
+        };
+        LidarHealth {
+            status_code,
+            temperature: StatusLevel::from_bits(bits(0, 2)),
+            voltage: StatusLevel::from_bits(bits(2, 2)),
+            motor: StatusLevel::from_bits(bits(4, 2)),
+            window: match bits(6, 2) {
+                0 => WindowStatus::Clean,
+                _ => WindowStatus::DirtyOrBlocked,
+            },
+            firmware: match bits(8, 1) {
+                0 => FirmwareStatus::Ok,
+                _ => FirmwareStatus::Abnormal,
+            },
+            pps: signal(bits(9, 1)),
+            device: match bits(10, 1) {
+                0 => DeviceStatus::Normal,
+                _ => DeviceStatus::EndOfServiceLife,
+            },
+            fan: match bits(11, 1) {
+                0 => FanStatus::Normal,
+                _ => FanStatus::Warning,
+            },
+            self_heating: bits(12, 1) == 1,
+            ptp: signal(bits(13, 1)),
+            time_sync: TimeSyncStatus::from_bits(bits(14, 3)),
+            system: StatusLevel::from_bits(bits(30, 2)),
+        }
+    }
+
+    /// true if nothing needs the operator's attention, missing sync signals are not a fault
+    pub fn is_healthy(&self) -> bool {
+        self.temperature == StatusLevel::Normal
+            && self.voltage == StatusLevel::Normal
+            && self.motor == StatusLevel::Normal
+            && self.window == WindowStatus::Clean
+            && self.firmware == FirmwareStatus::Ok
+            && self.device == DeviceStatus::Normal
+            && self.fan == FanStatus::Normal
+            && self.time_sync != TimeSyncStatus::Abnormal
+            && self.system == StatusLevel::Normal
+    }
+}
+
+/// Health statistics over the rolling window of a [`HealthMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthSummary {
+    /// health reported by the most recent packet
+    pub latest: Option<LidarHealth>,
+    /// how long the latest health has been reported unchanged
+    pub latest_for: Duration,
+    pub window: Duration,
+    pub packets: u64,
+    pub unhealthy_packets: u64,
+    /// times the status code changed
+    pub changes: u64,
+}
+
+/// Packet statistics of one second
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    start: Instant,
+    packets: u64,
+    unhealthy_packets: u64,
+    changes: u64,
+}
+
+#[derive(Debug)]
+struct MonitorState {
+    latest: Option<(LidarHealth, Instant)>,
+    buckets: VecDeque<Bucket>,
+}
+
+/// Tracks status code of incoming packets, publishes changes and keeps a rolling summary
+#[derive(Debug)]
+pub struct HealthMonitor {
+    window: Duration,
+    events: EventBus,
+    state: Mutex<MonitorState>,
+}
+
+const BUCKET_SPAN: Duration = Duration::from_secs(1);
+
+impl HealthMonitor {
+    /// monitor publishing health changes to `events`, summarizing the last `window`
+    pub fn new(events: EventBus, window: Duration) -> Self {
+        HealthMonitor {
+            window,
+            events,
+            state: Mutex::new(MonitorState {
+                latest: None,
+                buckets: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// feed status code of every packet received by `receiver` into this monitor
+    pub fn attach(self: &Arc<Self>, receiver: &PointReceiver) {
+        let monitor = self.clone();
+        receiver.subscribe(move |packet| monitor.update(packet.header.status_code));
+    }
+
+    /// record status code of a packet, publishing an event if health changed
+    pub fn update(&self, status_code: u32) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let (health, changed) = match state.latest {
+            Some((health, _)) if health.status_code == status_code => (health, false),
+            _ => {
+                let health = LidarHealth::from_status_code(status_code);
+                state.latest = Some((health, now));
+                (health, true)
+            }
+        };
+
+        if state
+            .buckets
+            .back()
+            .is_none_or(|bucket| now.duration_since(bucket.start) >= BUCKET_SPAN)
+        {
+            state.buckets.push_back(Bucket {
+                start: now,
+                packets: 0,
+                unhealthy_packets: 0,
+                changes: 0,
+            });
+        }
+        while state
+            .buckets
+            .front()
+            .is_some_and(|bucket| now.duration_since(bucket.start) > self.window)
+        {
+            state.buckets.pop_front();
+        }
+        if let Some(bucket) = state.buckets.back_mut() {
+            bucket.packets += 1;
+            bucket.unhealthy_packets += u64::from(!health.is_healthy());
+            bucket.changes += u64::from(changed);
+        }
+        drop(state);
+
+        if changed {
+            if !health.is_healthy() && log_enabled!(log::Level::Warn) {
+                warn!("lidar health changed: {:?}", health);
+            }
+            self.events.publish(LidarEvent::HealthChanged(health));
+        }
+    }
+
+    /// health statistics over the rolling window
+    pub fn summary(&self) -> HealthSummary {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        let recent = state
+            .buckets
+            .iter()
+            .filter(|bucket| now.duration_since(bucket.start) <= self.window);
+        let (packets, unhealthy_packets, changes) =
+            recent.fold((0, 0, 0), |(packets, unhealthy, changes), bucket| {
+                (
+                    packets + bucket.packets,
+                    unhealthy + bucket.unhealthy_packets,
+                    changes + bucket.changes,
+                )
+            });
+        HealthSummary {
+            latest: state.latest.map(|(health, _)| health),
+            latest_for: state
+                .latest
+                .map(|(_, since)| now.duration_since(since))
+                .unwrap_or_default(),
+            window: self.window,
+            packets,
+            unhealthy_packets,
+            changes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_from_status_code() {
+        let health = LidarHealth::from_status_code(0);
+        assert!(health.is_healthy());
+        assert_eq!(health.time_sync, TimeSyncStatus::NotStarted);
+
+        // temperature warning, dirty window, pps ok, pps synchronization, system error
+        let health = LidarHealth::from_status_code(0x01 | 0x40 | 1 << 9 | 3 << 14 | 2 << 30);
+        assert_eq!(health.temperature, StatusLevel::Warning);
+        assert_eq!(health.voltage, StatusLevel::Normal);
+        assert_eq!(health.window, WindowStatus::DirtyOrBlocked);
+        assert_eq!(health.pps, SignalStatus::Ok);
+        assert_eq!(health.time_sync, TimeSyncStatus::Pps);
+        assert_eq!(health.system, StatusLevel::Error);
+        assert!(!health.is_healthy());
+    }
+
+    #[test]
+    fn test_health_monitor() {
+        let events = EventBus::new();
+        let subscriber = events.subscribe();
+        let monitor = HealthMonitor::new(events, Duration::from_secs(10));
+
+        monitor.update(0);
+        monitor.update(0);
+        monitor.update(0x40);
+
+        let changes: Vec<_> = subscriber.try_iter().collect();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[1],
+            LidarEvent::HealthChanged(LidarHealth::from_status_code(0x40))
+        );
+
+        let summary = monitor.summary();
+        assert_eq!(summary.packets, 3);
+        assert_eq!(summary.unhealthy_packets, 1);
+        assert_eq!(summary.changes, 2);
+        assert_eq!(
+            summary.latest.map(|h| h.window),
+            Some(WindowStatus::DirtyOrBlocked)
+        );
+    }
+}
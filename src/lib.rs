@@ -1 +1,17 @@
+pub mod accumulator;
+pub mod client;
+pub mod cloud;
+pub mod connection;
+pub mod discovery;
+pub mod error;
+pub mod event;
+pub mod export;
+pub mod health;
 pub mod lidar_frame;
+pub mod lvx;
+pub mod pcap;
+pub mod receiver;
+pub mod session;
+pub mod simulator;
+pub mod time_sync;
+pub mod transform;
@@ -1,4 +1,377 @@
+use super::frames::{CoordinateType, HandshakeReq, ReturnMode};
+use crate::error::{LivoxError, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 pub const USER_IP: [u8; 4] = [192, 168, 1, 50];
 pub const DATA_PORT: u16 = 50000;
 pub const CMD_PORT: u16 = 50001;
 pub const IMU_PORT: u16 = 50002;
+pub const BROADCAST_PORT: u16 = 55000;
+pub const HEARTBEAT_INTERVAL_MS: u64 = 1000;
+pub const COMMAND_TIMEOUT_MS: u64 = 1000;
+pub const COMMAND_RETRIES: u32 = 2;
+pub const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+/// Extrinsic parameters written to lidar, roll/pitch/yaw in degrees, x/y/z in millimeters
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Extrinsics {
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Runtime configuration of host and lidar, built in code or loaded from a TOML file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LidarConfig {
+    /// ip address of host, lidar sends point cloud and IMU data to it
+    pub host_ip: Ipv4Addr,
+    pub data_port: u16,
+    pub cmd_port: u16,
+    pub imu_port: u16,
+    pub broadcast_port: u16,
+    /// connect to lidar with this broadcast code, or whichever broadcasts first if absent
+    pub broadcast_code: Option<String>,
+    /// 0x00: Cartesian, 0x01: Spherical
+    pub coordinate_type: CoordinateType,
+    /// 0x00: Single Return First, 0x01: Single Return Strongest, 0x02: Dual Return, 0x03: Triple Return
+    pub return_mode: ReturnMode,
+    /// extrinsic parameters written to lidar after handshake, left untouched if absent
+    pub extrinsics: Option<Extrinsics>,
+    /// slot id written to flash by `SessionManager::assign_slot_ids`, reported in every
+    /// data packet once lidar reboots
+    pub slot_id: Option<u8>,
+    pub heartbeat_interval_ms: u64,
+    /// consecutive unanswered heartbeats after which connection is considered lost
+    pub max_missed_heartbeats: u32,
+    /// time to wait for a command response before retransmitting
+    pub command_timeout_ms: u64,
+    /// retransmissions of a command before giving up
+    pub command_retries: u32,
+    /// how often host UTC time is pushed to lidar, never if absent
+    pub utc_sync_interval_ms: Option<u64>,
+    /// pcap file every datagram on control, data and IMU ports is captured into, if any
+    pub capture_file: Option<PathBuf>,
+}
+
+impl Default for LidarConfig {
+    fn default() -> Self {
+        LidarConfig {
+            host_ip: Ipv4Addr::from(USER_IP),
+            data_port: DATA_PORT,
+            cmd_port: CMD_PORT,
+            imu_port: IMU_PORT,
+            broadcast_port: BROADCAST_PORT,
+            broadcast_code: None,
+            coordinate_type: CoordinateType::Cartesian,
+            return_mode: ReturnMode::SingleFirst,
+            extrinsics: None,
+            slot_id: None,
+            heartbeat_interval_ms: HEARTBEAT_INTERVAL_MS,
+            max_missed_heartbeats: MAX_MISSED_HEARTBEATS,
+            command_timeout_ms: COMMAND_TIMEOUT_MS,
+            command_retries: COMMAND_RETRIES,
+            utc_sync_interval_ms: None,
+            capture_file: None,
+        }
+    }
+}
+
+impl LidarConfig {
+    /// parse configuration from TOML, missing keys take default values
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let config: LidarConfig = toml::from_str(toml)
+            .map_err(|e| LivoxError::Config(format!("Failed to parse lidar config: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// load configuration from TOML file
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let toml = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read lidar config {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::from_toml_str(&toml)
+    }
+
+    /// check values which would be rejected by lidar
+    pub fn validate(&self) -> Result<()> {
+        if self.heartbeat_interval_ms == 0 {
+            return Err(LivoxError::Config(
+                "Heartbeat interval must be positive".to_string(),
+            ));
+        }
+        if self.max_missed_heartbeats == 0 {
+            return Err(LivoxError::Config(
+                "Max missed heartbeats must be positive".to_string(),
+            ));
+        }
+        if self.command_timeout_ms == 0 {
+            return Err(LivoxError::Config(
+                "Command timeout must be positive".to_string(),
+            ));
+        }
+        if self.utc_sync_interval_ms == Some(0) {
+            return Err(LivoxError::Config(
+                "UTC sync interval must be positive".to_string(),
+            ));
+        }
+        if self.data_port == self.cmd_port
+            || self.data_port == self.imu_port
+            || self.cmd_port == self.imu_port
+        {
+            return Err(LivoxError::Config(format!(
+                "Command, data and IMU ports must differ, got {}, {} and {}",
+                self.cmd_port, self.data_port, self.imu_port
+            )));
+        }
+        if let Some(slot_id) = self.slot_id {
+            if !(0x01..=0x09).contains(&slot_id) {
+                return Err(LivoxError::Config(format!(
+                    "Slot id must be within 1 to 9, got {}",
+                    slot_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// handshake request telling lidar where to send data
+    pub fn handshake_req(&self) -> HandshakeReq {
+        HandshakeReq::new(
+            self.host_ip.octets(),
+            self.data_port,
+            self.cmd_port,
+            self.imu_port,
+        )
+    }
+
+    pub fn data_addr(&self) -> SocketAddr {
+        SocketAddr::from((self.host_ip, self.data_port))
+    }
+
+    pub fn cmd_addr(&self) -> SocketAddr {
+        SocketAddr::from((self.host_ip, self.cmd_port))
+    }
+
+    pub fn imu_addr(&self) -> SocketAddr {
+        SocketAddr::from((self.host_ip, self.imu_port))
+    }
+
+    pub fn broadcast_addr(&self) -> SocketAddr {
+        SocketAddr::from(([0, 0, 0, 0], self.broadcast_port))
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_interval_ms)
+    }
+
+    pub fn command_timeout(&self) -> Duration {
+        Duration::from_millis(self.command_timeout_ms)
+    }
+
+    pub fn utc_sync_interval(&self) -> Option<Duration> {
+        self.utc_sync_interval_ms.map(Duration::from_millis)
+    }
+}
+
+/// Configuration of several lidars sharing one broadcast port, each with ports of its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// port every lidar broadcasts to, `broadcast_port` of each lidar is ignored
+    pub broadcast_port: u16,
+    /// span of the frames clouds are merged into, clouds are not merged if absent
+    pub merge_window_ms: Option<u64>,
+    /// every lidar needs a broadcast code, a command port and a data port of its own
+    pub lidars: Vec<LidarConfig>,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            broadcast_port: BROADCAST_PORT,
+            merge_window_ms: None,
+            lidars: Vec::new(),
+        }
+    }
+}
+
+impl SessionConfig {
+    /// parse configuration from TOML, every lidar is given as a `[[lidars]]` table
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let config: SessionConfig = toml::from_str(toml)
+            .map_err(|e| LivoxError::Config(format!("Failed to parse session config: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// load configuration from TOML file
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let toml = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to read session config {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::from_toml_str(&toml)
+    }
+
+    /// check every lidar, and that lidars can be told apart
+    pub fn validate(&self) -> Result<()> {
+        if self.lidars.is_empty() {
+            return Err(LivoxError::Config("Session has no lidar".to_string()));
+        }
+        if self.merge_window_ms == Some(0) {
+            return Err(LivoxError::Config(
+                "Merge window must be positive".to_string(),
+            ));
+        }
+        let mut codes = Vec::new();
+        let mut addrs = Vec::new();
+        for lidar in &self.lidars {
+            lidar.validate()?;
+            let Some(code) = lidar.broadcast_code.as_ref() else {
+                return Err(LivoxError::Config(
+                    "Every lidar of a session needs a broadcast code".to_string(),
+                ));
+            };
+            if codes.contains(&code) {
+                return Err(LivoxError::Config(format!(
+                    "Broadcast code {} given twice",
+                    code
+                )));
+            }
+            codes.push(code);
+            for addr in [lidar.cmd_addr(), lidar.data_addr(), lidar.imu_addr()] {
+                if addrs.contains(&addr) {
+                    return Err(LivoxError::Config(format!(
+                        "Port {} used by more than one lidar",
+                        addr.port()
+                    )));
+                }
+                addrs.push(addr);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn broadcast_addr(&self) -> SocketAddr {
+        SocketAddr::from(([0, 0, 0, 0], self.broadcast_port))
+    }
+
+    pub fn merge_window(&self) -> Option<Duration> {
+        self.merge_window_ms.map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_from_toml_str() {
+        let config = LidarConfig::from_toml_str(
+            r#"
+            host_ip = "192.168.1.60"
+            broadcast_code = "0TFDG3B006H2Z11"
+            return_mode = 0x02
+
+            [extrinsics]
+            roll = 0.0
+            pitch = 0.0
+            yaw = 90.0
+            x = 100
+            y = 0
+            z = -50
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.host_ip, Ipv4Addr::new(192, 168, 1, 60));
+        assert_eq!(config.data_port, DATA_PORT);
+        assert_eq!(config.broadcast_code.as_deref(), Some("0TFDG3B006H2Z11"));
+        assert_eq!(config.return_mode, ReturnMode::Dual);
+        assert_eq!(config.extrinsics.map(|e| e.yaw), Some(90.0));
+        assert!(LidarConfig::from_toml_str("return_mode = 7").is_err());
+        assert!(LidarConfig::from_toml_str("coordinate_type = 2").is_err());
+        assert!(LidarConfig::from_toml_str("imu_port = 50000").is_err());
+    }
+
+    #[test]
+    fn test_session_from_toml_str() {
+        let config = SessionConfig::from_toml_str(
+            r#"
+            merge_window_ms = 100
+
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z11"
+            slot_id = 1
+
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z12"
+            slot_id = 2
+            cmd_port = 50011
+            data_port = 50010
+            imu_port = 50012
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.broadcast_port, BROADCAST_PORT);
+        assert_eq!(config.merge_window(), Some(Duration::from_millis(100)));
+        assert_eq!(config.lidars.len(), 2);
+        assert_eq!(config.lidars[1].slot_id, Some(2));
+        assert_eq!(config.lidars[1].data_port, 50010);
+
+        // both lidars on default ports
+        assert!(SessionConfig::from_toml_str(
+            r#"
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z11"
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z12"
+            "#
+        )
+        .is_err());
+        // IMU packets of the second lidar would land on data port of the first one
+        assert!(SessionConfig::from_toml_str(
+            r#"
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z11"
+            [[lidars]]
+            broadcast_code = "0TFDG3B006H2Z12"
+            cmd_port = 50011
+            data_port = 50010
+            imu_port = 50000
+            "#
+        )
+        .is_err());
+        assert!(SessionConfig::from_toml_str("[[lidars]]\nslot_id = 1").is_err());
+        assert!(SessionConfig::from_toml_str("").is_err());
+        let sample = SessionConfig::from_toml_file(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/session_config.toml"
+        ))
+        .unwrap();
+        assert_eq!(sample.lidars.len(), 2);
+    }
+}
@@ -1,16 +1,26 @@
+mod codec;
 mod frame_definitions;
+mod hub_definitions;
+mod point_definitions;
 mod traits;
 
-use anyhow::{anyhow, Result};
+use crate::error::{CrcKind, LivoxError, Result};
 use crc::{Crc, CRC_16_MCRF4XX};
-use livox_lidar_derive::{CheckStatus, GetCmd, Len};
-use serde::{ser::SerializeTupleStruct, Deserialize, Serialize};
+use livox_lidar_derive::{CheckStatus, GetCmd, KeyValue, Len, LivoxCommand};
+use serde::{Deserialize, Serialize};
 
 use std::mem;
 
-use super::cfg::{CMD_PORT, DATA_PORT, IMU_PORT, USER_IP};
-
+const SOF: u8 = 0xAA;
 const CRC16_INIT: u16 = 0x9232;
 const CRC32_INIT: u32 = 0x564f580a;
+/// sof, version, length, command type, sequence number and CRC16
+const HEADER_LEN: usize = 9;
+const CRC32_LEN: usize = mem::size_of::<u32>();
+/// CRC16 algorithm of frame header, table built once
+static CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_MCRF4XX);
+pub use codec::*;
 pub use frame_definitions::*;
+pub use hub_definitions::*;
+pub use point_definitions::*;
 pub use traits::*;
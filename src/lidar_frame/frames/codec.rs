@@ -0,0 +1,439 @@
+use super::*;
+
+/// decode a whole data segment, rejecting it unless `T` takes every byte of it
+fn decode_segment<T>(segment: &[u8]) -> Result<T>
+where
+    T: Serialize + for<'de> Deserialize<'de>,
+{
+    let value: T = bincode::deserialize(segment)
+        .map_err(|e| LivoxError::Decode(format!("Failed to deserialize data segment: {}", e)))?;
+    let len = bincode::serialized_size(&value)
+        .map_err(|e| LivoxError::Encode(format!("Failed to size data segment: {}", e)))?;
+    if len != segment.len() as u64 {
+        return Err(LivoxError::LengthMismatch {
+            expected: len as usize,
+            found: segment.len(),
+        });
+    }
+    Ok(value)
+}
+
+/// decode a value from the start of bytes, ignoring whatever follows
+fn decode_prefix<T>(bytes: &[u8]) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    bincode::deserialize(bytes)
+        .map_err(|e| LivoxError::Decode(format!("Failed to deserialize data segment: {}", e)))
+}
+
+/// decode response body answering `cmd` and check its return code; a rejection may stop
+/// short of the fields a success carries, it is reported as `DeviceRejected` all the same
+pub fn decode_response<T>(cmd: Cmd, body: &[u8]) -> Result<T>
+where
+    T: CheckStatus + for<'de> Deserialize<'de>,
+{
+    match bincode::deserialize::<T>(body) {
+        Ok(resp) => {
+            resp.check_status(cmd)?;
+            Ok(resp)
+        }
+        // every response starts with its return code
+        Err(e) => match body.first() {
+            Some(&ret_code) if ret_code != 0 => Err(LivoxError::DeviceRejected { cmd, ret_code }),
+            _ => Err(LivoxError::Decode(format!(
+                "Failed to deserialize response: {}",
+                e
+            ))),
+        },
+    }
+}
+
+/// Every request type, generating `Request` and `Response` enums keyed by its
+/// `LivoxCommand::CMD` and answered by its `LivoxCommand::Response`
+macro_rules! commands {
+    ($($(#[$doc:meta])* $variant:ident($req:ty),)*) => {
+        /// Command sent to lidar, typed by its command set and id
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Request {
+            $($(#[$doc])* $variant($req),)*
+        }
+
+        /// Response sent back by lidar, typed by the command it answers
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Response {
+            $($(#[$doc])* $variant(<$req as LivoxCommand>::Response),)*
+        }
+
+        impl GetCmd for Request {
+            fn cmd(&self) -> Cmd {
+                match self {
+                    $(Request::$variant(req) => req.cmd(),)*
+                }
+            }
+        }
+
+        impl Request {
+            /// decode data segment of a command frame, starting from the command
+            pub fn decode(segment: &[u8]) -> Result<Self> {
+                let cmd: Cmd = decode_prefix(segment)?;
+                match cmd {
+                    $(cmd if cmd == <$req as LivoxCommand>::CMD => {
+                        Ok(Request::$variant(decode_segment(segment)?))
+                    })*
+                    _ => Err(LivoxError::Decode(format!("Unsupported command: {:?}", cmd))),
+                }
+            }
+
+            /// serialize into a command frame
+            pub fn serialize(&self, seq_num: u16) -> Result<Vec<u8>> {
+                match self {
+                    $(Request::$variant(req) => ControlFrame::new(seq_num, req).serialize(),)*
+                }
+            }
+        }
+
+        impl GetCmd for Response {
+            fn cmd(&self) -> Cmd {
+                match self {
+                    $(Response::$variant(_) => <$req as LivoxCommand>::CMD,)*
+                }
+            }
+        }
+
+        impl Response {
+            /// decode response body of an ACK frame answering `cmd`
+            pub fn decode(cmd: Cmd, payload: &[u8]) -> Result<Self> {
+                match cmd {
+                    $(cmd if cmd == <$req as LivoxCommand>::CMD => {
+                        Ok(Response::$variant(decode_prefix(payload)?))
+                    })*
+                    _ => Err(LivoxError::Decode(format!("Unsupported response: {:?}", cmd))),
+                }
+            }
+
+            /// serialize into an ACK frame
+            pub fn serialize(&self, seq_num: u16) -> Result<Vec<u8>> {
+                let cmd = self.cmd();
+                match self {
+                    $(Response::$variant(resp) => {
+                        ControlFrame::ack(seq_num, &Ack::new(cmd, resp.clone())).serialize()
+                    })*
+                }
+            }
+
+            /// return code of response, 0x00 on success
+            pub fn ret_code(&self) -> u8 {
+                match self {
+                    $(Response::$variant(resp) => resp.ret_code,)*
+                }
+            }
+        }
+    };
+}
+
+commands! {
+    Handshake(HandshakeReq),
+    DeviceInfo(DeviceInfoReq),
+    Heartbeat(HeartbeatReq),
+    SampleCtrl(SampleCtrlReq),
+    ChangeCoordinate(ChangeCoordinateReq),
+    Disconnect(DisconnectReq),
+    IpConfig(IpConfigReq),
+    IpInfo(IpInfoReq),
+    Reboot(RebootReq),
+    WriteFlash(WriteFlashReq),
+    ReadFlash(ReadFlashReq),
+    ModeSwitch(ModeSwitchReq),
+    WriteOuterParameters(WriteOuterParameters),
+    ReadOuterParameters(ReadOuterParameters),
+    SetReturnMode(SetReturnMode),
+    RainFogSuppression(RainFogSuppressionReq),
+    FanControl(FanControlReq),
+    GetFanState(GetFanState),
+    GetReturnMode(GetReturnMode),
+    SetImuPushFrequency(SetImuPushFrequency),
+    GetImuPushFrequency(GetImuPushFrequency),
+    UpdateUtcSyncTime(UpdateUtcSyncTime),
+    HubQueryLidarInfo(HubQueryLidarInfo),
+    HubSetMode(HubSetModeReq),
+    HubQueryLidarStatus(HubQueryLidarStatus),
+    HubSlotPower(HubSlotPowerReq),
+    HubSetExtrinsics(HubSetExtrinsicsReq),
+    HubGetExtrinsics(HubGetExtrinsicsReq),
+    // hub command 0x06 is left out of the published SDK1 protocol, its layout is unknown
+    HubExtrinsicsCalculation(HubExtrinsicsCalculationReq),
+    HubRainFogSuppression(HubRainFogSuppressionReq),
+    HubQuerySlotPower(HubQuerySlotPower),
+    HubFanControl(HubFanControlReq),
+    HubGetFanState(HubGetFanStateReq),
+    HubSetReturnMode(HubSetReturnModeReq),
+    HubGetReturnMode(HubGetReturnModeReq),
+    HubSetImuPushFrequency(HubSetImuPushFrequencyReq),
+    HubGetImuPushFrequency(HubGetImuPushFrequencyReq),
+}
+
+/// Message pushed by lidar, no response expected
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    Broadcast(Broadcast),
+    AbnormalStatus(AbnormalStatusMsg),
+}
+
+impl GetCmd for Message {
+    fn cmd(&self) -> Cmd {
+        match self {
+            Message::Broadcast(msg) => msg.cmd(),
+            Message::AbnormalStatus(msg) => msg.cmd(),
+        }
+    }
+}
+
+impl Message {
+    /// decode data segment of a message frame, starting from the command
+    pub fn decode(segment: &[u8]) -> Result<Self> {
+        let cmd: Cmd = decode_prefix(segment)?;
+        match cmd {
+            BROADCAST_CMD => Ok(Message::Broadcast(decode_segment(segment)?)),
+            ABNORMAL_STATUS_CMD => Ok(Message::AbnormalStatus(decode_segment(segment)?)),
+            _ => Err(LivoxError::Decode(format!(
+                "Unsupported message: {:?}",
+                cmd
+            ))),
+        }
+    }
+
+    /// serialize into a message frame
+    pub fn serialize(&self, seq_num: u16) -> Result<Vec<u8>> {
+        match self {
+            Message::Broadcast(msg) => ControlFrame::msg(seq_num, msg).serialize(),
+            Message::AbnormalStatus(msg) => ControlFrame::msg(seq_num, msg).serialize(),
+        }
+    }
+}
+
+/// data segment of a checked frame, from command to CRC32
+fn segment(buffer: &[u8]) -> &[u8] {
+    &buffer[9..buffer.len() - 4]
+}
+
+impl ControlFrame<'_, Request> {
+    /// deserialize command frame as received by lidar, return sequence number and request
+    pub fn deserialize(buffer: &[u8]) -> Result<(u16, Request)> {
+        let (cmd_type, seq_num, _, _) = deserialize_frame(buffer)?;
+        if cmd_type != CmdType::Cmd {
+            return Err(LivoxError::Decode(format!(
+                "Expected command frame, found {:?}",
+                cmd_type
+            )));
+        }
+        Ok((seq_num, Request::decode(segment(buffer))?))
+    }
+}
+
+/// deserialize ACK frame, return sequence number and typed response
+pub fn deserialize_ack(buffer: &[u8]) -> Result<(u16, Response)> {
+    let (cmd_type, seq_num, cmd, payload) = deserialize_frame(buffer)?;
+    if cmd_type != CmdType::Ack {
+        return Err(LivoxError::Decode(format!(
+            "Expected ACK frame, found {:?}",
+            cmd_type
+        )));
+    }
+    Ok((seq_num, Response::decode(cmd, payload)?))
+}
+
+/// deserialize MSG frame, return sequence number and typed message
+pub fn deserialize_msg(buffer: &[u8]) -> Result<(u16, Message)> {
+    let (cmd_type, seq_num, _, _) = deserialize_frame(buffer)?;
+    if cmd_type != CmdType::Msg {
+        return Err(LivoxError::Decode(format!(
+            "Expected MSG frame, found {:?}",
+            cmd_type
+        )));
+    }
+    Ok((seq_num, Message::decode(segment(buffer))?))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lidar_frame::cfg::{CMD_PORT, DATA_PORT, IMU_PORT, USER_IP};
+    use proptest::prelude::*;
+
+    fn broadcast_codes() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec("[0-9A-Z]{15}", 0..8)
+    }
+
+    fn request() -> impl Strategy<Value = Request> {
+        prop_oneof![
+            (any::<[u8; 4]>(), any::<u16>(), any::<u16>(), any::<u16>()).prop_map(
+                |(ip, data_port, cmd_port, imu_port)| {
+                    Request::Handshake(HandshakeReq::new(ip, data_port, cmd_port, imu_port))
+                }
+            ),
+            any::<u16>().prop_map(|timeout| Request::Reboot(RebootReq::new(timeout))),
+            (0x00u8..=0x03).prop_map(|mode| {
+                Request::SetReturnMode(SetReturnMode::new(ReturnMode::try_from(mode).unwrap()))
+            }),
+            (
+                -180.0f32..180.0,
+                -180.0f32..180.0,
+                -180.0f32..180.0,
+                any::<i32>(),
+                any::<i32>(),
+                any::<i32>()
+            )
+                .prop_map(|(roll, pitch, yaw, x, y, z)| {
+                    Request::WriteOuterParameters(WriteOuterParameters::new(
+                        roll, pitch, yaw, x, y, z,
+                    ))
+                }),
+            (any::<bool>(), any::<bool>(), 0x01u8..=0x09).prop_map(
+                |(high_sensitivity, repetitive_scan, slot_id)| {
+                    let req = WriteFlashReq::new(high_sensitivity, repetitive_scan, slot_id);
+                    Request::WriteFlash(req.unwrap())
+                }
+            ),
+            (
+                any::<u8>(),
+                any::<u8>(),
+                any::<u8>(),
+                any::<u8>(),
+                any::<u32>()
+            )
+                .prop_map(|(year, month, day, hour, microsecond)| {
+                    let req = UpdateUtcSyncTime::new(year, month, day, hour, microsecond);
+                    Request::UpdateUtcSyncTime(req)
+                }),
+            (broadcast_codes(), 0x01u8..=0x03).prop_map(|(codes, mode)| {
+                let lidars = codes
+                    .iter()
+                    .map(|code| HubLidarValue::new(code, mode))
+                    .collect();
+                Request::HubSetMode(HubSetModeReq::new(lidars))
+            }),
+            broadcast_codes().prop_map(|codes| {
+                let codes: Vec<&str> = codes.iter().map(String::as_str).collect();
+                Request::HubGetFanState(HubGetFanStateReq::new(&codes))
+            }),
+        ]
+    }
+
+    fn response() -> impl Strategy<Value = Response> {
+        prop_oneof![
+            (any::<u8>(), any::<u8>(), any::<u8>(), any::<u32>()).prop_map(
+                |(ret_code, work_state, feature_msg, ack_msg)| {
+                    Response::Heartbeat(HeartbeatResp {
+                        ret_code,
+                        work_state,
+                        feature_msg,
+                        ack_msg,
+                    })
+                }
+            ),
+            (any::<u8>(), broadcast_codes()).prop_map(|(ret_code, codes)| {
+                let lidars = codes
+                    .iter()
+                    .map(|code| HubLidarResult {
+                        ret_code,
+                        broadcast_code: encode_broadcast_code(code),
+                    })
+                    .collect::<Vec<_>>();
+                Response::HubSetMode(HubLidarResultsResp {
+                    ret_code,
+                    lidars: lidars.into(),
+                })
+            }),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(req in request(), resp in response(), seq_num in any::<u16>()) {
+            let serialized = req.serialize(seq_num).unwrap();
+            prop_assert_eq!(ControlFrame::deserialize(&serialized).unwrap(), (seq_num, req));
+            let serialized = resp.serialize(seq_num).unwrap();
+            prop_assert_eq!(deserialize_ack(&serialized).unwrap(), (seq_num, resp));
+        }
+
+        #[test]
+        fn test_parsers_are_total(
+            req in request(),
+            cut in any::<prop::sample::Index>(),
+            flip in any::<(prop::sample::Index, u8)>(),
+            garbage in prop::collection::vec(any::<u8>(), 0..64),
+        ) {
+            let serialized = req.serialize(0x01).unwrap();
+            let truncated = &serialized[..cut.index(serialized.len())];
+            prop_assert!(ControlFrame::deserialize(truncated).is_err());
+
+            let mut mutated = serialized.clone();
+            mutated[flip.0.index(serialized.len())] ^= flip.1.max(1);
+            for buffer in [&mutated[..], &garbage[..], truncated] {
+                let _ = ControlFrame::deserialize(buffer);
+                let _ = deserialize_ack(buffer);
+                let _ = deserialize_msg(buffer);
+                let _ = deserialize_broadcast(buffer);
+                let _ = deserialize_data(buffer);
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let serial = vec![
+            170, 1, 25, 0, 0, 17, 0, 149, 212, 0, 1, 192, 168, 1, 50, 80, 195, 81, 195, 82, 195,
+            105, 142, 213, 13,
+        ];
+        let (seq_num, req) = ControlFrame::deserialize(&serial).unwrap();
+        assert_eq!(seq_num, 0x11);
+        assert_eq!(
+            req,
+            Request::Handshake(HandshakeReq::new(USER_IP, DATA_PORT, CMD_PORT, IMU_PORT))
+        );
+        assert_eq!(req.serialize(seq_num).unwrap(), serial);
+
+        let req = Request::WriteFlash(WriteFlashReq::new(true, false, 8).unwrap());
+        let (_, decoded) = ControlFrame::deserialize(&req.serialize(0x02).unwrap()).unwrap();
+        assert_eq!(decoded, req);
+    }
+
+    #[test]
+    fn test_deserialize_ack() {
+        let resp = Response::Heartbeat(HeartbeatResp {
+            ret_code: 0,
+            work_state: 0x01,
+            feature_msg: 0,
+            ack_msg: 0x40,
+        });
+        let serialized = resp.serialize(0x07).unwrap();
+        assert_eq!(deserialize_ack(&serialized).unwrap(), (0x07, resp));
+        assert!(ControlFrame::deserialize(&serialized).is_err());
+
+        let msg = Message::AbnormalStatus(AbnormalStatusMsg::new(0x40));
+        let serialized = msg.serialize(0x08).unwrap();
+        assert_eq!(deserialize_msg(&serialized).unwrap(), (0x08, msg));
+    }
+
+    #[test]
+    fn test_decode_short_rejection() {
+        // lidar rejecting a flash read sends return code, error key and error code only
+        let rejection = vec![
+            170, 1, 19, 0, 1, 33, 0, 67, 116, 0, 12, 1, 3, 0, 1, 202, 10, 147, 14,
+        ];
+        let (seq_num, cmd, body) = deserialize_resp(&rejection).unwrap();
+        assert_eq!((seq_num, cmd), (0x21, ReadFlashReq::CMD));
+        assert!(matches!(
+            decode_response::<ReadFlashResp>(cmd, body),
+            Err(LivoxError::DeviceRejected { ret_code: 0x01, .. })
+        ));
+
+        // the same fields with a success code are a broken response
+        assert!(matches!(
+            decode_response::<ReadFlashResp>(cmd, &[0, 3, 0, 1]),
+            Err(LivoxError::Decode(_))
+        ));
+    }
+}
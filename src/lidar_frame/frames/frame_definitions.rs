@@ -1,35 +1,5 @@
 use super::*;
 
-#[derive(Len, Debug, Deserialize)]
-pub struct CartesianPoint {
-    x: i32, // millimeters
-    y: i32,
-    z: i32,
-    reflectivity: u8,
-    tag: u8,
-}
-
-#[derive(Len, Debug, Deserialize)]
-pub struct SphericalPoint {
-    depth: u32,  // millimeters
-    zenith: u16, // 0.01 degree
-    azimuth: u16,
-    reflectivity: u8,
-    tag: u8,
-}
-
-#[derive(Len, Debug, Deserialize)]
-pub struct DataFrame {
-    version: u8,
-    slot_id: u8,
-    lidar_id: u8,
-    reserved: u8,
-    status_code: u32,
-    timestamp_type: u8,
-    data_type: u8,
-    timestamp: u64,
-}
-
 /// Command set and command id.
 #[derive(Debug, Serialize, Deserialize, Len, Hash, PartialEq, Eq, Clone, Copy)]
 pub struct Cmd {
@@ -37,153 +7,279 @@ pub struct Cmd {
     cmd_id: u8,
 }
 
+impl Cmd {
+    pub const fn new(cmd_set: u8, cmd_id: u8) -> Self {
+        Cmd { cmd_set, cmd_id }
+    }
+
+    pub fn cmd_set(&self) -> u8 {
+        self.cmd_set
+    }
+
+    pub fn cmd_id(&self) -> u8 {
+        self.cmd_id
+    }
+}
+
+/// Enum carried as a single protocol byte, decoding fails on bytes without a variant
+macro_rules! byte_enum {
+    ($(#[$doc:meta])* $name:ident { $($(#[$variant_doc:meta])* $variant:ident = $value:literal,)* }) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(into = "u8", try_from = "u8")]
+        #[repr(u8)]
+        pub enum $name {
+            $($(#[$variant_doc])* $variant = $value,)*
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                value as u8
+            }
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = LivoxError;
+
+            fn try_from(value: u8) -> Result<Self> {
+                match value {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err(LivoxError::Decode(format!(
+                        "Invalid {}: {}",
+                        stringify!($name),
+                        value
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+byte_enum! {
+    /// Device type, as told by broadcast
+    DeviceType {
+        Hub = 0x00,
+        Mid40 = 0x01,
+        Tele15 = 0x02,
+        Horizon = 0x03,
+        Mid70 = 0x06,
+        Avia = 0x07,
+    }
+}
+
+byte_enum! {
+    /// Point cloud coordinate type
+    CoordinateType {
+        Cartesian = 0x00,
+        Spherical = 0x01,
+    }
+}
+
+byte_enum! {
+    /// How lidar gets its ip address
+    IpMode {
+        Dynamic = 0x00,
+        Static = 0x01,
+    }
+}
+
+byte_enum! {
+    /// Lidar working mode
+    LidarMode {
+        Normal = 0x01,
+        PowerSaving = 0x02,
+        Standby = 0x03,
+    }
+}
+
+byte_enum! {
+    /// Returns of each laser pulse turned into points
+    ReturnMode {
+        SingleFirst = 0x00,
+        SingleStrongest = 0x01,
+        Dual = 0x02,
+        Triple = 0x03,
+    }
+}
+
+byte_enum! {
+    /// IMU data push frequency
+    ImuPushFrequency {
+        /// IMU data not pushed
+        Off = 0x00,
+        Hz200 = 0x01,
+    }
+}
+
 /// Broadcast frame, received from lidar
-#[derive(Debug, Serialize, Deserialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, GetCmd)]
 pub struct Broadcast {
     cmd: Cmd,
-    broadcast_code: [u8; 16],
-    dev_type: u8,
+    pub broadcast_code: [u8; 16],
+    pub dev_type: DeviceType,
     _reserved: u16,
 }
 
-/// Handshake to connect lidar, ip address and ports is constantly configured in cfg.rs
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct HandshakeReq {
-    cmd: Cmd,
-    user_ip: [u8; 4],
-    data_port: u16,
-    cmd_port: u16,
-    imu_port: u16,
-}
-
-/// Handshake to connect lidar
-pub const HANDSHAKE_REQ: HandshakeReq = HandshakeReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x01,
-    },
-    user_ip: USER_IP,
-    data_port: DATA_PORT,
-    cmd_port: CMD_PORT,
-    imu_port: IMU_PORT,
+/// Command of broadcast frame
+pub const BROADCAST_CMD: Cmd = Cmd {
+    cmd_set: 0x00,
+    cmd_id: 0x00,
 };
 
-#[derive(Debug, Deserialize, CheckStatus)]
+impl Broadcast {
+    /// broadcast frame as sent by lidar, code longer than 16 bytes is truncated
+    pub fn new(broadcast_code: &str, dev_type: DeviceType) -> Self {
+        Broadcast {
+            cmd: BROADCAST_CMD,
+            broadcast_code: encode_broadcast_code(broadcast_code),
+            dev_type,
+            _reserved: 0,
+        }
+    }
+
+    /// broadcast code with trailing NUL bytes stripped, also known as serial number
+    pub fn broadcast_code_str(&self) -> String {
+        String::from_utf8_lossy(&self.broadcast_code)
+            .trim_end_matches('\0')
+            .to_string()
+    }
+}
+
+/// Handshake to connect lidar, ip address and ports are generated from `LidarConfig`
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x01, response = CommonResp)]
+pub struct HandshakeReq {
+    pub user_ip: [u8; 4],
+    pub data_port: u16,
+    pub cmd_port: u16,
+    pub imu_port: u16,
+}
+
+impl HandshakeReq {
+    pub fn new(user_ip: [u8; 4], data_port: u16, cmd_port: u16, imu_port: u16) -> Self {
+        HandshakeReq {
+            user_ip,
+            data_port,
+            cmd_port,
+            imu_port,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
 pub struct CommonResp {
-    ret_code: u8,
+    pub ret_code: u8,
 }
 
 /// Request device information
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct DeviceInfoReq(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x02, response = DeviceInfoResp)]
+pub struct DeviceInfoReq;
 
 /// Request device information
-pub const DEVICE_INFO_REQ: DeviceInfoReq = DeviceInfoReq(Cmd {
-    cmd_set: 0x00,
-    cmd_id: 0x02,
-});
+pub const DEVICE_INFO_REQ: DeviceInfoReq = DeviceInfoReq;
 
-#[derive(Debug, Deserialize, CheckStatus)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
 pub struct DeviceInfoResp {
-    ret_code: u8,
-    version: [u8; 4],
+    pub ret_code: u8,
+    pub version: [u8; 4],
 }
 
 /// Send Heartbeat frame to lidar
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct HeartbeatReq(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x03, response = HeartbeatResp)]
+pub struct HeartbeatReq;
 
 /// Send Heartbeat frame to lidar
-pub const HEARTBEAT_REQ: HeartbeatReq = HeartbeatReq(Cmd {
-    cmd_set: 0x00,
-    cmd_id: 0x03,
-});
+pub const HEARTBEAT_REQ: HeartbeatReq = HeartbeatReq;
 
-#[derive(Debug, Deserialize, CheckStatus)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
 pub struct HeartbeatResp {
-    ret_code: u8,
-    work_state: u8,
-    feature_msg: u8,
-    ack_msg: u32,
+    pub ret_code: u8,
+    pub work_state: u8,
+    pub feature_msg: u8,
+    pub ack_msg: u32,
 }
 
 /// Start or end lidar sample, 0x00: start, 0x01: end
-#[derive(Debug, Serialize, Deserialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x04, response = CommonResp)]
 pub struct SampleCtrlReq {
-    cmd: Cmd,
-    sample_ctrl: u8,
+    pub sample_ctrl: u8,
 }
 
 /// Start lidar sampling
-pub const SAMPLE_START_REQ: SampleCtrlReq = SampleCtrlReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x04,
-    },
-    sample_ctrl: 0x01,
-};
+pub const SAMPLE_START_REQ: SampleCtrlReq = SampleCtrlReq { sample_ctrl: 0x01 };
 
 /// End lidar sampling
-pub const SAMPLE_END_REQ: SampleCtrlReq = SampleCtrlReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x04,
-    },
-    sample_ctrl: 0x00,
-};
+pub const SAMPLE_END_REQ: SampleCtrlReq = SampleCtrlReq { sample_ctrl: 0x00 };
 
-/// Change point cloud coordinate type, 0x00: Cartesian, 0x01: Spherical
-#[derive(Debug, Serialize, Len, GetCmd)]
+/// Change point cloud coordinate type
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x05, response = CommonResp)]
 pub struct ChangeCoordinateReq {
-    cmd: Cmd,
-    coordinate_type: u8,
+    pub coordinate_type: CoordinateType,
+}
+
+impl ChangeCoordinateReq {
+    pub fn new(coordinate_type: CoordinateType) -> Self {
+        ChangeCoordinateReq { coordinate_type }
+    }
 }
 
 /// Change point cloud coordinate type to cartesian
 pub const CARTESIAN_COORDINATE_REQ: ChangeCoordinateReq = ChangeCoordinateReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x05,
-    },
-    coordinate_type: 0x00,
+    coordinate_type: CoordinateType::Cartesian,
 };
 
 /// Change point cloud coordinate type to spherical
 pub const SPHERICAL_COORDINATE_REQ: ChangeCoordinateReq = ChangeCoordinateReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x05,
-    },
-    coordinate_type: 0x01,
+    coordinate_type: CoordinateType::Spherical,
 };
 
 /// Disconnect from lidar
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct DisconnectReq(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x06, response = CommonResp)]
+pub struct DisconnectReq;
 
 /// Disconnect from lidar
-pub const DISCONNECT_REQ: DisconnectReq = DisconnectReq(Cmd {
+pub const DISCONNECT_REQ: DisconnectReq = DisconnectReq;
+
+/// Abnormal status pushed by lidar, status code shares the layout of `DataFrame::status_code`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, GetCmd)]
+pub struct AbnormalStatusMsg {
+    cmd: Cmd,
+    pub status_code: u32,
+}
+
+/// Command of abnormal status message
+pub const ABNORMAL_STATUS_CMD: Cmd = Cmd {
     cmd_set: 0x00,
-    cmd_id: 0x06,
-});
+    cmd_id: 0x07,
+};
+
+impl AbnormalStatusMsg {
+    pub fn new(status_code: u32) -> Self {
+        AbnormalStatusMsg {
+            cmd: ABNORMAL_STATUS_CMD,
+            status_code,
+        }
+    }
+}
 
 /// Configure ip address, net mask and gateway address
-#[derive(Debug, Serialize, Deserialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x08, response = CommonResp)]
 pub struct IpConfigReq {
-    cmd: Cmd,
-    ip_mode: u8,
-    ip_addr: [u8; 4],
-    net_mask: [u8; 4],
-    gw_addr: [u8; 4],
+    pub ip_mode: IpMode,
+    pub ip_addr: [u8; 4],
+    pub net_mask: [u8; 4],
+    pub gw_addr: [u8; 4],
 }
 
 impl IpConfigReq {
-    pub fn new(ip_mode: u8, ip_addr: [u8; 4], net_mask: [u8; 4], gw_addr: [u8; 4]) -> Self {
+    pub fn new(ip_mode: IpMode, ip_addr: [u8; 4], net_mask: [u8; 4], gw_addr: [u8; 4]) -> Self {
         IpConfigReq {
-            cmd: Cmd {
-                cmd_set: 0x00,
-                cmd_id: 0x08,
-            },
             ip_mode,
             ip_addr,
             net_mask,
@@ -193,150 +289,150 @@ impl IpConfigReq {
 }
 
 /// Get ip info of device
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct IpInfoReq(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x09, response = IpInfoResp)]
+pub struct IpInfoReq;
 
 /// Get ip information of device
-pub const IP_INFO_REQ: IpInfoReq = IpInfoReq(Cmd {
-    cmd_set: 0x00,
-    cmd_id: 0x09,
-});
+pub const IP_INFO_REQ: IpInfoReq = IpInfoReq;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct IpInfoResp {
+    pub ret_code: u8,
+    pub ip_mode: IpMode,
+    pub ip_addr: [u8; 4],
+    pub net_mask: [u8; 4],
+    pub gw_addr: [u8; 4],
+}
 
 /// Reboot device
-#[derive(Debug, Serialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x0A, response = CommonResp)]
 pub struct RebootReq {
-    cmd: Cmd,
-    timeout: u16,
+    pub timeout: u16,
 }
 
 impl RebootReq {
     pub fn new(timeout: u16) -> Self {
-        RebootReq {
-            cmd: Cmd {
-                cmd_set: 0x00,
-                cmd_id: 0x0A,
-            },
-            timeout,
-        }
+        RebootReq { timeout }
     }
 }
 
 /// Reboot device immediately
-pub const REBOOT_IMMEDIATE_REQ: RebootReq = RebootReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x0A,
-    },
-    timeout: 0x00,
-};
+pub const REBOOT_IMMEDIATE_REQ: RebootReq = RebootReq { timeout: 0x00 };
 
 /// Set flash configuration, won't lose after reboot
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x0B, response = WriteFlashResp)]
 pub struct WriteFlashReq {
-    cmd: Cmd,
-    high_sensitivity: bool,
-    repetitive_scan: bool,
-    slot_id: u8,
+    #[livox(key = FLASH_KEY_HIGH_SENSITIVITY)]
+    pub high_sensitivity: bool,
+    #[livox(key = FLASH_KEY_SCAN_PATTERN)]
+    pub repetitive_scan: bool,
+    #[livox(key = FLASH_KEY_SLOT_ID)]
+    pub slot_id: u8,
 }
 
-impl Serialize for WriteFlashReq {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut serializer = serializer.serialize_tuple_struct("WriteFlashReq", 17)?;
-        serializer.serialize_field(&self.cmd)?;
-        serializer.serialize_field(&0x01u16)?;
-        serializer.serialize_field(&0x01u16)?;
-        serializer.serialize_field(&self.high_sensitivity)?;
-        serializer.serialize_field(&0x02u16)?;
-        serializer.serialize_field(&0x01u16)?;
-        serializer.serialize_field(&self.repetitive_scan)?;
-        serializer.serialize_field(&0x03u16)?;
-        serializer.serialize_field(&0x01u16)?;
-        serializer.serialize_field(&self.slot_id)?;
-        serializer.end()
-    }
-}
+/// Flash configuration key of high sensitivity, 0x00: off, 0x01: on
+const FLASH_KEY_HIGH_SENSITIVITY: u16 = 0x01;
+/// Flash configuration key of scan pattern, 0x00: non-repetitive, 0x01: repetitive
+const FLASH_KEY_SCAN_PATTERN: u16 = 0x02;
+/// Flash configuration key of slot id
+const FLASH_KEY_SLOT_ID: u16 = 0x03;
 
 impl WriteFlashReq {
-    pub fn new(high_sensitivity: bool, repetitive_scan: bool, slot_id: u8) -> Self {
+    /// fails unless slot id is within 1 to 9
+    pub fn new(high_sensitivity: bool, repetitive_scan: bool, slot_id: u8) -> Result<Self> {
         match slot_id {
-            0x01u8..=0x09u8 => WriteFlashReq {
-                cmd: Cmd {
-                    cmd_set: 0x00,
-                    cmd_id: 0x0B,
-                },
+            0x01u8..=0x09u8 => Ok(WriteFlashReq {
                 high_sensitivity,
                 repetitive_scan,
                 slot_id,
-            },
-            _ => panic!("Invalid slot_id: {}", slot_id),
+            }),
+            _ => Err(LivoxError::InvalidArgument(format!(
+                "Invalid slot id: {}",
+                slot_id
+            ))),
         }
     }
 }
 
 /// Set default flash configuration
 pub const WRITE_FLASH_DEFAULT_REQ: WriteFlashReq = WriteFlashReq {
-    cmd: Cmd {
-        cmd_set: 0x00,
-        cmd_id: 0x0B,
-    },
     high_sensitivity: true,
     repetitive_scan: false,
     slot_id: 0x01,
 };
 
-impl Len for WriteFlashReq {
-    fn len() -> u16 {
-        (mem::size_of::<u8>() * 3 + mem::size_of::<u16>() * 2 * 3) as u16 + Cmd::len()
-    }
+/// Response to writing or reading flash configuration, key and error code of the
+/// first entry lidar rejected if any
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct WriteFlashResp {
+    pub ret_code: u8,
+    pub error_key: u16,
+    pub error_code: u8,
+}
+
+/// Read flash configuration, every key written by `WriteFlashReq` is queried
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x00, cmd_id = 0x0C, response = ReadFlashResp)]
+pub struct ReadFlashReq {
+    key_num: u8,
+    keys: [u16; 3],
+}
+
+/// Read high sensitivity, scan pattern and slot id from flash
+pub const READ_FLASH_REQ: ReadFlashReq = ReadFlashReq {
+    key_num: 3,
+    keys: [
+        FLASH_KEY_HIGH_SENSITIVITY,
+        FLASH_KEY_SCAN_PATTERN,
+        FLASH_KEY_SLOT_ID,
+    ],
+};
+
+/// Flash configuration read back, entries are meaningless unless `ret_code` is 0
+#[derive(Debug, Clone, PartialEq, KeyValue, CheckStatus)]
+pub struct ReadFlashResp {
+    pub ret_code: u8,
+    pub error_key: u16,
+    pub error_code: u8,
+    #[livox(key = FLASH_KEY_HIGH_SENSITIVITY)]
+    pub high_sensitivity: bool,
+    #[livox(key = FLASH_KEY_SCAN_PATTERN)]
+    pub repetitive_scan: bool,
+    #[livox(key = FLASH_KEY_SLOT_ID)]
+    pub slot_id: u8,
 }
 
 /// Set Lidar mode
-/// 0x01: Normal mode
-/// 0x02: Low power mode
-/// 0x03: Standby mode
-#[derive(Debug, Serialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x00, response = CommonResp)]
 pub struct ModeSwitchReq {
-    cmd: Cmd,
-    mode: u8,
+    pub mode: LidarMode,
 }
 
 impl ModeSwitchReq {
-    pub fn new(mode: u8) -> Self {
-        match mode {
-            0x01u8..=0x03u8 => ModeSwitchReq {
-                cmd: Cmd {
-                    cmd_set: 0x01,
-                    cmd_id: 0x00,
-                },
-                mode,
-            },
-            _ => panic!("Invalid lidar mode: {}", mode),
-        }
+    pub fn new(mode: LidarMode) -> Self {
+        ModeSwitchReq { mode }
     }
 }
 
 /// Write outer param
-#[derive(Debug, Serialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x01, response = CommonResp)]
 pub struct WriteOuterParameters {
-    cmd: Cmd,
-    roll: f32,
-    pitch: f32,
-    yaw: f32,
-    x: i32,
-    y: i32,
-    z: i32,
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
 }
 
 impl WriteOuterParameters {
     pub fn new(roll: f32, pitch: f32, yaw: f32, x: i32, y: i32, z: i32) -> Self {
         WriteOuterParameters {
-            cmd: Cmd {
-                cmd_set: 0x01,
-                cmd_id: 0x01,
-            },
             roll,
             pitch,
             yaw,
@@ -348,68 +444,64 @@ impl WriteOuterParameters {
 }
 
 /// Get outer parameters
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct ReadOuterParameters(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x02, response = ReadOuterParametersResp)]
+pub struct ReadOuterParameters;
 
 /// Read outer parameters of lidar
-pub const READ_OUTER_PARAMETERS: ReadOuterParameters = ReadOuterParameters(Cmd {
-    cmd_set: 0x01,
-    cmd_id: 0x02,
-});
-
-/// Set Lidar Return Mode:
-/// 0x00: Single Return First
-/// 0x01: Single Return Strongest
-/// 0x02: Dual Return
-/// 0x03: Triple Return
-#[derive(Debug, Serialize, Len, GetCmd)]
+pub const READ_OUTER_PARAMETERS: ReadOuterParameters = ReadOuterParameters;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct ReadOuterParametersResp {
+    pub ret_code: u8,
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Set Lidar Return Mode
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x06, response = CommonResp)]
 pub struct SetReturnMode {
-    cmd: Cmd,
-    mode: u8,
+    pub mode: ReturnMode,
 }
 
 impl SetReturnMode {
-    pub fn new(mode: u8) -> Self {
-        match mode {
-            0x00u8..=0x03u8 => SetReturnMode {
-                cmd: Cmd {
-                    cmd_set: 0x01,
-                    cmd_id: 0x06,
-                },
-                mode,
-            },
-            _ => panic!("Invalid return mode: {}", mode),
-        }
+    pub fn new(mode: ReturnMode) -> Self {
+        SetReturnMode { mode }
     }
 }
 
 /// Get Lidar Return Mode
-#[derive(Debug, Serialize, Len, GetCmd)]
-pub struct GetReturnMode(Cmd);
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x07, response = GetReturnModeResp)]
+pub struct GetReturnMode;
 
-pub const GET_RETURN_MODE: GetReturnMode = GetReturnMode(Cmd {
-    cmd_set: 0x01,
-    cmd_id: 0x07,
-});
+pub const GET_RETURN_MODE: GetReturnMode = GetReturnMode;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct GetReturnModeResp {
+    pub ret_code: u8,
+    pub mode: ReturnMode,
+}
 
 /// Update UTC Synchronize Time
-#[derive(Debug, Serialize, Len, GetCmd)]
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x0A, response = CommonResp)]
 pub struct UpdateUtcSyncTime {
-    cmd: Cmd,
-    year: u8,
-    month: u8,
-    day: u8,
-    hour: u8,
-    microsecond: u32,
+    pub year: u8,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub microsecond: u32,
 }
 
 impl UpdateUtcSyncTime {
     pub fn new(year: u8, month: u8, day: u8, hour: u8, microsecond: u32) -> Self {
         UpdateUtcSyncTime {
-            cmd: Cmd {
-                cmd_set: 0x01,
-                cmd_id: 0x0A,
-            },
             year,
             month,
             day,
@@ -419,57 +511,218 @@ impl UpdateUtcSyncTime {
     }
 }
 
+/// Turn rain and fog suppression on or off, 0x00: off, 0x01: on
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x03, response = CommonResp)]
+pub struct RainFogSuppressionReq {
+    pub state: u8,
+}
+
+impl RainFogSuppressionReq {
+    pub fn new(on: bool) -> Self {
+        RainFogSuppressionReq { state: on.into() }
+    }
+}
+
+/// Turn fan on or off, 0x00: off, 0x01: on
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x04, response = CommonResp)]
+pub struct FanControlReq {
+    pub state: u8,
+}
+
+impl FanControlReq {
+    pub fn new(on: bool) -> Self {
+        FanControlReq { state: on.into() }
+    }
+}
+
+/// Get fan state
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x05, response = GetFanStateResp)]
+pub struct GetFanState;
+
+pub const GET_FAN_STATE: GetFanState = GetFanState;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct GetFanStateResp {
+    pub ret_code: u8,
+    /// 0x00: off, 0x01: on
+    pub state: u8,
+}
+
+/// Set IMU push frequency
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x08, response = CommonResp)]
+pub struct SetImuPushFrequency {
+    pub frequency: ImuPushFrequency,
+}
+
+impl SetImuPushFrequency {
+    pub fn new(frequency: ImuPushFrequency) -> Self {
+        SetImuPushFrequency { frequency }
+    }
+}
+
+/// Get IMU push frequency
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x01, cmd_id = 0x09, response = GetImuPushFrequencyResp)]
+pub struct GetImuPushFrequency;
+
+pub const GET_IMU_PUSH_FREQUENCY: GetImuPushFrequency = GetImuPushFrequency;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct GetImuPushFrequencyResp {
+    pub ret_code: u8,
+    pub frequency: ImuPushFrequency,
+}
+
+/// Data segment of a response, command echoed back followed by response body
+#[derive(Debug, Serialize)]
+pub struct Ack<T> {
+    cmd: Cmd,
+    resp: T,
+}
+
+impl<T> Ack<T> {
+    pub fn new(cmd: Cmd, resp: T) -> Self {
+        Ack { cmd, resp }
+    }
+}
+
+impl<T: Len> Len for Ack<T> {
+    const LEN: u16 = Cmd::LEN + T::LEN;
+}
+
+impl<T> GetCmd for Ack<T> {
+    fn cmd(&self) -> Cmd {
+        self.cmd
+    }
+}
+
+/// Type of control frame, in byte 4 of header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CmdType {
+    /// command sent to lidar
+    Cmd = 0x00,
+    /// response to a command
+    Ack = 0x01,
+    /// message pushed by lidar, no response expected
+    Msg = 0x02,
+}
+
+impl TryFrom<u8> for CmdType {
+    type Error = LivoxError;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0x00 => Ok(CmdType::Cmd),
+            0x01 => Ok(CmdType::Ack),
+            0x02 => Ok(CmdType::Msg),
+            _ => Err(LivoxError::Decode(format!(
+                "Invalid command type: {}",
+                value
+            ))),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ControlFrame<'a, T> {
+    cmd_type: CmdType,
     seq_num: u16,
     frame_seg: &'a T,
 }
 
 impl<'a, T> ControlFrame<'a, T> {
+    /// command frame sent to lidar
     pub fn new(seq_num: u16, frame_seg: &'a T) -> Self {
-        ControlFrame { seq_num, frame_seg }
+        Self::with_cmd_type(CmdType::Cmd, seq_num, frame_seg)
+    }
+
+    /// response frame, as sent by lidar
+    pub fn ack(seq_num: u16, frame_seg: &'a T) -> Self {
+        Self::with_cmd_type(CmdType::Ack, seq_num, frame_seg)
+    }
+
+    /// message frame, as pushed by lidar
+    pub fn msg(seq_num: u16, frame_seg: &'a T) -> Self {
+        Self::with_cmd_type(CmdType::Msg, seq_num, frame_seg)
     }
 
+    pub fn with_cmd_type(cmd_type: CmdType, seq_num: u16, frame_seg: &'a T) -> Self {
+        ControlFrame {
+            cmd_type,
+            seq_num,
+            frame_seg,
+        }
+    }
+
+    /// serialize frame, length is taken from the data segment as hub commands vary in length
     pub fn serialize(&self) -> Result<Vec<u8>>
     where
-        T: Serialize + Len,
+        T: Serialize,
     {
-        let crc16 = Crc::<u16>::new(&CRC_16_MCRF4XX);
-        let mut digest16 = crc16.digest_with_initial(CRC16_INIT);
-
-        let mut digest32 = crc32fast::Hasher::new_with_initial(CRC32_INIT);
-
-        let buffer_len = Self::len();
+        let seg_len = bincode::serialized_size(self.frame_seg)
+            .map_err(|e| LivoxError::Encode(format!("Failed to size data segment: {}", e)))?;
+        let mut buf = vec![0; HEADER_LEN + seg_len as usize + CRC32_LEN];
+        let len = self.serialize_into(&mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
 
-        let mut buf = Vec::with_capacity(buffer_len as usize);
+    /// serialize frame into the head of `buf` without allocating, return its length
+    pub fn serialize_into(&self, buf: &mut [u8]) -> Result<usize>
+    where
+        T: Serialize,
+    {
+        let buf_len = buf.len();
+        let too_short = || {
+            LivoxError::Encode(format!(
+                "Buffer of {} bytes is too short for control frame",
+                buf_len
+            ))
+        };
+        let seg_space = buf_len
+            .checked_sub(HEADER_LEN + CRC32_LEN)
+            .ok_or_else(too_short)?;
+
+        // serialize data segment right after header, leaving room for CRC32
+        let mut seg = &mut buf[HEADER_LEN..HEADER_LEN + seg_space];
+        bincode::serialize_into(&mut seg, self.frame_seg).map_err(|e| match *e {
+            bincode::ErrorKind::Io(_) => too_short(),
+            e => LivoxError::Encode(format!("Failed to serialize data segment: {}", e)),
+        })?;
+        let len = HEADER_LEN + seg_space - seg.len() + CRC32_LEN;
+        let buffer_len = u16::try_from(len).map_err(|_| {
+            LivoxError::Encode(format!("Control frame of {} bytes is too long", len))
+        })?;
 
         // sof
-        buf.push(0xAAu8);
+        buf[0] = SOF;
 
         // version of communication protocol
-        buf.push(0x01u8);
+        buf[1] = 0x01;
 
-        // length of data frame
-        buf.extend((buffer_len).to_le_bytes());
+        buf[2..4].copy_from_slice(&buffer_len.to_le_bytes());
 
-        // command type, always CMD: 0x00
-        buf.push(0x00u8);
+        // command type, CMD: 0x00, ACK: 0x01, MSG: 0x02
+        buf[4] = self.cmd_type as u8;
 
-        buf.extend(self.seq_num.to_le_bytes());
+        buf[5..7].copy_from_slice(&self.seq_num.to_le_bytes());
 
         // calculate CRC16
-        digest16.update(&buf);
-        buf.extend(digest16.finalize().to_le_bytes());
-
-        // serialize data segment
-        bincode::serialize_into(&mut buf, &self.frame_seg)?;
+        let mut digest16 = CRC16.digest_with_initial(CRC16_INIT);
+        digest16.update(&buf[..7]);
+        buf[7..9].copy_from_slice(&digest16.finalize().to_le_bytes());
 
         // calculate CRC32
-        digest32.update(&buf);
-
-        buf.extend(digest32.finalize().to_le_bytes());
+        let mut digest32 = crc32fast::Hasher::new_with_initial(CRC32_INIT);
+        digest32.update(&buf[..len - CRC32_LEN]);
+        buf[len - CRC32_LEN..len].copy_from_slice(&digest32.finalize().to_le_bytes());
 
-        Ok(buf)
+        Ok(len)
     }
 }
 
@@ -477,108 +730,397 @@ impl<T> Len for ControlFrame<'_, T>
 where
     T: Len,
 {
-    fn len() -> u16 {
-        const SOF_LEN: usize = mem::size_of::<u8>();
-        const VERSION_LEN: usize = mem::size_of::<u8>();
-        const LENGTH_LEN: usize = mem::size_of::<u16>();
-        const CMD_TYPE_LEN: usize = mem::size_of::<u8>();
-        const SEQ_NUM_LEN: usize = mem::size_of::<u16>();
-        const CRC16_LEN: usize = mem::size_of::<u16>();
-        const CRC32_LEN: usize = mem::size_of::<u32>();
-        (SOF_LEN + VERSION_LEN + LENGTH_LEN + CMD_TYPE_LEN + SEQ_NUM_LEN + CRC16_LEN + CRC32_LEN)
-            as u16
-            + T::len()
-    }
+    const LEN: u16 = (HEADER_LEN + CRC32_LEN) as u16 + T::LEN;
 }
 
 /// deserialize from buffer, return tuple of sequence number and inner frame
-pub fn deserialize_resp<'a>(buffer: &'a [u8]) -> Result<(u16, Cmd, &'a [u8])> {
-    let len = u16::from_le_bytes(buffer[2..=3].try_into()?) as usize;
+pub fn deserialize_resp(buffer: &[u8]) -> Result<(u16, Cmd, &[u8])> {
+    deserialize_frame(buffer).map(|(_, seq_num, cmd, frame)| (seq_num, cmd, frame))
+}
+
+/// header, command and CRC32, the least a control frame is made of
+const MIN_FRAME_LEN: usize = HEADER_LEN + Cmd::LEN as usize + CRC32_LEN;
+
+/// deserialize from buffer, return tuple of frame type, sequence number and inner frame;
+/// short, oversized or garbage buffers are rejected before anything is indexed
+pub fn deserialize_frame(buffer: &[u8]) -> Result<(CmdType, u16, Cmd, &[u8])> {
+    if buffer.len() < MIN_FRAME_LEN {
+        return Err(LivoxError::LengthMismatch {
+            expected: MIN_FRAME_LEN,
+            found: buffer.len(),
+        });
+    }
+    if buffer[0] != SOF {
+        return Err(LivoxError::Decode(format!(
+            "Invalid start of frame: 0x{:02X}",
+            buffer[0]
+        )));
+    }
+    let len = u16::from_le_bytes([buffer[2], buffer[3]]) as usize;
     if buffer.len() != len {
-        return Err(anyhow!(
-            concat!(
-                "Cannot deserialize the serial due to an incompatible length:",
-                "the length of the serial is {}, ",
-                "while the length of the <ControlFrame> frame is {}."
-            ),
-            buffer.len(),
-            len,
-        ));
+        return Err(LivoxError::LengthMismatch {
+            expected: len,
+            found: buffer.len(),
+        });
     }
 
-    let crc16 = Crc::<u16>::new(&CRC_16_MCRF4XX);
-    let mut digest16 = crc16.digest_with_initial(CRC16_INIT);
+    let mut digest16 = CRC16.digest_with_initial(CRC16_INIT);
     digest16.update(&buffer[..7]);
-    let checksum_recv = u16::from_le_bytes(buffer[7..=8].try_into()?);
+    let checksum_recv = u16::from_le_bytes([buffer[7], buffer[8]]);
     let checksum_cal = digest16.finalize();
     if checksum_cal != checksum_recv {
-        return Err(anyhow!(
-            concat!(
-                "Crc16 for header of <ControlFrame> failed",
-                "checksum received is 0x{:X?}, ",
-                "while the calculated checksum is 0x{:X?}.",
-            ),
-            checksum_recv,
-            checksum_cal
-        ));
+        return Err(LivoxError::CrcMismatch {
+            kind: CrcKind::Header,
+            received: checksum_recv.into(),
+            calculated: checksum_cal.into(),
+        });
     }
 
     let mut digest32 = crc32fast::Hasher::new_with_initial(CRC32_INIT);
     digest32.update(&buffer[..len - 4]);
 
-    let checksum_recv = u32::from_le_bytes(buffer[len - 4..].try_into()?);
+    let mut checksum_recv = [0u8; 4];
+    checksum_recv.copy_from_slice(&buffer[len - 4..]);
+    let checksum_recv = u32::from_le_bytes(checksum_recv);
     let checksum_cal = digest32.finalize();
 
     if checksum_cal != checksum_recv {
-        return Err(anyhow!(
-            concat!(
-                "Crc32 for frame of <ControlFrame> failed",
-                "checksum received is {:X?}, ",
-                "while the calculated checksum is {:X?}.",
-            ),
-            checksum_recv,
-            checksum_cal
-        ));
+        return Err(LivoxError::CrcMismatch {
+            kind: CrcKind::Frame,
+            received: checksum_recv,
+            calculated: checksum_cal,
+        });
     }
 
-    let seq_num = u16::from_le_bytes(buffer[5..=6].try_into()?);
+    let cmd_type = CmdType::try_from(buffer[4])?;
+    let seq_num = u16::from_le_bytes([buffer[5], buffer[6]]);
 
     bincode::deserialize(&buffer[9..11])
-        .map_err(|e| anyhow!("Failed to deserialize data segment: {}", e))
-        .map(|cmd| (seq_num, cmd, &buffer[11..len - 4]))
+        .map_err(|e| LivoxError::Decode(format!("Failed to deserialize data segment: {}", e)))
+        .map(|cmd| (cmd_type, seq_num, cmd, &buffer[11..len - 4]))
+}
+
+/// deserialize broadcast frame received on broadcast port
+pub fn deserialize_broadcast(buffer: &[u8]) -> Result<Broadcast> {
+    let (_, cmd, _) = deserialize_resp(buffer)?;
+    if cmd != BROADCAST_CMD {
+        return Err(LivoxError::Decode(format!(
+            "Frame on {:?} is not a broadcast frame",
+            cmd
+        )));
+    }
+
+    // data segment of broadcast frame starts from cmd
+    bincode::deserialize(&buffer[9..buffer.len() - 4])
+        .map_err(|e| LivoxError::Decode(format!("Failed to deserialize broadcast frame: {}", e)))
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::lidar_frame::cfg::{CMD_PORT, DATA_PORT, IMU_PORT, USER_IP};
 
     #[test]
     fn test_serialize() {
-        let control_frame = ControlFrame::new(0x11, &HANDSHAKE_REQ);
+        let handshake_req = HandshakeReq::new(USER_IP, DATA_PORT, CMD_PORT, IMU_PORT);
+        let control_frame = ControlFrame::new(0x11, &handshake_req);
         let serialized = control_frame.serialize().unwrap();
 
         assert_eq!(
             serialized,
             vec![
-                170, 1, 27, 0, 0, 17, 0, 29, 194, 0, 1, 50, 1, 168, 192, 80, 195, 81, 195, 82, 195,
-                34, 129, 121, 236
+                170, 1, 25, 0, 0, 17, 0, 149, 212, 0, 1, 192, 168, 1, 50, 80, 195, 81, 195, 82,
+                195, 105, 142, 213, 13
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_into() {
+        let handshake_req = HandshakeReq::new(USER_IP, DATA_PORT, CMD_PORT, IMU_PORT);
+        let control_frame = ControlFrame::new(0x11, &handshake_req);
+
+        let mut buffer = [0xFF; 64];
+        let len = control_frame.serialize_into(&mut buffer).unwrap();
+        assert_eq!(len, ControlFrame::<HandshakeReq>::LEN as usize);
+        assert_eq!(&buffer[..len], control_frame.serialize().unwrap());
+
+        let mut exact = [0; ControlFrame::<HandshakeReq>::LEN as usize];
+        assert_eq!(
+            control_frame.serialize_into(&mut exact).unwrap(),
+            exact.len()
+        );
+        assert!(matches!(
+            control_frame.serialize_into(&mut buffer[..len - 1]),
+            Err(LivoxError::Encode(_))
+        ));
+        assert!(control_frame.serialize_into(&mut buffer[..4]).is_err());
+    }
+
+    #[test]
+    fn test_serialize_lidar_commands() {
+        let serialized = ControlFrame::new(0x21, &READ_FLASH_REQ)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 22, 0, 0, 33, 0, 203, 8, 0, 12, 3, 1, 0, 2, 0, 3, 0, 93, 33, 94, 213]
+        );
+
+        let rain_fog_req = RainFogSuppressionReq::new(true);
+        let serialized = ControlFrame::new(0x21, &rain_fog_req).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 16, 0, 0, 33, 0, 83, 51, 1, 3, 1, 241, 173, 136, 224]
+        );
+
+        let fan_req = FanControlReq::new(false);
+        let serialized = ControlFrame::new(0x21, &fan_req).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 16, 0, 0, 33, 0, 83, 51, 1, 4, 0, 160, 11, 206, 216]
+        );
+
+        let serialized = ControlFrame::new(0x21, &GET_FAN_STATE).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 1, 5, 160, 94, 135, 24]
+        );
+
+        let imu_req = SetImuPushFrequency::new(ImuPushFrequency::Hz200);
+        let serialized = ControlFrame::new(0x21, &imu_req).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 16, 0, 0, 33, 0, 83, 51, 1, 8, 1, 58, 116, 124, 3]
+        );
+
+        let serialized = ControlFrame::new(0x21, &GET_IMU_PUSH_FREQUENCY)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 1, 9, 139, 18, 49, 17]
+        );
+
+        let serialized = ControlFrame::new(0x21, &IP_INFO_REQ).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 0, 9, 202, 35, 42, 8]
+        );
+
+        let serialized = ControlFrame::new(0x21, &READ_OUTER_PARAMETERS)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 1, 2, 3, 203, 227, 134]
+        );
+
+        let serialized = ControlFrame::new(0x21, &GET_RETURN_MODE)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 1, 7, 140, 63, 137, 246]
+        );
+    }
+
+    #[test]
+    fn test_livox_command_derive() {
+        assert_eq!(HEARTBEAT_REQ.cmd(), Cmd::new(0x00, 0x03));
+        assert_eq!(HeartbeatReq::LEN, 2);
+        assert_eq!(WriteFlashReq::LEN, 17);
+
+        let req = WriteFlashReq::new(true, false, 8).unwrap();
+        let serialized = bincode::serialize(&req).unwrap();
+        assert_eq!(serialized.len(), WriteFlashReq::LEN as usize);
+        assert_eq!(
+            serialized,
+            vec![0, 11, 1, 0, 1, 0, 1, 2, 0, 1, 0, 0, 3, 0, 1, 0, 8]
+        );
+
+        // lidar may list keys in any order, but each only once
+        let reordered = [0, 11, 3, 0, 1, 0, 8, 1, 0, 1, 0, 1, 2, 0, 1, 0, 0];
+        assert_eq!(
+            bincode::deserialize::<WriteFlashReq>(&reordered).unwrap(),
+            req
+        );
+        let repeated = [0, 11, 3, 0, 1, 0, 8, 3, 0, 1, 0, 8, 2, 0, 1, 0, 0];
+        assert!(bincode::deserialize::<WriteFlashReq>(&repeated).is_err());
+
+        let wrong_cmd = bincode::serialize(&DISCONNECT_REQ).unwrap();
+        assert!(bincode::deserialize::<HeartbeatReq>(&wrong_cmd).is_err());
+    }
+
+    #[test]
+    fn test_serialize_lidar_responses() {
+        let write_flash_resp = WriteFlashResp {
+            ret_code: 0,
+            error_key: 0,
+            error_code: 0,
+        };
+        let ack = Ack::new(Cmd::new(0x00, 0x0B), write_flash_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 19, 0, 1, 33, 0, 67, 116, 0, 11, 0, 0, 0, 0, 112, 63, 78, 113]
+        );
+
+        let read_flash_resp = ReadFlashResp {
+            ret_code: 0,
+            error_key: 0,
+            error_code: 0,
+            high_sensitivity: true,
+            repetitive_scan: false,
+            slot_id: 3,
+        };
+        let ack = Ack::new(Cmd::new(0x00, 0x0C), read_flash_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized.len() as u16,
+            ControlFrame::<Ack<ReadFlashResp>>::LEN
+        );
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 34, 0, 1, 33, 0, 214, 171, 0, 12, 0, 0, 0, 0, 1, 0, 1, 0, 1, 2, 0, 1, 0, 0,
+                3, 0, 1, 0, 3, 70, 211, 136, 202
             ]
         );
+
+        let fan_resp = GetFanStateResp {
+            ret_code: 0,
+            state: 1,
+        };
+        let ack = Ack::new(Cmd::new(0x01, 0x05), fan_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 17, 0, 1, 33, 0, 203, 98, 1, 5, 0, 1, 25, 97, 122, 80]
+        );
+
+        let imu_resp = GetImuPushFrequencyResp {
+            ret_code: 0,
+            frequency: ImuPushFrequency::Hz200,
+        };
+        let ack = Ack::new(Cmd::new(0x01, 0x09), imu_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 17, 0, 1, 33, 0, 203, 98, 1, 9, 0, 1, 125, 152, 96, 89]
+        );
+
+        let ip_info_resp = IpInfoResp {
+            ret_code: 0,
+            ip_mode: IpMode::Static,
+            ip_addr: [192, 168, 1, 3],
+            net_mask: [255, 255, 255, 0],
+            gw_addr: [192, 168, 1, 1],
+        };
+        let ack = Ack::new(Cmd::new(0x00, 0x09), ip_info_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 29, 0, 1, 33, 0, 251, 21, 0, 9, 0, 1, 192, 168, 1, 3, 255, 255, 255, 0,
+                192, 168, 1, 1, 248, 80, 106, 102
+            ]
+        );
+
+        let outer_parameters_resp = ReadOuterParametersResp {
+            ret_code: 0,
+            roll: 1.5,
+            pitch: -2.0,
+            yaw: 90.0,
+            x: 100,
+            y: -200,
+            z: 300,
+        };
+        let ack = Ack::new(Cmd::new(0x01, 0x02), outer_parameters_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 40, 0, 1, 33, 0, 126, 231, 1, 2, 0, 0, 0, 192, 63, 0, 0, 0, 192, 0, 0, 180,
+                66, 100, 0, 0, 0, 56, 255, 255, 255, 44, 1, 0, 0, 193, 170, 2, 13
+            ]
+        );
+
+        let return_mode_resp = GetReturnModeResp {
+            ret_code: 0,
+            mode: ReturnMode::Dual,
+        };
+        let ack = Ack::new(Cmd::new(0x01, 0x07), return_mode_resp);
+        let serialized = ControlFrame::ack(0x21, &ack).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 17, 0, 1, 33, 0, 203, 98, 1, 7, 0, 2, 205, 228, 247, 202]
+        );
+    }
+
+    #[test]
+    fn test_byte_enum() {
+        let req = SetReturnMode::new(ReturnMode::Dual);
+        let serialized = bincode::serialize(&req).unwrap();
+        assert_eq!(serialized, vec![0x01, 0x06, 0x02]);
+        assert_eq!(
+            bincode::deserialize::<SetReturnMode>(&serialized).unwrap(),
+            req
+        );
+        assert!(bincode::deserialize::<SetReturnMode>(&[0x01, 0x06, 0x07]).is_err());
+        assert_eq!(ModeSwitchReq::LEN, 3);
+        assert!(LidarMode::try_from(0x00).is_err());
+        assert!(WriteFlashReq::new(true, false, 10).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_frame_type() {
+        let msg = AbnormalStatusMsg::new(0x0000_0040);
+        let serialized = ControlFrame::msg(0x05, &msg).serialize().unwrap();
+        assert_eq!(serialized[4], 0x02);
+
+        let (cmd_type, seq_num, cmd, frame) = deserialize_frame(&serialized).unwrap();
+        assert_eq!(cmd_type, CmdType::Msg);
+        assert_eq!(seq_num, 0x05);
+        assert_eq!(cmd, ABNORMAL_STATUS_CMD);
+        assert_eq!(frame, 0x0000_0040u32.to_le_bytes());
+
+        let mut corrupted = serialized.clone();
+        corrupted[11] ^= 0xFF;
+        assert!(matches!(
+            deserialize_frame(&corrupted),
+            Err(LivoxError::CrcMismatch {
+                kind: CrcKind::Frame,
+                ..
+            })
+        ));
+        corrupted[5] ^= 0xFF;
+        assert!(matches!(
+            deserialize_frame(&corrupted),
+            Err(LivoxError::CrcMismatch {
+                kind: CrcKind::Header,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_broadcast() {
+        let broadcast = Broadcast {
+            cmd: BROADCAST_CMD,
+            broadcast_code: *b"0TFDG3B006H2Z11\0",
+            dev_type: DeviceType::Horizon,
+            _reserved: 0,
+        };
+        let serialized = ControlFrame::new(0x11, &broadcast).serialize().unwrap();
+
+        let deserialized = deserialize_broadcast(&serialized).unwrap();
+        assert_eq!(deserialized.broadcast_code_str(), "0TFDG3B006H2Z11");
+        assert_eq!(deserialized.dev_type, DeviceType::Horizon);
+        assert!(deserialize_broadcast(
+            &ControlFrame::new(0x11, &HEARTBEAT_REQ).serialize().unwrap()
+        )
+        .is_err());
     }
 }
-// #[test]
-// fn test_deserialize() {
-//     let mut control_frame = ControlFrame::new(CmdType::Cmd, 0x0, Broadcast::new());
-//     let serial = vec![
-//         0xAA, 0x1, 0x22, 0x0, 0x0, 0x11, 0x0, 0xA8, 0x47, 0x0, 0x0, 0x1, 0x1, 0x1, 0x1, 0x1,
-//         0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x0, 0x45, 0x11, 0x9E, 0xBE,
-//         0x83, 0x49,
-//     ];
-//     control_frame.deserialize(&serial).unwrap();
-//     assert_eq!(control_frame.sof, 0xAA);
-//     assert_eq!(control_frame.version, 0x1);
-//     assert_eq!(control_frame.cmd_type, CmdType::Cmd);
-//     assert_eq!(control_frame.seq_num, 0x11);
-// }
-// }
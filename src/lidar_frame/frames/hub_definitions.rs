@@ -0,0 +1,551 @@
+use super::*;
+
+use std::marker::PhantomData;
+
+/// Entries preceded by their count in a single byte, the way hub commands list lidars
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Counted<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for Counted<T> {
+    fn from(entries: Vec<T>) -> Self {
+        Counted(entries)
+    }
+}
+
+impl<T: Serialize> Serialize for Counted<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+
+        let count = u8::try_from(self.0.len()).map_err(|_| {
+            serde::ser::Error::custom(format!("Too many entries to count: {}", self.0.len()))
+        })?;
+        let mut serializer = serializer.serialize_tuple(1 + self.0.len())?;
+        serializer.serialize_element(&count)?;
+        for entry in &self.0 {
+            serializer.serialize_element(entry)?;
+        }
+        serializer.end()
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Counted<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct CountedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> serde::de::Visitor<'de> for CountedVisitor<T> {
+            type Value = Counted<T>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("count followed by as many entries")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Counted<T>, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let count: u8 = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let entries = (0..count as usize)
+                    .map(|index| {
+                        seq.next_element()?
+                            .ok_or_else(|| serde::de::Error::invalid_length(1 + index, &self))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Counted(entries))
+            }
+        }
+
+        // count is only known once read, so ask for as many elements as could be counted
+        deserializer.deserialize_tuple(1 + u8::MAX as usize, CountedVisitor(PhantomData))
+    }
+}
+
+/// broadcast code padded with NUL bytes, code longer than 16 bytes is truncated
+pub fn encode_broadcast_code(broadcast_code: &str) -> [u8; 16] {
+    let mut code = [0u8; 16];
+    let len = broadcast_code.len().min(code.len());
+    code[..len].copy_from_slice(&broadcast_code.as_bytes()[..len]);
+    code
+}
+
+/// Lidar connected to a hub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarInfo {
+    pub broadcast_code: [u8; 16],
+    pub dev_type: DeviceType,
+    pub version: [u8; 4],
+    /// hub port lidar is plugged in
+    pub slot: u8,
+    pub id: u8,
+}
+
+/// Setting of one lidar behind a hub, such as mode, return mode or on/off state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarValue {
+    pub broadcast_code: [u8; 16],
+    pub value: u8,
+}
+
+impl HubLidarValue {
+    /// value is a protocol byte, such as `LidarMode` or `ReturnMode`
+    pub fn new(broadcast_code: &str, value: impl Into<u8>) -> Self {
+        HubLidarValue {
+            broadcast_code: encode_broadcast_code(broadcast_code),
+            value: value.into(),
+        }
+    }
+}
+
+/// Setting of one lidar read through a hub, meaningless unless `ret_code` is 0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarValueResult {
+    pub ret_code: u8,
+    pub broadcast_code: [u8; 16],
+    pub value: u8,
+}
+
+/// Whether one lidar behind a hub accepted a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarResult {
+    pub ret_code: u8,
+    pub broadcast_code: [u8; 16],
+}
+
+/// State of one lidar behind a hub, as its heartbeat response would tell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarStatus {
+    pub broadcast_code: [u8; 16],
+    pub work_state: u8,
+    pub feature_msg: u8,
+    /// same layout as `DataFrame::status_code`
+    pub status_code: u32,
+}
+
+/// Extrinsic parameters of one lidar behind a hub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarExtrinsics {
+    pub broadcast_code: [u8; 16],
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Extrinsic parameters of one lidar read through a hub, meaningless unless `ret_code` is 0
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len)]
+pub struct HubLidarExtrinsicsResult {
+    pub ret_code: u8,
+    pub broadcast_code: [u8; 16],
+    pub roll: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Query lidars connected to hub
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x00, response = HubQueryLidarInfoResp)]
+pub struct HubQueryLidarInfo;
+
+pub const HUB_QUERY_LIDAR_INFO: HubQueryLidarInfo = HubQueryLidarInfo;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CheckStatus)]
+pub struct HubQueryLidarInfoResp {
+    pub ret_code: u8,
+    pub lidars: Counted<HubLidarInfo>,
+}
+
+/// Query state of lidars connected to hub
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x02, response = HubQueryLidarStatusResp)]
+pub struct HubQueryLidarStatus;
+
+pub const HUB_QUERY_LIDAR_STATUS: HubQueryLidarStatus = HubQueryLidarStatus;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CheckStatus)]
+pub struct HubQueryLidarStatusResp {
+    pub ret_code: u8,
+    pub lidars: Counted<HubLidarStatus>,
+}
+
+/// Power a hub slot on or off, 0x00: off, 0x01: on
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x03, response = CommonResp)]
+pub struct HubSlotPowerReq {
+    pub slot: u8,
+    pub state: u8,
+}
+
+impl HubSlotPowerReq {
+    /// fails unless slot is within 1 to 9
+    pub fn new(slot: u8, on: bool) -> Result<Self> {
+        match slot {
+            0x01u8..=0x09u8 => Ok(HubSlotPowerReq {
+                slot,
+                state: on.into(),
+            }),
+            _ => Err(LivoxError::InvalidArgument(format!(
+                "Invalid slot: {}",
+                slot
+            ))),
+        }
+    }
+}
+
+/// Query power state of hub slots
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x09, response = HubQuerySlotPowerResp)]
+pub struct HubQuerySlotPower;
+
+pub const HUB_QUERY_SLOT_POWER: HubQuerySlotPower = HubQuerySlotPower;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Len, CheckStatus)]
+pub struct HubQuerySlotPowerResp {
+    pub ret_code: u8,
+    /// bit `n - 1` set if slot `n` is powered
+    pub power_state: u16,
+}
+
+/// Start or stop hub calculating extrinsic parameters of its lidars, 0x00: stop, 0x01: start
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x07, response = CommonResp)]
+pub struct HubExtrinsicsCalculationReq {
+    pub state: u8,
+}
+
+impl HubExtrinsicsCalculationReq {
+    pub fn new(start: bool) -> Self {
+        HubExtrinsicsCalculationReq {
+            state: start.into(),
+        }
+    }
+}
+
+/// Write extrinsic parameters of lidars behind hub
+#[derive(Debug, Clone, PartialEq, LivoxCommand)]
+#[livox(cmd_set = 0x02, cmd_id = 0x04, response = HubLidarResultsResp, variable_len)]
+pub struct HubSetExtrinsicsReq {
+    pub lidars: Counted<HubLidarExtrinsics>,
+}
+
+impl HubSetExtrinsicsReq {
+    pub fn new(lidars: Vec<HubLidarExtrinsics>) -> Self {
+        HubSetExtrinsicsReq {
+            lidars: lidars.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CheckStatus)]
+pub struct HubGetExtrinsicsResp {
+    pub ret_code: u8,
+    pub lidars: Counted<HubLidarExtrinsicsResult>,
+}
+
+/// Declare a hub command applying a setting to lidars behind hub, each lidar with its own value
+macro_rules! hub_values_command {
+    ($(#[$doc:meta])* $name:ident, $cmd_id:literal) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, LivoxCommand)]
+        #[livox(cmd_set = 0x02, cmd_id = $cmd_id, response = HubLidarResultsResp, variable_len)]
+        pub struct $name {
+            pub lidars: Counted<HubLidarValue>,
+        }
+
+        impl $name {
+            pub fn new(lidars: Vec<HubLidarValue>) -> Self {
+                $name {
+                    lidars: lidars.into(),
+                }
+            }
+        }
+    };
+}
+
+/// Declare a hub command reading a setting of lidars behind hub
+macro_rules! hub_lidars_command {
+    ($(#[$doc:meta])* $name:ident, $cmd_id:literal, $resp:ty) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, PartialEq, LivoxCommand)]
+        #[livox(cmd_set = 0x02, cmd_id = $cmd_id, response = $resp, variable_len)]
+        pub struct $name {
+            pub broadcast_codes: Counted<[u8; 16]>,
+        }
+
+        impl $name {
+            pub fn new(broadcast_codes: &[&str]) -> Self {
+                $name {
+                    broadcast_codes: broadcast_codes
+                        .iter()
+                        .map(|code| encode_broadcast_code(code))
+                        .collect::<Vec<_>>()
+                        .into(),
+                }
+            }
+        }
+    };
+}
+
+hub_values_command!(
+    /// Set mode of lidars behind hub, values are `LidarMode`
+    HubSetModeReq,
+    0x01
+);
+hub_values_command!(
+    /// Turn rain and fog suppression of lidars behind hub on or off, 0x00: off, 0x01: on
+    HubRainFogSuppressionReq,
+    0x08
+);
+hub_values_command!(
+    /// Turn fan of lidars behind hub on or off, 0x00: off, 0x01: on
+    HubFanControlReq,
+    0x0A
+);
+hub_values_command!(
+    /// Set return mode of lidars behind hub, values are `ReturnMode`
+    HubSetReturnModeReq,
+    0x0C
+);
+hub_values_command!(
+    /// Set IMU push frequency of lidars behind hub, values are `ImuPushFrequency`
+    HubSetImuPushFrequencyReq,
+    0x0E
+);
+hub_lidars_command!(
+    /// Read extrinsic parameters of lidars behind hub
+    HubGetExtrinsicsReq,
+    0x05,
+    HubGetExtrinsicsResp
+);
+hub_lidars_command!(
+    /// Read fan state of lidars behind hub
+    HubGetFanStateReq,
+    0x0B,
+    HubLidarValuesResp
+);
+hub_lidars_command!(
+    /// Read return mode of lidars behind hub
+    HubGetReturnModeReq,
+    0x0D,
+    HubLidarValuesResp
+);
+hub_lidars_command!(
+    /// Read IMU push frequency of lidars behind hub
+    HubGetImuPushFrequencyReq,
+    0x0F,
+    HubLidarValuesResp
+);
+
+/// Whether each lidar behind hub accepted a setting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CheckStatus)]
+pub struct HubLidarResultsResp {
+    pub ret_code: u8,
+    pub lidars: Counted<HubLidarResult>,
+}
+
+/// Setting read from each lidar behind hub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CheckStatus)]
+pub struct HubLidarValuesResp {
+    pub ret_code: u8,
+    pub lidars: Counted<HubLidarValueResult>,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const CODE: &str = "3WEDH7600101011";
+
+    #[test]
+    fn test_serialize_hub_commands() {
+        let serialized = ControlFrame::new(0x21, &HUB_QUERY_LIDAR_INFO)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 2, 0, 236, 249, 192, 67]
+        );
+
+        let serialized = ControlFrame::new(0x21, &HUB_QUERY_SLOT_POWER)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 15, 0, 0, 33, 0, 239, 237, 2, 9, 72, 65, 28, 58]
+        );
+
+        let slot_req = HubSlotPowerReq::new(3, true).unwrap();
+        let serialized = ControlFrame::new(0x21, &slot_req).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 17, 0, 0, 33, 0, 23, 56, 2, 3, 3, 1, 251, 254, 75, 109]
+        );
+
+        let calculation_req = HubExtrinsicsCalculationReq::new(true);
+        let serialized = ControlFrame::new(0x21, &calculation_req)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![170, 1, 16, 0, 0, 33, 0, 83, 51, 2, 7, 1, 172, 214, 162, 134]
+        );
+
+        let extrinsics_req = HubSetExtrinsicsReq::new(vec![HubLidarExtrinsics {
+            broadcast_code: encode_broadcast_code(CODE),
+            roll: 0.0,
+            pitch: 0.0,
+            yaw: 90.0,
+            x: 100,
+            y: -200,
+            z: 0,
+        }]);
+        let serialized = ControlFrame::new(0x21, &extrinsics_req)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 56, 0, 0, 33, 0, 226, 9, 2, 4, 1, 51, 87, 69, 68, 72, 55, 54, 48, 48, 49,
+                48, 49, 48, 49, 49, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 180, 66, 100, 0, 0, 0, 56,
+                255, 255, 255, 0, 0, 0, 0, 100, 103, 77, 79
+            ]
+        );
+
+        let fan_req = HubFanControlReq::new(vec![
+            HubLidarValue::new(CODE, true),
+            HubLidarValue::new("0TFDG3B006H2Z11", false),
+        ]);
+        let serialized = ControlFrame::new(0x21, &fan_req).serialize().unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 50, 0, 0, 33, 0, 74, 69, 2, 10, 2, 51, 87, 69, 68, 72, 55, 54, 48, 48, 49,
+                48, 49, 48, 49, 49, 0, 1, 48, 84, 70, 68, 71, 51, 66, 48, 48, 54, 72, 50, 90, 49,
+                49, 0, 0, 167, 181, 143, 124
+            ]
+        );
+        let (seq_num, decoded) = ControlFrame::deserialize(&serialized).unwrap();
+        assert_eq!((seq_num, decoded), (0x21, Request::HubFanControl(fan_req)));
+
+        let return_mode_req = HubGetReturnModeReq::new(&[CODE]);
+        let serialized = ControlFrame::new(0x21, &return_mode_req)
+            .serialize()
+            .unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 32, 0, 0, 33, 0, 130, 231, 2, 13, 1, 51, 87, 69, 68, 72, 55, 54, 48, 48,
+                49, 48, 49, 48, 49, 49, 0, 2, 102, 188, 145
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_hub_responses() {
+        let broadcast_code = encode_broadcast_code(CODE);
+
+        let resp = Response::HubQueryLidarInfo(HubQueryLidarInfoResp {
+            ret_code: 0,
+            lidars: vec![HubLidarInfo {
+                broadcast_code,
+                dev_type: DeviceType::Mid40,
+                version: [1, 2, 3, 4],
+                slot: 1,
+                id: 1,
+            }]
+            .into(),
+        });
+        let serialized = resp.serialize(0x21).unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 40, 0, 1, 33, 0, 126, 231, 2, 0, 0, 1, 51, 87, 69, 68, 72, 55, 54, 48, 48,
+                49, 48, 49, 48, 49, 49, 0, 1, 1, 2, 3, 4, 1, 1, 230, 213, 21, 94
+            ]
+        );
+        assert_eq!(deserialize_ack(&serialized).unwrap(), (0x21, resp));
+
+        let resp = Response::HubQueryLidarStatus(HubQueryLidarStatusResp {
+            ret_code: 0,
+            lidars: vec![HubLidarStatus {
+                broadcast_code,
+                work_state: 1,
+                feature_msg: 0,
+                status_code: 0,
+            }]
+            .into(),
+        });
+        assert_eq!(
+            resp.serialize(0x21).unwrap(),
+            vec![
+                170, 1, 39, 0, 1, 33, 0, 130, 141, 2, 2, 0, 1, 51, 87, 69, 68, 72, 55, 54, 48, 48,
+                49, 48, 49, 48, 49, 49, 0, 1, 0, 0, 0, 0, 0, 105, 221, 149, 174
+            ]
+        );
+
+        let resp = Response::HubQuerySlotPower(HubQuerySlotPowerResp {
+            ret_code: 0,
+            power_state: 0x0005,
+        });
+        assert_eq!(
+            resp.serialize(0x21).unwrap(),
+            vec![170, 1, 18, 0, 1, 33, 0, 7, 127, 2, 9, 0, 5, 0, 10, 11, 72, 28]
+        );
+
+        let resp = Response::HubSetMode(HubLidarResultsResp {
+            ret_code: 0,
+            lidars: vec![HubLidarResult {
+                ret_code: 1,
+                broadcast_code,
+            }]
+            .into(),
+        });
+        assert_eq!(
+            resp.serialize(0x21).unwrap(),
+            vec![
+                170, 1, 34, 0, 1, 33, 0, 214, 171, 2, 1, 0, 1, 1, 51, 87, 69, 68, 72, 55, 54, 48,
+                48, 49, 48, 49, 48, 49, 49, 0, 119, 43, 135, 75
+            ]
+        );
+
+        let resp = Response::HubGetFanState(HubLidarValuesResp {
+            ret_code: 0,
+            lidars: vec![HubLidarValueResult {
+                ret_code: 0,
+                broadcast_code,
+                value: 1,
+            }]
+            .into(),
+        });
+        let serialized = resp.serialize(0x21).unwrap();
+        assert_eq!(
+            serialized,
+            vec![
+                170, 1, 35, 0, 1, 33, 0, 146, 160, 2, 11, 0, 1, 0, 51, 87, 69, 68, 72, 55, 54, 48,
+                48, 49, 48, 49, 48, 49, 49, 0, 1, 144, 197, 202, 31
+            ]
+        );
+        assert_eq!(deserialize_ack(&serialized).unwrap(), (0x21, resp));
+
+        let resp = Response::HubGetExtrinsics(HubGetExtrinsicsResp {
+            ret_code: 0,
+            lidars: Counted(vec![]),
+        });
+        assert_eq!(
+            resp.serialize(0x21).unwrap(),
+            vec![170, 1, 17, 0, 1, 33, 0, 203, 98, 2, 5, 0, 0, 97, 254, 200, 53]
+        );
+    }
+}
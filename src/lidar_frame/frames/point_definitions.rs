@@ -0,0 +1,480 @@
+use super::*;
+
+use std::marker::PhantomData;
+
+/// Header of every packet sent to data port
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct DataFrame {
+    pub version: u8,
+    pub slot_id: u8,
+    pub lidar_id: u8,
+    pub reserved: u8,
+    pub status_code: u32,
+    pub timestamp_type: u8,
+    pub data_type: u8,
+    pub timestamp: u64,
+}
+
+/// Layout of a single point in the data segment of a point cloud packet
+pub trait Point: Len + for<'de> Deserialize<'de> {
+    /// `data_type` in [`DataFrame`] carrying this kind of point
+    const DATA_TYPE: u8;
+    /// number of points in a packet sent by lidar
+    const POINTS_PER_PACKET: usize;
+}
+
+/// Standard cartesian point, data type 0x00
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct StandardCartesianPoint {
+    pub x: i32, // millimeters
+    pub y: i32,
+    pub z: i32,
+    pub reflectivity: u8,
+}
+
+/// Standard spherical point, data type 0x01
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct StandardSphericalPoint {
+    pub depth: u32,  // millimeters
+    pub zenith: u16, // 0.01 degree
+    pub azimuth: u16,
+    pub reflectivity: u8,
+}
+
+/// Extended cartesian point, data type 0x02
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct CartesianPoint {
+    pub x: i32, // millimeters
+    pub y: i32,
+    pub z: i32,
+    pub reflectivity: u8,
+    pub tag: u8,
+}
+
+/// Extended spherical point, data type 0x03
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SphericalPoint {
+    pub depth: u32,  // millimeters
+    pub zenith: u16, // 0.01 degree
+    pub azimuth: u16,
+    pub reflectivity: u8,
+    pub tag: u8,
+}
+
+/// Dual return extended cartesian point, data type 0x04
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DualCartesianPoint {
+    pub x1: i32, // millimeters
+    pub y1: i32,
+    pub z1: i32,
+    pub reflectivity1: u8,
+    pub tag1: u8,
+    pub x2: i32,
+    pub y2: i32,
+    pub z2: i32,
+    pub reflectivity2: u8,
+    pub tag2: u8,
+}
+
+/// Dual return extended spherical point, data type 0x05
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct DualSphericalPoint {
+    pub zenith: u16, // 0.01 degree
+    pub azimuth: u16,
+    pub depth1: u32, // millimeters
+    pub reflectivity1: u8,
+    pub tag1: u8,
+    pub depth2: u32,
+    pub reflectivity2: u8,
+    pub tag2: u8,
+}
+
+/// Triple return extended cartesian point, data type 0x07
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TripleCartesianPoint {
+    pub x1: i32, // millimeters
+    pub y1: i32,
+    pub z1: i32,
+    pub reflectivity1: u8,
+    pub tag1: u8,
+    pub x2: i32,
+    pub y2: i32,
+    pub z2: i32,
+    pub reflectivity2: u8,
+    pub tag2: u8,
+    pub x3: i32,
+    pub y3: i32,
+    pub z3: i32,
+    pub reflectivity3: u8,
+    pub tag3: u8,
+}
+
+/// Triple return extended spherical point, data type 0x08
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TripleSphericalPoint {
+    pub zenith: u16, // 0.01 degree
+    pub azimuth: u16,
+    pub depth1: u32, // millimeters
+    pub reflectivity1: u8,
+    pub tag1: u8,
+    pub depth2: u32,
+    pub reflectivity2: u8,
+    pub tag2: u8,
+    pub depth3: u32,
+    pub reflectivity3: u8,
+    pub tag3: u8,
+}
+
+/// IMU sample, data type 0x06
+#[derive(Len, Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ImuPoint {
+    pub gyro_x: f32, // rad/s
+    pub gyro_y: f32,
+    pub gyro_z: f32,
+    pub acc_x: f32, // g
+    pub acc_y: f32,
+    pub acc_z: f32,
+}
+
+impl Point for StandardCartesianPoint {
+    const DATA_TYPE: u8 = 0x00;
+    const POINTS_PER_PACKET: usize = 100;
+}
+
+impl Point for StandardSphericalPoint {
+    const DATA_TYPE: u8 = 0x01;
+    const POINTS_PER_PACKET: usize = 100;
+}
+
+impl Point for CartesianPoint {
+    const DATA_TYPE: u8 = 0x02;
+    const POINTS_PER_PACKET: usize = 96;
+}
+
+impl Point for SphericalPoint {
+    const DATA_TYPE: u8 = 0x03;
+    const POINTS_PER_PACKET: usize = 96;
+}
+
+impl Point for DualCartesianPoint {
+    const DATA_TYPE: u8 = 0x04;
+    const POINTS_PER_PACKET: usize = 48;
+}
+
+impl Point for DualSphericalPoint {
+    const DATA_TYPE: u8 = 0x05;
+    const POINTS_PER_PACKET: usize = 48;
+}
+
+impl Point for ImuPoint {
+    const DATA_TYPE: u8 = 0x06;
+    const POINTS_PER_PACKET: usize = 1;
+}
+
+impl Point for TripleCartesianPoint {
+    const DATA_TYPE: u8 = 0x07;
+    const POINTS_PER_PACKET: usize = 30;
+}
+
+impl Point for TripleSphericalPoint {
+    const DATA_TYPE: u8 = 0x08;
+    const POINTS_PER_PACKET: usize = 30;
+}
+
+/// Borrowed view of the points in a packet, points are decoded lazily on access
+#[derive(Debug, Clone, Copy)]
+pub struct PointSlice<'a, P> {
+    bytes: &'a [u8],
+    _point: PhantomData<P>,
+}
+
+impl<'a, P: Point> PointSlice<'a, P> {
+    /// wrap raw data segment, fails if it is not a whole number of points
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let point_len = P::LEN as usize;
+        if !bytes.len().is_multiple_of(point_len) {
+            return Err(LivoxError::Decode(format!(
+                "Data segment of {} bytes is not a multiple of point length {}",
+                bytes.len(),
+                point_len
+            )));
+        }
+        Ok(PointSlice {
+            bytes,
+            _point: PhantomData,
+        })
+    }
+
+    /// number of points
+    pub fn len(&self) -> usize {
+        self.bytes.len() / P::LEN as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// decode point at index, None if out of range
+    pub fn get(&self, index: usize) -> Option<P> {
+        let point_len = P::LEN as usize;
+        let start = index.checked_mul(point_len)?;
+        let bytes = self.bytes.get(start..start.checked_add(point_len)?)?;
+        bincode::deserialize(bytes).ok()
+    }
+
+    /// iterate over decoded points
+    pub fn iter(&self) -> impl Iterator<Item = P> + 'a {
+        self.bytes
+            .chunks_exact(P::LEN as usize)
+            .filter_map(|bytes| bincode::deserialize(bytes).ok())
+    }
+
+    /// raw bytes of the data segment
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// Points of a packet, layout selected by `data_type` in [`DataFrame`]
+#[derive(Debug, Clone, Copy)]
+pub enum Points<'a> {
+    StandardCartesian(PointSlice<'a, StandardCartesianPoint>),
+    StandardSpherical(PointSlice<'a, StandardSphericalPoint>),
+    Cartesian(PointSlice<'a, CartesianPoint>),
+    Spherical(PointSlice<'a, SphericalPoint>),
+    DualCartesian(PointSlice<'a, DualCartesianPoint>),
+    DualSpherical(PointSlice<'a, DualSphericalPoint>),
+    Imu(PointSlice<'a, ImuPoint>),
+    TripleCartesian(PointSlice<'a, TripleCartesianPoint>),
+    TripleSpherical(PointSlice<'a, TripleSphericalPoint>),
+}
+
+impl<'a> Points<'a> {
+    /// select point layout by data type and wrap the data segment
+    pub fn new(data_type: u8, bytes: &'a [u8]) -> Result<Self> {
+        Ok(match data_type {
+            StandardCartesianPoint::DATA_TYPE => Points::StandardCartesian(PointSlice::new(bytes)?),
+            StandardSphericalPoint::DATA_TYPE => Points::StandardSpherical(PointSlice::new(bytes)?),
+            CartesianPoint::DATA_TYPE => Points::Cartesian(PointSlice::new(bytes)?),
+            SphericalPoint::DATA_TYPE => Points::Spherical(PointSlice::new(bytes)?),
+            DualCartesianPoint::DATA_TYPE => Points::DualCartesian(PointSlice::new(bytes)?),
+            DualSphericalPoint::DATA_TYPE => Points::DualSpherical(PointSlice::new(bytes)?),
+            ImuPoint::DATA_TYPE => Points::Imu(PointSlice::new(bytes)?),
+            TripleCartesianPoint::DATA_TYPE => Points::TripleCartesian(PointSlice::new(bytes)?),
+            TripleSphericalPoint::DATA_TYPE => Points::TripleSpherical(PointSlice::new(bytes)?),
+            _ => {
+                return Err(LivoxError::Decode(format!(
+                    "Unsupported point data type: {}",
+                    data_type
+                )))
+            }
+        })
+    }
+
+    /// number of points, multiple returns of the same direction count as one
+    pub fn len(&self) -> usize {
+        match self {
+            Points::StandardCartesian(points) => points.len(),
+            Points::StandardSpherical(points) => points.len(),
+            Points::Cartesian(points) => points.len(),
+            Points::Spherical(points) => points.len(),
+            Points::DualCartesian(points) => points.len(),
+            Points::DualSpherical(points) => points.len(),
+            Points::Imu(points) => points.len(),
+            Points::TripleCartesian(points) => points.len(),
+            Points::TripleSpherical(points) => points.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// raw bytes of the data segment
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            Points::StandardCartesian(points) => points.as_bytes(),
+            Points::StandardSpherical(points) => points.as_bytes(),
+            Points::Cartesian(points) => points.as_bytes(),
+            Points::Spherical(points) => points.as_bytes(),
+            Points::DualCartesian(points) => points.as_bytes(),
+            Points::DualSpherical(points) => points.as_bytes(),
+            Points::Imu(points) => points.as_bytes(),
+            Points::TripleCartesian(points) => points.as_bytes(),
+            Points::TripleSpherical(points) => points.as_bytes(),
+        }
+    }
+}
+
+/// number of points in a packet of `data_type` sent by lidar, None if unknown
+pub fn points_per_packet(data_type: u8) -> Option<usize> {
+    Some(match data_type {
+        StandardCartesianPoint::DATA_TYPE => StandardCartesianPoint::POINTS_PER_PACKET,
+        StandardSphericalPoint::DATA_TYPE => StandardSphericalPoint::POINTS_PER_PACKET,
+        CartesianPoint::DATA_TYPE => CartesianPoint::POINTS_PER_PACKET,
+        SphericalPoint::DATA_TYPE => SphericalPoint::POINTS_PER_PACKET,
+        DualCartesianPoint::DATA_TYPE => DualCartesianPoint::POINTS_PER_PACKET,
+        DualSphericalPoint::DATA_TYPE => DualSphericalPoint::POINTS_PER_PACKET,
+        ImuPoint::DATA_TYPE => ImuPoint::POINTS_PER_PACKET,
+        TripleCartesianPoint::DATA_TYPE => TripleCartesianPoint::POINTS_PER_PACKET,
+        TripleSphericalPoint::DATA_TYPE => TripleSphericalPoint::POINTS_PER_PACKET,
+        _ => return None,
+    })
+}
+
+/// length in bytes of a packet of `data_type` sent by lidar, header included
+pub fn packet_len(data_type: u8) -> Option<usize> {
+    let point_len = match data_type {
+        StandardCartesianPoint::DATA_TYPE => StandardCartesianPoint::LEN,
+        StandardSphericalPoint::DATA_TYPE => StandardSphericalPoint::LEN,
+        CartesianPoint::DATA_TYPE => CartesianPoint::LEN,
+        SphericalPoint::DATA_TYPE => SphericalPoint::LEN,
+        DualCartesianPoint::DATA_TYPE => DualCartesianPoint::LEN,
+        DualSphericalPoint::DATA_TYPE => DualSphericalPoint::LEN,
+        ImuPoint::DATA_TYPE => ImuPoint::LEN,
+        TripleCartesianPoint::DATA_TYPE => TripleCartesianPoint::LEN,
+        TripleSphericalPoint::DATA_TYPE => TripleSphericalPoint::LEN,
+        _ => return None,
+    };
+    Some(DataFrame::LEN as usize + point_len as usize * points_per_packet(data_type)?)
+}
+
+/// Point cloud packet received from data port, borrowing the datagram
+#[derive(Debug, Clone, Copy)]
+pub struct PointPacket<'a> {
+    pub header: DataFrame,
+    pub points: Points<'a>,
+}
+
+/// deserialize datagram from data port into header and points
+pub fn deserialize_data(buffer: &[u8]) -> Result<PointPacket<'_>> {
+    let header_len = DataFrame::LEN as usize;
+    if buffer.len() < header_len {
+        return Err(LivoxError::LengthMismatch {
+            expected: header_len,
+            found: buffer.len(),
+        });
+    }
+
+    let header: DataFrame = bincode::deserialize(&buffer[..header_len]).map_err(|e| {
+        LivoxError::Decode(format!("Failed to deserialize data frame header: {}", e))
+    })?;
+    let points = Points::new(header.data_type, &buffer[header_len..])?;
+
+    Ok(PointPacket { header, points })
+}
+
+/// serialize header and points into a datagram as sent to data port,
+/// `data_type` of header is taken from the point type
+pub fn serialize_data<P: Point + Serialize>(header: &DataFrame, points: &[P]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(DataFrame::LEN as usize + points.len() * P::LEN as usize);
+    bincode::serialize_into(
+        &mut buf,
+        &DataFrame {
+            data_type: P::DATA_TYPE,
+            ..*header
+        },
+    )?;
+    for point in points {
+        bincode::serialize_into(&mut buf, point)?;
+    }
+    Ok(buf)
+}
+
+/// IMU sample received from IMU port, stamped with the packet timestamp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImuSample {
+    pub timestamp_type: u8,
+    pub timestamp: u64,
+    pub gyro_x: f32, // rad/s
+    pub gyro_y: f32,
+    pub gyro_z: f32,
+    pub acc_x: f32, // g
+    pub acc_y: f32,
+    pub acc_z: f32,
+}
+
+impl ImuSample {
+    fn new(header: &DataFrame, point: ImuPoint) -> Self {
+        ImuSample {
+            timestamp_type: header.timestamp_type,
+            timestamp: header.timestamp,
+            gyro_x: point.gyro_x,
+            gyro_y: point.gyro_y,
+            gyro_z: point.gyro_z,
+            acc_x: point.acc_x,
+            acc_y: point.acc_y,
+            acc_z: point.acc_z,
+        }
+    }
+}
+
+impl PointPacket<'_> {
+    /// iterate over IMU samples, empty if this is not an IMU packet
+    pub fn imu_samples(&self) -> impl Iterator<Item = ImuSample> + '_ {
+        let points = match self.points {
+            Points::Imu(points) => Some(points),
+            _ => None,
+        };
+        points
+            .into_iter()
+            .flat_map(|points| points.iter())
+            .map(|point| ImuSample::new(&self.header, point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_deserialize_data() {
+        let mut serial = vec![5, 1, 1, 0, 0, 0, 0, 0, 0, 0x02, 0x10, 0, 0, 0, 0, 0, 0, 0];
+        serial.extend(1000i32.to_le_bytes());
+        serial.extend((-2000i32).to_le_bytes());
+        serial.extend(300i32.to_le_bytes());
+        serial.extend([100, 0x10]);
+        serial.extend(0i32.to_le_bytes());
+        serial.extend(0i32.to_le_bytes());
+        serial.extend(1i32.to_le_bytes());
+        serial.extend([7, 0]);
+
+        let packet = deserialize_data(&serial).unwrap();
+        assert_eq!(packet.header.data_type, 0x02);
+        assert_eq!(packet.header.timestamp, 0x10);
+        let Points::Cartesian(points) = packet.points else {
+            panic!("expected extended cartesian points");
+        };
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            points.get(0),
+            Some(CartesianPoint {
+                x: 1000,
+                y: -2000,
+                z: 300,
+                reflectivity: 100,
+                tag: 0x10,
+            })
+        );
+        assert_eq!(points.iter().nth(1).map(|p| p.z), Some(1));
+
+        serial.pop();
+        assert!(deserialize_data(&serial).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_imu() {
+        let mut serial = vec![5, 1, 1, 0, 0, 0, 0, 0, 0, 0x06, 0x20, 0, 0, 0, 0, 0, 0, 0];
+        [0.1f32, 0.2, 0.3, 0.0, 0.0, 1.0]
+            .iter()
+            .for_each(|v| serial.extend(v.to_le_bytes()));
+
+        let packet = deserialize_data(&serial).unwrap();
+        let samples: Vec<_> = packet.imu_samples().collect();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].timestamp, 0x20);
+        assert_eq!(samples[0].gyro_y, 0.2);
+        assert_eq!(samples[0].acc_z, 1.0);
+    }
+}
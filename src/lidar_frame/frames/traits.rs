@@ -1,15 +1,24 @@
 use super::Cmd;
-/// Constantly offer length of data fragment for serialization constant
+use crate::error::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Length of data fragment once serialized, known at compile time
 pub trait Len {
-    fn len() -> u16;
+    const LEN: u16;
 }
 
 /// Check response status, true for success
 pub trait CheckStatus {
-    /// Check response status, true for success
-    fn check_status(&self) -> anyhow::Result<()>;
+    /// Check response status to `cmd`, `DeviceRejected` unless return code is 0
+    fn check_status(&self, cmd: Cmd) -> Result<()>;
 }
 
 pub trait GetCmd {
     fn cmd(&self) -> Cmd;
 }
+
+/// Command with a fixed command set and id, answered by a single response type
+pub trait LivoxCommand: GetCmd + Serialize {
+    const CMD: Cmd;
+    type Response: CheckStatus + DeserializeOwned;
+}
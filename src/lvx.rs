@@ -0,0 +1,727 @@
+use crate::discovery::DiscoveredDevice;
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::cfg::Extrinsics;
+use crate::lidar_frame::frames::{packet_len, DataFrame, DeviceType, Len, PointPacket};
+use crate::receiver::{DatagramSource, PointReceiver, POLL_INTERVAL};
+use livox_lidar_derive::Len;
+use log::{info, log_enabled, warn};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SIGNATURE: &[u8; 10] = b"livox_tech";
+const VERSION: [u8; 4] = [1, 1, 0, 0];
+const MAGIC_CODE: u32 = 0xAC0E_A767;
+/// offset of `data_type` in a data port datagram, which decides its length
+const DATA_TYPE_OFFSET: usize = 9;
+/// duration of a frame written by this crate, the only one Livox Viewer writes as well
+pub const FRAME_DURATION: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Serialize, Deserialize, Len)]
+struct PublicHeader {
+    signature: [u8; 16],
+    version: [u8; 4],
+    magic_code: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Len)]
+struct PrivateHeader {
+    /// milliseconds
+    frame_duration: u32,
+    device_count: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, Len)]
+struct DeviceInfo {
+    lidar_broadcast_code: [u8; 16],
+    hub_broadcast_code: [u8; 16],
+    device_index: u8,
+    device_type: DeviceType,
+    extrinsic_enable: u8,
+    roll: f32, // degrees
+    pitch: f32,
+    yaw: f32,
+    x: f32, // meters
+    y: f32,
+    z: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Len)]
+struct FrameHeader {
+    /// offset of this frame from start of file
+    current_offset: u64,
+    next_offset: u64,
+    frame_index: u64,
+}
+
+/// Lidar recorded in a LVX file, packets refer to it by `device_index`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LvxDevice {
+    pub broadcast_code: String,
+    /// broadcast code of the hub lidar is connected through, empty if connected directly
+    pub hub_broadcast_code: String,
+    pub device_index: u8,
+    pub dev_type: DeviceType,
+    pub extrinsics: Option<Extrinsics>,
+}
+
+impl LvxDevice {
+    /// device info of a discovered lidar, with the extrinsics written to it if any
+    pub fn new(
+        device: &DiscoveredDevice,
+        device_index: u8,
+        extrinsics: Option<Extrinsics>,
+    ) -> Self {
+        LvxDevice {
+            broadcast_code: device.broadcast_code.clone(),
+            hub_broadcast_code: String::new(),
+            device_index,
+            dev_type: device.dev_type,
+            extrinsics,
+        }
+    }
+
+    fn to_info(&self) -> DeviceInfo {
+        let code = |code: &str| {
+            let mut bytes = [0u8; 16];
+            let len = code.len().min(bytes.len());
+            bytes[..len].copy_from_slice(&code.as_bytes()[..len]);
+            bytes
+        };
+        let extrinsics = self.extrinsics.unwrap_or_default();
+        DeviceInfo {
+            lidar_broadcast_code: code(&self.broadcast_code),
+            hub_broadcast_code: code(&self.hub_broadcast_code),
+            device_index: self.device_index,
+            device_type: self.dev_type,
+            extrinsic_enable: u8::from(self.extrinsics.is_some()),
+            roll: extrinsics.roll,
+            pitch: extrinsics.pitch,
+            yaw: extrinsics.yaw,
+            x: extrinsics.x as f32 / 1000.0,
+            y: extrinsics.y as f32 / 1000.0,
+            z: extrinsics.z as f32 / 1000.0,
+        }
+    }
+
+    fn from_info(info: &DeviceInfo) -> Self {
+        let code = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_string()
+        };
+        LvxDevice {
+            broadcast_code: code(&info.lidar_broadcast_code),
+            hub_broadcast_code: code(&info.hub_broadcast_code),
+            device_index: info.device_index,
+            dev_type: info.device_type,
+            extrinsics: (info.extrinsic_enable != 0).then(|| Extrinsics {
+                roll: info.roll,
+                pitch: info.pitch,
+                yaw: info.yaw,
+                x: (info.x * 1000.0).round() as i32,
+                y: (info.y * 1000.0).round() as i32,
+                z: (info.z * 1000.0).round() as i32,
+            }),
+        }
+    }
+}
+
+/// Writes data port packets into a LVX 1.1 file, grouped into frames by arrival time
+pub struct LvxWriter<W: Write> {
+    writer: W,
+    started: Instant,
+    /// bytes written so far, frame offsets are counted from start of file
+    offset: u64,
+    frame_index: u64,
+    /// packages of the frame being collected
+    frame: Vec<u8>,
+}
+
+impl LvxWriter<BufWriter<File>> {
+    /// create LVX file at `path`, overwriting any existing one
+    pub fn create(path: impl AsRef<Path>, devices: &[LvxDevice]) -> Result<Self> {
+        let file = File::create(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to create LVX file {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::new(BufWriter::new(file), devices)
+    }
+}
+
+impl<W: Write> LvxWriter<W> {
+    /// write headers and device info, frames start from now
+    pub fn new(mut writer: W, devices: &[LvxDevice]) -> Result<Self> {
+        let mut signature = [0u8; 16];
+        signature[..SIGNATURE.len()].copy_from_slice(SIGNATURE);
+        let device_count = u8::try_from(devices.len()).map_err(|_| {
+            LivoxError::InvalidArgument(format!("Too many devices for LVX file: {}", devices.len()))
+        })?;
+
+        bincode::serialize_into(
+            &mut writer,
+            &PublicHeader {
+                signature,
+                version: VERSION,
+                magic_code: MAGIC_CODE,
+            },
+        )?;
+        bincode::serialize_into(
+            &mut writer,
+            &PrivateHeader {
+                frame_duration: FRAME_DURATION.as_millis() as u32,
+                device_count,
+            },
+        )?;
+        for device in devices {
+            bincode::serialize_into(&mut writer, &device.to_info())?;
+        }
+
+        Ok(LvxWriter {
+            writer,
+            started: Instant::now(),
+            offset: (PublicHeader::LEN
+                + PrivateHeader::LEN
+                + DeviceInfo::LEN * u16::from(device_count)) as u64,
+            frame_index: 0,
+            frame: Vec::new(),
+        })
+    }
+
+    /// append datagram received from data port just now
+    pub fn write_datagram(&mut self, device_index: u8, datagram: &[u8]) -> Result<()> {
+        self.write_datagram_at(device_index, datagram, self.started.elapsed())
+    }
+
+    /// append datagram received `elapsed` after recording started,
+    /// frames are written in order so `elapsed` must not go backwards
+    pub fn write_datagram_at(
+        &mut self,
+        device_index: u8,
+        datagram: &[u8],
+        elapsed: Duration,
+    ) -> Result<()> {
+        let data_type = *datagram.get(DATA_TYPE_OFFSET).ok_or_else(|| {
+            LivoxError::Decode(format!("Datagram of {} bytes is too short", datagram.len()))
+        })?;
+        if packet_len(data_type) != Some(datagram.len()) {
+            return Err(LivoxError::InvalidArgument(format!(
+                "Packet of data type {} with {} bytes cannot be stored in LVX file",
+                data_type,
+                datagram.len()
+            )));
+        }
+
+        // close frames which ended before this packet, empty ones included to keep timing
+        while elapsed >= FRAME_DURATION * (self.frame_index as u32 + 1) {
+            self.write_frame()?;
+        }
+        self.frame.push(device_index);
+        self.frame.extend_from_slice(datagram);
+        Ok(())
+    }
+
+    /// append point packet received from data port just now
+    pub fn write_packet(&mut self, device_index: u8, packet: &PointPacket<'_>) -> Result<()> {
+        let mut datagram = bincode::serialize(&packet.header)?;
+        datagram.extend_from_slice(packet.points.as_bytes());
+        self.write_datagram(device_index, &datagram)
+    }
+
+    fn write_frame(&mut self) -> Result<()> {
+        let frame_len = FrameHeader::LEN as u64 + self.frame.len() as u64;
+        bincode::serialize_into(
+            &mut self.writer,
+            &FrameHeader {
+                current_offset: self.offset,
+                next_offset: self.offset + frame_len,
+                frame_index: self.frame_index,
+            },
+        )?;
+        self.writer.write_all(&self.frame)?;
+        self.offset += frame_len;
+        self.frame_index += 1;
+        self.frame.clear();
+        Ok(())
+    }
+
+    /// write the last frame and flush, return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.write_frame()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Records every packet of a point receiver until finished
+pub struct LvxRecorder<W: Write + Send + 'static> {
+    writer: Arc<Mutex<Option<LvxWriter<W>>>>,
+}
+
+impl<W: Write + Send + 'static> LvxRecorder<W> {
+    /// write packets received by `receiver` as coming from device at `device_index`
+    pub fn attach(writer: LvxWriter<W>, receiver: &PointReceiver, device_index: u8) -> Self {
+        let writer = Arc::new(Mutex::new(Some(writer)));
+        let duplicated_writer = writer.clone();
+        receiver.subscribe(move |packet| {
+            if let Some(writer) = duplicated_writer.lock().unwrap().as_mut() {
+                if let Err(e) = writer.write_packet(device_index, packet) {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("error occurred when recording packet: {}", e);
+                    }
+                }
+            }
+        });
+        LvxRecorder { writer }
+    }
+
+    /// stop recording and finish the file
+    pub fn finish(self) -> Result<W> {
+        self.writer
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| LivoxError::Closed("LVX recording already finished".to_string()))?
+            .finish()
+    }
+}
+
+/// Packet read back from a LVX file
+#[derive(Debug, Clone, PartialEq)]
+pub struct LvxPacket {
+    pub device_index: u8,
+    /// time since recording started
+    pub offset: Duration,
+    /// datagram as received from data port
+    pub datagram: Vec<u8>,
+}
+
+/// Reads packets of a LVX 1.1 file in order, seeking by time
+pub struct LvxReader<R> {
+    reader: R,
+    frame_duration: Duration,
+    devices: Vec<LvxDevice>,
+    /// offset of every frame from start of file, indexed by frame index
+    frames: Vec<u64>,
+    next_frame: usize,
+    /// packages of the frame being read
+    frame: Vec<u8>,
+    frame_pos: usize,
+    frame_start: Duration,
+    /// timestamp of the first packet of the frame being read
+    frame_timestamp: Option<u64>,
+}
+
+impl LvxReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to open LVX file {}: {}", path.as_ref().display(), e),
+            ))
+        })?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek> LvxReader<R> {
+    /// read headers and index frames
+    pub fn new(mut reader: R) -> Result<Self> {
+        let public: PublicHeader = bincode::deserialize_from(&mut reader)
+            .map_err(|e| LivoxError::Decode(format!("Failed to read LVX public header: {}", e)))?;
+        if !public.signature.starts_with(SIGNATURE) || public.magic_code != MAGIC_CODE {
+            return Err(LivoxError::Decode("Not a LVX file".to_string()));
+        }
+        if public.version[..2] != VERSION[..2] {
+            return Err(LivoxError::Decode(format!(
+                "Unsupported LVX version: {}.{}.{}.{}",
+                public.version[0], public.version[1], public.version[2], public.version[3]
+            )));
+        }
+        let private: PrivateHeader = bincode::deserialize_from(&mut reader)
+            .map_err(|e| LivoxError::Decode(format!("Failed to read LVX private header: {}", e)))?;
+        if private.frame_duration == 0 {
+            return Err(LivoxError::Decode(
+                "LVX frame duration must be positive".to_string(),
+            ));
+        }
+        let devices = (0..private.device_count)
+            .map(|_| {
+                bincode::deserialize_from(&mut reader)
+                    .map(|info| LvxDevice::from_info(&info))
+                    .map_err(|e| {
+                        LivoxError::Decode(format!("Failed to read LVX device info: {}", e))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // walk the chain of frame headers, a truncated last frame is left out
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let mut frames = Vec::new();
+        let mut offset = (PublicHeader::LEN
+            + PrivateHeader::LEN
+            + DeviceInfo::LEN * u16::from(private.device_count)) as u64;
+        while offset + FrameHeader::LEN as u64 <= file_len {
+            reader.seek(SeekFrom::Start(offset))?;
+            let header: FrameHeader = bincode::deserialize_from(&mut reader)?;
+            if header.current_offset != offset
+                || header.next_offset < offset + FrameHeader::LEN as u64
+                || header.next_offset > file_len
+            {
+                if log_enabled!(log::Level::Warn) {
+                    warn!(
+                        "LVX frame at offset {} is broken, ignoring the rest",
+                        offset
+                    );
+                }
+                break;
+            }
+            frames.push(offset);
+            offset = header.next_offset;
+        }
+
+        Ok(LvxReader {
+            reader,
+            frame_duration: Duration::from_millis(u64::from(private.frame_duration)),
+            devices,
+            frames,
+            next_frame: 0,
+            frame: Vec::new(),
+            frame_pos: 0,
+            frame_start: Duration::ZERO,
+            frame_timestamp: None,
+        })
+    }
+
+    pub fn devices(&self) -> &[LvxDevice] {
+        &self.devices
+    }
+
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_duration
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// length of the recording
+    pub fn duration(&self) -> Duration {
+        self.frame_duration * self.frames.len() as u32
+    }
+
+    /// continue reading from the frame containing `position`
+    pub fn seek(&mut self, position: Duration) {
+        let frame = position.as_nanos() / self.frame_duration.as_nanos();
+        self.next_frame = (frame as usize).min(self.frames.len());
+        self.frame.clear();
+        self.frame_pos = 0;
+    }
+
+    /// next packet in file, None at end of file
+    pub fn next_packet(&mut self) -> Result<Option<LvxPacket>> {
+        while self.frame_pos >= self.frame.len() {
+            if !self.read_frame()? {
+                return Ok(None);
+            }
+        }
+
+        let (len, header) = match self.package_header() {
+            Ok(package) => package,
+            Err(e) => {
+                // rest of frame cannot be told apart without the length of this package
+                self.frame_pos = self.frame.len();
+                return Err(e);
+            }
+        };
+        let package = &self.frame[self.frame_pos..];
+        let datagram = &package[1..=len];
+
+        // packets are spread over their frame by timestamp, as far as timestamps allow
+        let timestamp = header.lidar_time().unwrap_or(header.timestamp);
+        let first_timestamp = *self.frame_timestamp.get_or_insert(timestamp);
+        let in_frame = Duration::from_nanos(timestamp.saturating_sub(first_timestamp))
+            .min(self.frame_duration);
+        let packet = LvxPacket {
+            device_index: package[0],
+            offset: self.frame_start + in_frame,
+            datagram: datagram.to_vec(),
+        };
+        self.frame_pos += 1 + len;
+        Ok(Some(packet))
+    }
+
+    /// length and header of the datagram in package at `frame_pos`
+    fn package_header(&self) -> Result<(usize, DataFrame)> {
+        let datagram = &self.frame[self.frame_pos + 1..];
+        let data_type = *datagram
+            .get(DATA_TYPE_OFFSET)
+            .ok_or_else(|| LivoxError::Decode("LVX package truncated".to_string()))?;
+        let len = packet_len(data_type).ok_or_else(|| {
+            LivoxError::Decode(format!(
+                "Unsupported point data type in LVX file: {}",
+                data_type
+            ))
+        })?;
+        let datagram = datagram
+            .get(..len)
+            .ok_or_else(|| LivoxError::Decode("LVX package truncated".to_string()))?;
+        Ok((len, bincode::deserialize(datagram)?))
+    }
+
+    /// load the next frame, false at end of file, a frame failing to load is skipped
+    fn read_frame(&mut self) -> Result<bool> {
+        let Some(&offset) = self.frames.get(self.next_frame) else {
+            return Ok(false);
+        };
+        self.frame.clear();
+        self.frame_pos = 0;
+        self.frame_start = self.frame_duration * self.next_frame as u32;
+        self.frame_timestamp = None;
+        self.next_frame += 1;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let header: FrameHeader = bincode::deserialize_from(&mut self.reader)?;
+        self.frame.resize(
+            (header.next_offset - offset) as usize - FrameHeader::LEN as usize,
+            0,
+        );
+        if let Err(e) = self.reader.read_exact(&mut self.frame) {
+            self.frame.clear();
+            return Err(e.into());
+        }
+        Ok(true)
+    }
+}
+
+/// How fast a LVX file is replayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pace {
+    RealTime,
+    /// real time scaled by factor, 2.0 replays twice as fast
+    Speed(f64),
+    AsFastAsPossible,
+}
+
+/// Replays a LVX file as a datagram source, in place of the data port socket
+pub struct LvxPlayer<R> {
+    reader: LvxReader<R>,
+    pace: Pace,
+    /// wall clock time and recording time replay started from
+    origin: Option<(Instant, Duration)>,
+    pending: Option<LvxPacket>,
+    finished: bool,
+}
+
+impl<R: Read + Seek> LvxPlayer<R> {
+    /// replay from where `reader` currently is, fails unless speed is finite and positive
+    pub fn new(reader: LvxReader<R>, pace: Pace) -> Result<Self> {
+        if let Pace::Speed(speed) = pace {
+            if !speed.is_finite() || speed <= 0.0 {
+                return Err(LivoxError::InvalidArgument(format!(
+                    "Invalid replay speed: {}",
+                    speed
+                )));
+            }
+        }
+        Ok(LvxPlayer {
+            reader,
+            pace,
+            origin: None,
+            pending: None,
+            finished: false,
+        })
+    }
+
+    /// time left until packet at `offset` is due, `Duration::MAX` if too far ahead to tell
+    fn wait(&mut self, offset: Duration) -> Duration {
+        let speed = match self.pace {
+            Pace::RealTime => 1.0,
+            Pace::Speed(speed) => speed,
+            Pace::AsFastAsPossible => return Duration::ZERO,
+        };
+        let (started, origin) = *self.origin.get_or_insert((Instant::now(), offset));
+        Duration::try_from_secs_f64(offset.saturating_sub(origin).as_secs_f64() / speed)
+            .ok()
+            .and_then(|elapsed| started.checked_add(elapsed))
+            .map_or(Duration::MAX, |due| {
+                due.saturating_duration_since(Instant::now())
+            })
+    }
+}
+
+impl<R: Read + Seek + Send + 'static> DatagramSource for LvxPlayer<R> {
+    fn recv_datagram(&mut self, buffer: &mut [u8]) -> std::io::Result<Option<usize>> {
+        if self.pending.is_none() && !self.finished {
+            self.pending = self
+                .reader
+                .next_packet()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if self.pending.is_none() {
+                info!("LVX replay finished");
+                self.finished = true;
+            }
+        }
+        let Some(offset) = self.pending.as_ref().map(|packet| packet.offset) else {
+            thread::sleep(POLL_INTERVAL);
+            return Ok(None);
+        };
+
+        let wait = self.wait(offset);
+        if wait > POLL_INTERVAL {
+            thread::sleep(POLL_INTERVAL);
+            return Ok(None);
+        }
+        thread::sleep(wait);
+
+        let Some(packet) = self.pending.take() else {
+            return Ok(None);
+        };
+        let len = packet.datagram.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&packet.datagram[..len]);
+        Ok(Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::lidar_frame::frames::{serialize_data, CartesianPoint, Point};
+    use std::io::Cursor;
+
+    fn datagram(timestamp: u64) -> Vec<u8> {
+        let header = DataFrame {
+            version: 5,
+            slot_id: 1,
+            lidar_id: 1,
+            reserved: 0,
+            status_code: 0,
+            timestamp_type: 0,
+            data_type: CartesianPoint::DATA_TYPE,
+            timestamp,
+        };
+        let point = CartesianPoint {
+            x: 1000,
+            y: -2000,
+            z: 300,
+            reflectivity: 100,
+            tag: 0,
+        };
+        serialize_data(&header, &[point; CartesianPoint::POINTS_PER_PACKET]).unwrap()
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let device = LvxDevice {
+            broadcast_code: "0TFDG3B006H2Z11".to_string(),
+            hub_broadcast_code: String::new(),
+            device_index: 0,
+            dev_type: DeviceType::Horizon,
+            extrinsics: Some(Extrinsics {
+                yaw: 90.0,
+                x: 100,
+                ..Extrinsics::default()
+            }),
+        };
+        let mut writer =
+            LvxWriter::new(Cursor::new(Vec::new()), std::slice::from_ref(&device)).unwrap();
+        writer
+            .write_datagram_at(0, &datagram(0), Duration::ZERO)
+            .unwrap();
+        writer
+            .write_datagram_at(0, &datagram(10_000_000), Duration::from_millis(10))
+            .unwrap();
+        writer
+            .write_datagram_at(0, &datagram(120_000_000), Duration::from_millis(120))
+            .unwrap();
+        assert!(writer
+            .write_datagram_at(0, &datagram(0)[..100], Duration::from_millis(120))
+            .is_err());
+        let file = writer.finish().unwrap().into_inner();
+        assert_eq!(&file[..10], SIGNATURE);
+
+        let mut reader = LvxReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.devices(), &[device]);
+        assert_eq!(reader.frame_count(), 3);
+
+        let offsets: Vec<_> = std::iter::from_fn(|| reader.next_packet().unwrap())
+            .map(|packet| packet.offset)
+            .collect();
+        assert_eq!(offsets, [0, 10, 100].map(Duration::from_millis).to_vec());
+
+        reader.seek(Duration::from_millis(110));
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.datagram, datagram(120_000_000));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_skips_corrupt_frame() {
+        let mut writer = LvxWriter::new(Cursor::new(Vec::new()), &[]).unwrap();
+        writer
+            .write_datagram_at(0, &datagram(0), Duration::ZERO)
+            .unwrap();
+        writer
+            .write_datagram_at(0, &datagram(0), Duration::from_millis(10))
+            .unwrap();
+        writer
+            .write_datagram_at(0, &datagram(100_000_000), Duration::from_millis(100))
+            .unwrap();
+        let mut file = writer.finish().unwrap().into_inner();
+        let offsets = LvxReader::new(Cursor::new(file.clone())).unwrap().frames;
+        file[offsets[0] as usize + FrameHeader::LEN as usize + 1 + DATA_TYPE_OFFSET] = 0xFF;
+
+        let mut reader = LvxReader::new(Cursor::new(file)).unwrap();
+        assert!(matches!(reader.next_packet(), Err(LivoxError::Decode(_))));
+        let packet = reader.next_packet().unwrap().unwrap();
+        assert_eq!(packet.datagram, datagram(100_000_000));
+        assert!(reader.next_packet().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_player_pace() {
+        let mut writer = LvxWriter::new(Cursor::new(Vec::new()), &[]).unwrap();
+        writer
+            .write_datagram_at(0, &datagram(0), Duration::ZERO)
+            .unwrap();
+        writer
+            .write_datagram_at(0, &datagram(100_000_000), Duration::from_millis(100))
+            .unwrap();
+        let file = writer.finish().unwrap().into_inner();
+        let reader = || LvxReader::new(Cursor::new(file.clone())).unwrap();
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                LvxPlayer::new(reader(), Pace::Speed(speed)),
+                Err(LivoxError::InvalidArgument(_))
+            ));
+        }
+
+        // second packet is due too far ahead for an instant to hold
+        let mut player = LvxPlayer::new(reader(), Pace::Speed(f64::MIN_POSITIVE)).unwrap();
+        let mut buffer = [0u8; 1500];
+        assert!(player.recv_datagram(&mut buffer).unwrap().is_some());
+        assert_eq!(player.recv_datagram(&mut buffer).unwrap(), None);
+
+        let mut player = LvxPlayer::new(reader(), Pace::Speed(2.0)).unwrap();
+        let started = Instant::now();
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            if let Some(len) = player.recv_datagram(&mut buffer).unwrap() {
+                received.push(buffer[..len].to_vec());
+            }
+        }
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(received, [datagram(0), datagram(100_000_000)]);
+        assert_eq!(player.recv_datagram(&mut buffer).unwrap(), None);
+    }
+}
@@ -1,199 +1,41 @@
 use env_logger::{Builder, Target};
-use livox_lidar_rs::lidar_frame::cfg::{CMD_PORT, DATA_PORT, USER_IP};
-use livox_lidar_rs::lidar_frame::frames::{
-    deserialize_resp, CheckStatus, Cmd, CommonResp, ControlFrame, DataFrame, DisconnectReq, GetCmd,
-    Len, SampleCtrlReq, DISCONNECT_REQ, HANDSHAKE_REQ, HEARTBEAT_REQ, SAMPLE_END_REQ,
-    SAMPLE_START_REQ,
-};
+use livox_lidar_rs::connection::ConnectionSupervisor;
+use livox_lidar_rs::event::LidarEvent;
+use livox_lidar_rs::health::HealthMonitor;
+use livox_lidar_rs::lidar_frame::cfg::LidarConfig;
+use livox_lidar_rs::lvx::{LvxDevice, LvxRecorder, LvxWriter};
+use livox_lidar_rs::pcap::Capture;
+use livox_lidar_rs::receiver::{ImuReceiver, PointReceiver};
 use log::{debug, info, log_enabled, warn};
-use serde::Serialize;
-use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::Duration;
 
-type AnyhowHandle = thread::JoinHandle<anyhow::Result<()>>;
-
-type TransmitterMap = HashMap<Cmd, mpsc::Sender<Vec<u8>>>;
-type ReceiverMap = HashMap<Cmd, mpsc::Receiver<Vec<u8>>>;
-
-fn heartbeat_daemon_launch(
-    command_emitter: Arc<CommandProcessor>,
-) -> anyhow::Result<(AnyhowHandle, mpsc::Sender<()>)> {
-    debug!("heartbeat thread started ✅");
-
-    let (tx, rx) = mpsc::channel();
-
-    let time_to_live = Duration::from_millis(1000);
-
-    // launch heartbeat daemon, in which send heartbeat request every 1 second
-    let handle: AnyhowHandle = thread::spawn(move || loop {
-        if let Ok(_) = rx.try_recv() {
-            info!("received sig_term, heartbeat daemon exiting...");
-            return Ok(());
-        }
-        debug!("heartbeat daemon: no sig_term received, continue...");
-        let _: CommonResp = command_emitter.command_execute(HEARTBEAT_REQ)?;
-        thread::sleep(time_to_live);
-    });
-    Ok((handle, tx))
-}
-
-// fn point_deserialize
-fn data_receiver_launch(
+/// forward timestamp and points of every packet to local socket
+fn data_forward_launch(
     data_socket: UdpSocket,
-) -> anyhow::Result<(AnyhowHandle, mpsc::Sender<()>)> {
-    let mut buffer = [0; 1024];
-
-    let (tx, rx) = mpsc::channel();
-
+    capture: Option<&Capture>,
+) -> anyhow::Result<PointReceiver> {
     let local_socket = UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 54321)))?;
     local_socket.connect(SocketAddr::from(([127, 0, 0, 1], 47384)))?;
 
-    let handle: AnyhowHandle = thread::spawn(move || loop {
-        if let Ok(_) = rx.try_recv() {
-            info!("received sig_term, data receiver exiting...");
-            return Ok(());
-        }
-        debug!("data receiver: no sig_term received, continue...");
-        match data_socket.recv_from(&mut buffer) {
-            Ok((size, _)) => {
-                let unused_len = DataFrame::len() as usize - std::mem::size_of::<u64>();
-                // let timestamp = u64::from_le_bytes(
-                //     buffer[unused_len..DataFrame::len() as usize]
-                //         .try_into()
-                //         .unwrap(),
-                // );
-                // &buffer[DataFrame::len() as usize..size];
-                if let Err(e) = local_socket.send(&buffer[unused_len..size]) {
-                    if log_enabled!(log::Level::Warn) {
-                        warn!("error occurred when sending data to local socket: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                if log_enabled!(log::Level::Warn) {
-                    warn!("error occurred when receiving data: {}", e);
-                }
+    let point_receiver = match capture {
+        Some(capture) => PointReceiver::launch_from(capture.tap(data_socket)?)?,
+        None => PointReceiver::launch(data_socket)?,
+    };
+    let mut forward_buffer = Vec::new();
+    point_receiver.subscribe(move |packet| {
+        forward_buffer.clear();
+        forward_buffer.extend(packet.header.timestamp.to_le_bytes());
+        forward_buffer.extend(packet.points.as_bytes());
+        if let Err(e) = local_socket.send(&forward_buffer) {
+            if log_enabled!(log::Level::Warn) {
+                warn!("error occurred when sending data to local socket: {}", e);
             }
         }
     });
-    Ok((handle, tx))
-}
-
-struct CommandProcessor {
-    control_socket: UdpSocket,
-    seq_ref: Mutex<u16>,
-    transmit_map: Arc<Mutex<TransmitterMap>>,
-    receive_map: Arc<Mutex<ReceiverMap>>,
-    term_sender: mpsc::Sender<()>,
-}
-
-impl CommandProcessor {
-    fn new(lidar_addr: SocketAddr, control_socket: UdpSocket) -> Self {
-        if let Err(e) = control_socket.connect(lidar_addr) {
-            if log_enabled!(log::Level::Error) {
-                warn!("error occurred when connecting to lidar: {}", e);
-            }
-        }
-        let (tx, rx) = mpsc::channel();
-        let duplicated_control_socket = control_socket.try_clone().unwrap();
-
-        let transmit_map: Arc<Mutex<TransmitterMap>> = Arc::new(Mutex::new(HashMap::new()));
-        let receive_map: Arc<Mutex<ReceiverMap>> = Arc::new(Mutex::new(HashMap::new()));
-        let duplicated_transmit_map = transmit_map.clone();
-
-        // start command response receiver, receiving all response in this thread, and sending to corresponding channel
-        let _: AnyhowHandle = thread::spawn(move || {
-            let mut buffer = [0; 1024];
-            loop {
-                if let Ok(_) = rx.try_recv() {
-                    info!("received sig_term, command response receiver exiting...");
-                    return Ok(());
-                }
-                debug!("command response receiver: no sig_term received, continue...");
-                match control_socket.recv(&mut buffer) {
-                    Ok(size) => match deserialize_resp(&buffer[..size]) {
-                        Ok((_, cmd, frame)) => {
-                            if log_enabled!(log::Level::Debug) {
-                                debug!("command response on: {:?}", cmd);
-                            }
-
-                            transmit_map
-                                .lock()
-                                .unwrap()
-                                .get(&cmd.clone())
-                                .unwrap()
-                                .send(frame.to_vec())
-                                .unwrap()
-                        }
-                        Err(e) => {
-                            if log_enabled!(log::Level::Warn) {
-                                warn!("error occurred when deserializing response: {}", e);
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        if log_enabled!(log::Level::Warn) {
-                            warn!("error occurred when receiving response: {}", e);
-                        }
-                    }
-                }
-            }
-        });
-        Self {
-            control_socket: duplicated_control_socket,
-            seq_ref: Mutex::new(0),
-            transmit_map: duplicated_transmit_map,
-            receive_map,
-            term_sender: tx,
-        }
-    }
-
-    /// execute certain command and return the response
-    fn command_execute<T, P>(&self, req: T) -> anyhow::Result<P>
-    where
-        T: Len + Serialize + GetCmd,
-        P: CheckStatus + for<'de> serde::Deserialize<'de>,
-    {
-        self.receive_map
-            .lock()
-            .unwrap()
-            .entry(req.cmd())
-            .or_insert_with(|| {
-                let (tx, rx) = mpsc::channel();
-                self.transmit_map.lock().unwrap().insert(req.cmd(), tx);
-                rx
-            });
-
-        let mut seq = self.seq_ref.lock().unwrap();
-        self.control_socket
-            .send(&ControlFrame::new(*seq, &req).serialize()?)?;
-
-        *seq = seq.checked_add(1).unwrap_or_default();
-        drop(seq);
-
-        if log_enabled!(log::Level::Debug) {
-            debug!("sent command: {:?}", req.cmd());
-        }
-
-        let mes = self
-            .receive_map // listen to mpsc channel for response
-            .lock() // get lock
-            .unwrap() // if get lock wrong, crash immediately
-            .get(&req.cmd()) // get corresponding response channel
-            .unwrap() // must have corresponding channel already exist
-            .recv()?;
-        let resp: P = bincode::deserialize(&mes)?;
-        resp.check_status()?;
-
-        if log_enabled!(log::Level::Debug) {
-            debug!("command handled successfully ✅");
-        }
-
-        Ok(resp)
-    }
+    Ok(point_receiver)
 }
 
 fn main() -> anyhow::Result<()> {
@@ -201,81 +43,132 @@ fn main() -> anyhow::Result<()> {
 
     info!("livox lidar driver in Rust 🚀");
 
-    let broadcast_socket = UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 55000)))?;
-    broadcast_socket.set_read_timeout(Some(Duration::from_millis(1000)))?;
-    let control_socket = UdpSocket::bind(SocketAddr::from((USER_IP, CMD_PORT)))?;
-    let data_socket = UdpSocket::bind(SocketAddr::from((USER_IP, DATA_PORT)))?;
-    debug!("success init sockets ✅");
-
-    control_socket.set_read_timeout(Some(Duration::from_millis(1000)))?;
-    debug!("set control socket read timeout to 1 seconds");
-
-    debug!("start listening broadcast on 0.0.0.0:55000...");
-    let (_, lidar_addr) = broadcast_socket.recv_from(&mut Vec::new())?;
+    // optional path of TOML config as the first argument
+    let config = match std::env::args().nth(1) {
+        Some(path) => LidarConfig::from_toml_file(path)?,
+        None => LidarConfig::default(),
+    };
     if log_enabled!(log::Level::Debug) {
-        debug!("received broadcast from {:?}", lidar_addr);
+        debug!("lidar config: {:?}", config);
     }
 
-    let command_emitter = Arc::new(CommandProcessor::new(lidar_addr, control_socket));
-
-    debug!("trying handshake...");
-    let _: CommonResp = command_emitter.command_execute(HANDSHAKE_REQ)?;
-    debug!("handshake success ✅");
-
-    info!("success connected to lidar ✅");
+    let broadcast_socket = UdpSocket::bind(config.broadcast_addr())?;
+    let control_socket = UdpSocket::bind(config.cmd_addr())?;
+    let data_socket = UdpSocket::bind(config.data_addr())?;
+    let imu_socket = UdpSocket::bind(config.imu_addr())?;
+    debug!("success init sockets ✅");
 
-    let (handle, term_sender) = heartbeat_daemon_launch(command_emitter.clone())?;
-    info!("heartbeat daemon launched ✅");
+    // optional path of LVX file to record point clouds into as the second argument
+    let record_path = std::env::args().nth(2);
+    let extrinsics = config.extrinsics;
+    let capture = config
+        .capture_file
+        .as_ref()
+        .map(Capture::create)
+        .transpose()?;
+
+    let supervisor = ConnectionSupervisor::launch(config, broadcast_socket, control_socket)?;
+    if let Some(capture) = &capture {
+        supervisor.set_capture(capture.clone());
+        info!("capturing traffic into pcap file 🎥");
+    }
+    info!("connection supervisor launched ✅");
+
+    // alert operator of anything lidar pushes and of connection changes
+    let events = supervisor.subscribe_events();
+    thread::spawn(move || {
+        for event in events {
+            match event {
+                LidarEvent::AbnormalStatus(_) | LidarEvent::Message { .. } => {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("lidar event: {:?}", event);
+                    }
+                }
+                LidarEvent::StateChanged(_) | LidarEvent::HealthChanged(_) => {
+                    if log_enabled!(log::Level::Info) {
+                        info!("lidar event: {:?}", event);
+                    }
+                }
+            }
+        }
+    });
 
-    let (_, term_sender2) = data_receiver_launch(data_socket)?;
+    let point_receiver = data_forward_launch(data_socket, capture.as_ref())?;
+    let health_monitor = Arc::new(HealthMonitor::new(
+        supervisor.event_bus().clone(),
+        Duration::from_secs(10),
+    ));
+    health_monitor.attach(&point_receiver);
     info!("data receiver launched ✅");
 
-    let duplicated_command_emitter = command_emitter.clone();
+    let imu_receiver = match &capture {
+        Some(capture) => ImuReceiver::launch_from(capture.tap(imu_socket)?)?,
+        None => ImuReceiver::launch(imu_socket)?,
+    };
+    imu_receiver.subscribe(|sample| debug!("imu sample: {:?}", sample));
+    info!("imu receiver launched ✅");
+
+    // sampling starts once connected and restarts after every reconnection
+    supervisor.start_sampling();
 
-    // register SIGINT handler
+    // register SIGINT handler, cleanup happens on main thread
+    let (sigint_sender, sigint_receiver) = mpsc::channel();
     ctrlc::set_handler(move || {
         info!("received SIGINT in callback, disconnecting...");
-        match duplicated_command_emitter
-            .command_execute::<SampleCtrlReq, CommonResp>(SAMPLE_END_REQ)
-        {
-            Ok(_) => info!("success end sampling ✅"),
-            Err(e) => warn!("error occurred when ending sampling: {}", e),
+        if let Err(e) = sigint_sender.send(()) {
+            warn!("error occurred when forwarding SIGINT: {}", e);
         }
+    })?;
 
-        match duplicated_command_emitter
-            .command_execute::<DisconnectReq, CommonResp>(DISCONNECT_REQ)
-        {
-            Ok(_) => info!("success disconnect ✅"),
-            Err(e) => warn!("error occurred when disconnecting: {}", e),
+    // recording starts once lidar is found, its broadcast code goes into file header
+    let mut recorder = None;
+    let mut interrupted = false;
+    if let Some(path) = record_path {
+        loop {
+            if let Some(device) = supervisor.device() {
+                let devices = [LvxDevice::new(&device, 0, extrinsics)];
+                let writer = LvxWriter::create(&path, &devices)?;
+                recorder = Some(LvxRecorder::attach(writer, &point_receiver, 0));
+                info!("recording point clouds into {} 🎥", path);
+                break;
+            }
+            match sigint_receiver.recv_timeout(Duration::from_millis(100)) {
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                _ => {
+                    interrupted = true;
+                    break;
+                }
+            }
         }
+    }
+    if !interrupted {
+        sigint_receiver.recv()?;
+    }
 
-        info!("lidar disconnected ✅");
-        match term_sender2.send(()) {
-            Ok(_) => info!("sigint sent, data receiver terminating..."),
-            Err(e) => warn!(
-                "error occurred when sending sig_term to data receiver: {}",
-                e
-            ),
-        }
-        match term_sender.send(()) {
-            Ok(_) => info!("sigint sent, heartbeat daemon terminating..."),
-            Err(e) => warn!(
-                "error occurred when sending sig_term to heartbeat daemon: {}",
-                e
-            ),
+    match supervisor.terminate() {
+        Ok(_) => info!("lidar disconnected ✅"),
+        Err(e) => warn!("error occurred when terminating supervisor: {}", e),
+    }
+    if let Some(recorder) = recorder {
+        match recorder.finish() {
+            Ok(_) => info!("recording finished ✅"),
+            Err(e) => warn!("error occurred when finishing recording: {}", e),
         }
-        match duplicated_command_emitter.term_sender.send(()) {
-            Ok(_) => info!("sigint sent, command processor terminating..."),
-            Err(e) => warn!(
-                "error occurred when sending sig_term to command processor: {}",
-                e
-            ),
+    }
+    info!("lidar health summary: {:?}", health_monitor.summary());
+    match point_receiver.terminate() {
+        Ok(_) => info!("data receiver terminated"),
+        Err(e) => warn!("error occurred when terminating data receiver: {}", e),
+    }
+    match imu_receiver.terminate() {
+        Ok(_) => info!("imu receiver terminated"),
+        Err(e) => warn!("error occurred when terminating imu receiver: {}", e),
+    }
+    if let Some(capture) = capture {
+        match capture.flush() {
+            Ok(_) => info!("capture flushed ✅"),
+            Err(e) => warn!("error occurred when flushing capture: {}", e),
         }
-    })?;
-
-    command_emitter.command_execute::<SampleCtrlReq, CommonResp>(SAMPLE_START_REQ)?;
-    info!("success start sampling ✅");
-
-    handle.join().unwrap()?;
+    }
     Ok(())
 }
@@ -0,0 +1,755 @@
+use crate::error::{LivoxError, Result};
+use crate::receiver::{DatagramSource, POLL_INTERVAL};
+use log::{info, log_enabled, warn};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAPNG_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const PCAPNG_SIMPLE_PACKET: u32 = 0x0000_0003;
+const PCAPNG_ENHANCED_PACKET: u32 = 0x0000_0006;
+/// option of interface description giving resolution of timestamps
+const PCAPNG_IF_TSRESOL: u16 = 9;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const IPPROTO_UDP: u8 = 17;
+const IPV4_HEADER_LEN: usize = 20;
+const UDP_HEADER_LEN: usize = 8;
+/// largest packet kept in a capture, enough for any UDP datagram
+const SNAPLEN: u32 = 65535;
+/// largest snaplen libpcap captures with, a longer record means a corrupt capture
+const MAX_SNAPLEN: u32 = 262144;
+/// pcapng block holding a packet of `MAX_SNAPLEN` with room for its fields and options
+const MAX_BLOCK_LEN: usize = MAX_SNAPLEN as usize + 65536;
+
+/// UDP datagram sent or received by driver, as stored in a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedDatagram {
+    /// time since UNIX epoch
+    pub timestamp: Duration,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+fn ipv4(addr: SocketAddr) -> Result<Ipv4Addr> {
+    match addr.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(_) => Err(LivoxError::InvalidArgument(format!(
+            "Cannot capture datagram of IPv6 address {}",
+            addr
+        ))),
+    }
+}
+
+/// internet checksum of IPv4 header
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let sum = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)])))
+        .sum::<u32>();
+    let sum = (sum & 0xFFFF) + (sum >> 16);
+    !((sum & 0xFFFF) + (sum >> 16)) as u16
+}
+
+/// Writes datagrams into a pcap file of raw IPv4 packets, readable by Wireshark
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// create pcap file at `path`, overwriting any existing one
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to create pcap file {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// write global header of a microsecond resolution capture
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&PCAP_MAGIC_MICROS.to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?; // version 2.4
+        writer.write_all(&4u16.to_le_bytes())?;
+        writer.write_all(&0i32.to_le_bytes())?; // timezone, always UTC
+        writer.write_all(&0u32.to_le_bytes())?; // timestamp accuracy, unused
+        writer.write_all(&SNAPLEN.to_le_bytes())?;
+        writer.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+        Ok(PcapWriter { writer })
+    }
+
+    /// append datagram wrapped into IPv4 and UDP headers
+    pub fn write_datagram(&mut self, datagram: &CapturedDatagram) -> Result<()> {
+        let (src, dst) = (ipv4(datagram.src)?, ipv4(datagram.dst)?);
+        let udp_len = UDP_HEADER_LEN + datagram.payload.len();
+        let total_len = u16::try_from(IPV4_HEADER_LEN + udp_len).map_err(|_| {
+            LivoxError::InvalidArgument(format!(
+                "Datagram of {} bytes is too long to capture",
+                datagram.payload.len()
+            ))
+        })?;
+
+        let mut packet = Vec::with_capacity(total_len as usize);
+        packet.extend([0x45, 0x00]); // version 4, header of 5 words
+        packet.extend(total_len.to_be_bytes());
+        packet.extend([0x00, 0x00, 0x40, 0x00]); // identification, don't fragment
+        packet.extend([64, IPPROTO_UDP, 0x00, 0x00]); // ttl, protocol, checksum below
+        packet.extend(src.octets());
+        packet.extend(dst.octets());
+        let checksum = ipv4_checksum(&packet);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+        packet.extend(datagram.src.port().to_be_bytes());
+        packet.extend(datagram.dst.port().to_be_bytes());
+        packet.extend((udp_len as u16).to_be_bytes());
+        packet.extend([0x00, 0x00]); // UDP checksum is optional over IPv4
+        packet.extend(&datagram.payload);
+
+        self.writer
+            .write_all(&(datagram.timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&datagram.timestamp.subsec_micros().to_le_bytes())?;
+        self.writer
+            .write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer
+            .write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&packet)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Capture shared by command processor and receivers, cheap to clone
+#[derive(Clone)]
+pub struct Capture {
+    writer: Arc<Mutex<PcapWriter<Box<dyn Write + Send>>>>,
+}
+
+impl Capture {
+    /// capture into pcap file at `path`, overwriting any existing one
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::create(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to create pcap file {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::new(BufWriter::new(file))
+    }
+
+    pub fn new(writer: impl Write + Send + 'static) -> Result<Self> {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        Ok(Capture {
+            writer: Arc::new(Mutex::new(PcapWriter::new(writer)?)),
+        })
+    }
+
+    /// record datagram sent from `src` to `dst` just now, failures are only logged
+    pub fn record(&self, src: SocketAddr, dst: SocketAddr, payload: &[u8]) {
+        let datagram = CapturedDatagram {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            src,
+            dst,
+            payload: payload.to_vec(),
+        };
+        if let Err(e) = self.writer.lock().unwrap().write_datagram(&datagram) {
+            if log_enabled!(log::Level::Warn) {
+                warn!("error occurred when capturing datagram: {}", e);
+            }
+        }
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+
+    /// wrap socket so that every datagram received on it is captured,
+    /// for [`PointReceiver::launch_from`](crate::receiver::PointReceiver::launch_from)
+    pub fn tap(&self, socket: UdpSocket) -> Result<CapturedSocket> {
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        Ok(CapturedSocket {
+            local_addr: socket.local_addr()?,
+            socket,
+            capture: self.clone(),
+        })
+    }
+}
+
+/// Socket capturing every datagram received on it
+pub struct CapturedSocket {
+    socket: UdpSocket,
+    local_addr: SocketAddr,
+    capture: Capture,
+}
+
+impl DatagramSource for CapturedSocket {
+    fn recv_datagram(&mut self, buffer: &mut [u8]) -> std::io::Result<Option<usize>> {
+        match self.socket.recv_from(buffer) {
+            Ok((size, src)) => {
+                self.capture.record(src, self.local_addr, &buffer[..size]);
+                Ok(Some(size))
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Byte order and timestamp resolution of the capture being read
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    big_endian: bool,
+    /// timestamp units per second
+    resolution: u64,
+}
+
+impl Format {
+    fn u16(&self, bytes: &[u8], offset: usize) -> Option<u16> {
+        let bytes = bytes.get(offset..offset + 2)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, bytes: &[u8], offset: usize) -> Option<u32> {
+        let bytes = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+}
+
+/// Interface of a pcapng section, packets refer to it by index
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    link_type: u32,
+    /// timestamp units per second
+    resolution: u64,
+}
+
+#[derive(Debug)]
+enum Kind {
+    Pcap {
+        format: Format,
+        link_type: u32,
+        /// longest packet record of capture
+        snaplen: u32,
+    },
+    Pcapng {
+        format: Format,
+        interfaces: Vec<Interface>,
+    },
+}
+
+/// Reads UDP datagrams back from a pcap or pcapng capture, taken by [`Capture`]
+/// or by tcpdump and Wireshark on Ethernet, Linux cooked or loopback interfaces.
+/// Packets other than unfragmented UDP over IPv4 are skipped.
+pub struct PcapReader<R> {
+    reader: R,
+    kind: Kind,
+}
+
+impl PcapReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path.as_ref()).map_err(|e| {
+            LivoxError::Io(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to open pcap file {}: {}",
+                    path.as_ref().display(),
+                    e
+                ),
+            ))
+        })?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> PcapReader<R> {
+    /// read global header or section header of capture
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        let kind = if u32::from_le_bytes(magic) == PCAPNG_SECTION_HEADER {
+            let mut pcapng = Kind::Pcapng {
+                format: Format {
+                    big_endian: false,
+                    resolution: 1_000_000,
+                },
+                interfaces: Vec::new(),
+            };
+            let body = read_block_body(&mut reader, None)?;
+            start_section(&mut pcapng, &body)?;
+            pcapng
+        } else {
+            let (big_endian, resolution) =
+                match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+                    (PCAP_MAGIC_MICROS, _) => (false, 1_000_000),
+                    (PCAP_MAGIC_NANOS, _) => (false, 1_000_000_000),
+                    (_, PCAP_MAGIC_MICROS) => (true, 1_000_000),
+                    (_, PCAP_MAGIC_NANOS) => (true, 1_000_000_000),
+                    _ => {
+                        return Err(LivoxError::Decode(format!(
+                            "Not a pcap or pcapng file, magic {:02X?}",
+                            magic
+                        )))
+                    }
+                };
+            let format = Format {
+                big_endian,
+                resolution,
+            };
+            let mut header = [0u8; 20];
+            reader.read_exact(&mut header)?;
+            Kind::Pcap {
+                format,
+                link_type: format.u32(&header, 16).unwrap_or_default() & 0xFFFF,
+                snaplen: format.u32(&header, 12).unwrap_or_default().min(MAX_SNAPLEN),
+            }
+        };
+        Ok(PcapReader { reader, kind })
+    }
+
+    /// next UDP datagram in capture, None at end of file
+    pub fn next_datagram(&mut self) -> Result<Option<CapturedDatagram>> {
+        loop {
+            let Some((link_type, timestamp, packet)) = self.next_packet()? else {
+                return Ok(None);
+            };
+            if let Some((src, dst, payload)) = link_payload(link_type, &packet).and_then(udp) {
+                return Ok(Some(CapturedDatagram {
+                    timestamp,
+                    src,
+                    dst,
+                    payload: payload.to_vec(),
+                }));
+            }
+        }
+    }
+
+    /// next packet of any kind, with link type and timestamp
+    fn next_packet(&mut self) -> Result<Option<(u32, Duration, Vec<u8>)>> {
+        match &mut self.kind {
+            Kind::Pcap {
+                format,
+                link_type,
+                snaplen,
+            } => {
+                let mut header = [0u8; 16];
+                if !read_or_eof(&mut self.reader, &mut header)? {
+                    return Ok(None);
+                }
+                let field = |offset| format.u32(&header, offset).unwrap_or_default();
+                if field(8) > *snaplen {
+                    return Err(LivoxError::Decode(format!(
+                        "Pcap record of {} bytes exceeds snaplen {}",
+                        field(8),
+                        snaplen
+                    )));
+                }
+                let mut packet = vec![0u8; field(8) as usize];
+                self.reader.read_exact(&mut packet)?;
+                let timestamp = Duration::from_secs(u64::from(field(0)))
+                    + duration(u64::from(field(4)), format.resolution);
+                Ok(Some((*link_type, timestamp, packet)))
+            }
+            Kind::Pcapng { .. } => loop {
+                let mut block_type = [0u8; 4];
+                if !read_or_eof(&mut self.reader, &mut block_type)? {
+                    return Ok(None);
+                }
+                if u32::from_le_bytes(block_type) == PCAPNG_SECTION_HEADER {
+                    let body = read_block_body(&mut self.reader, None)?;
+                    start_section(&mut self.kind, &body)?;
+                    continue;
+                }
+                let Kind::Pcapng { format, interfaces } = &mut self.kind else {
+                    unreachable!("pcapng reader switched to pcap");
+                };
+                let block_type = format.u32(&block_type, 0).unwrap_or_default();
+                let body = read_block_body(&mut self.reader, Some(*format))?;
+                let malformed =
+                    || LivoxError::Decode(format!("Malformed pcapng block of type {}", block_type));
+
+                match block_type {
+                    PCAPNG_INTERFACE_DESCRIPTION => {
+                        let link_type = format.u16(&body, 0).ok_or_else(malformed)?;
+                        let resolution = body
+                            .get(8..)
+                            .and_then(|options| tsresol(*format, options))
+                            .unwrap_or(1_000_000);
+                        interfaces.push(Interface {
+                            link_type: u32::from(link_type),
+                            resolution,
+                        });
+                    }
+                    PCAPNG_ENHANCED_PACKET => {
+                        let field = |offset| format.u32(&body, offset).ok_or_else(malformed);
+                        let interface = *interfaces.get(field(0)? as usize).ok_or_else(|| {
+                            LivoxError::Decode("Packet of undescribed interface".to_string())
+                        })?;
+                        let units = (u64::from(field(4)?) << 32) | u64::from(field(8)?);
+                        let captured = field(12)? as usize;
+                        let packet = body.get(20..20 + captured).ok_or_else(malformed)?;
+                        return Ok(Some((
+                            interface.link_type,
+                            duration(units, interface.resolution),
+                            packet.to_vec(),
+                        )));
+                    }
+                    PCAPNG_SIMPLE_PACKET => {
+                        let interface = *interfaces.first().ok_or_else(|| {
+                            LivoxError::Decode("Packet of undescribed interface".to_string())
+                        })?;
+                        let original = format.u32(&body, 0).ok_or_else(malformed)? as usize;
+                        let packet = body.get(4..).ok_or_else(malformed)?;
+                        let packet = &packet[..original.min(packet.len())];
+                        return Ok(Some((interface.link_type, Duration::ZERO, packet.to_vec())));
+                    }
+                    // statistics, name resolution and custom blocks
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+/// fill buffer, false if reader was already at end of file
+fn read_or_eof(reader: &mut impl Read, buffer: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// read rest of pcapng block after its type, `format` is None for a section header,
+/// whose byte order is only known from the magic starting its body
+fn read_block_body(reader: &mut impl Read, format: Option<Format>) -> Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut magic = [0u8; 4];
+    let big_endian = match format {
+        Some(format) => format.big_endian,
+        None => {
+            reader.read_exact(&mut magic)?;
+            u32::from_le_bytes(magic) != PCAPNG_BYTE_ORDER_MAGIC
+        }
+    };
+    let len = match big_endian {
+        true => u32::from_be_bytes(len),
+        false => u32::from_le_bytes(len),
+    } as usize;
+
+    let prefix = if format.is_none() { magic.len() } else { 0 };
+    let body_len = len
+        .checked_sub(12)
+        .filter(|body_len| *body_len >= prefix && len <= MAX_BLOCK_LEN)
+        .ok_or_else(|| LivoxError::Decode(format!("Malformed pcapng block of {} bytes", len)))?;
+    let mut body = vec![0u8; body_len];
+    body[..prefix].copy_from_slice(&magic[..prefix]);
+    reader.read_exact(&mut body[prefix..])?;
+    let mut trailer = [0u8; 4];
+    reader.read_exact(&mut trailer)?;
+    Ok(body)
+}
+
+/// reset byte order and interfaces from body of a section header
+fn start_section(kind: &mut Kind, body: &[u8]) -> Result<()> {
+    let big_endian = match body
+        .get(..4)
+        .map(|magic| u32::from_le_bytes(magic.try_into().unwrap_or_default()))
+    {
+        Some(PCAPNG_BYTE_ORDER_MAGIC) => false,
+        Some(magic) if magic.swap_bytes() == PCAPNG_BYTE_ORDER_MAGIC => true,
+        _ => {
+            return Err(LivoxError::Decode(
+                "Malformed pcapng section header".to_string(),
+            ))
+        }
+    };
+    *kind = Kind::Pcapng {
+        format: Format {
+            big_endian,
+            resolution: 1_000_000,
+        },
+        interfaces: Vec::new(),
+    };
+    Ok(())
+}
+
+/// timestamp units per second given by `if_tsresol` option, if any
+fn tsresol(format: Format, mut options: &[u8]) -> Option<u64> {
+    while options.len() >= 4 {
+        let code = format.u16(options, 0)?;
+        let len = format.u16(options, 2)? as usize;
+        let value = options.get(4..4 + len)?;
+        if code == PCAPNG_IF_TSRESOL {
+            let exponent = u32::from(*value.first()? & 0x7F);
+            return match value.first()? & 0x80 {
+                0 => 10u64.checked_pow(exponent),
+                _ => 2u64.checked_pow(exponent),
+            };
+        }
+        // option values are padded to 32 bits
+        options = options.get(4 + len.div_ceil(4) * 4..)?;
+    }
+    None
+}
+
+fn duration(units: u64, resolution: u64) -> Duration {
+    let nanos = (units % resolution) as u128 * 1_000_000_000 / resolution as u128;
+    Duration::new(units / resolution, nanos as u32)
+}
+
+/// IPv4 packet carried by link layer frame
+fn link_payload(link_type: u32, frame: &[u8]) -> Option<&[u8]> {
+    match link_type {
+        LINKTYPE_RAW | LINKTYPE_IPV4 => Some(frame),
+        LINKTYPE_NULL => {
+            // address family in byte order of capturing host, AF_INET is 2 everywhere
+            let family = frame.get(..4)?;
+            (family == [2, 0, 0, 0] || family == [0, 0, 0, 2]).then(|| &frame[4..])
+        }
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            let mut ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            while ethertype == ETHERTYPE_VLAN {
+                offset += 4;
+                ethertype = u16::from_be_bytes(frame.get(offset..offset + 2)?.try_into().ok()?);
+            }
+            (ethertype == ETHERTYPE_IPV4).then_some(frame.get(offset + 2..)?)
+        }
+        LINKTYPE_LINUX_SLL => {
+            let protocol = u16::from_be_bytes(frame.get(14..16)?.try_into().ok()?);
+            (protocol == ETHERTYPE_IPV4).then_some(frame.get(16..)?)
+        }
+        _ => None,
+    }
+}
+
+/// addresses and payload of an unfragmented UDP over IPv4 packet
+fn udp(packet: &[u8]) -> Option<(SocketAddr, SocketAddr, &[u8])> {
+    let version_ihl = *packet.first()?;
+    if version_ihl >> 4 != 4 || *packet.get(9)? != IPPROTO_UDP {
+        return None;
+    }
+    let header_len = usize::from(version_ihl & 0x0F) * 4;
+    let total_len = usize::from(u16::from_be_bytes(packet.get(2..4)?.try_into().ok()?));
+    let fragment = u16::from_be_bytes(packet.get(6..8)?.try_into().ok()?);
+    // more fragments flag or fragment offset set
+    if fragment & 0x3FFF != 0 {
+        return None;
+    }
+    let ip = |offset: usize| -> Option<Ipv4Addr> {
+        let octets: [u8; 4] = packet.get(offset..offset + 4)?.try_into().ok()?;
+        Some(Ipv4Addr::from(octets))
+    };
+    let (src_ip, dst_ip) = (ip(12)?, ip(16)?);
+
+    // frames may be padded past the end of IP packet
+    let segment = packet.get(header_len..total_len.min(packet.len()))?;
+    let port = |offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            segment.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let udp_len = usize::from(port(4)?);
+    let payload = segment.get(UDP_HEADER_LEN..udp_len.min(segment.len()))?;
+    Some((
+        SocketAddr::from((src_ip, port(0)?)),
+        SocketAddr::from((dst_ip, port(2)?)),
+        payload,
+    ))
+}
+
+/// Replays datagrams of a capture sent to one port, as fast as they can be read,
+/// in place of that port's socket
+pub struct PcapSource<R> {
+    reader: PcapReader<R>,
+    port: u16,
+    finished: bool,
+}
+
+impl<R: Read> PcapSource<R> {
+    /// replay datagrams sent to `port`, such as data port of driver
+    pub fn new(reader: PcapReader<R>, port: u16) -> Self {
+        PcapSource {
+            reader,
+            port,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read + Send + 'static> DatagramSource for PcapSource<R> {
+    fn recv_datagram(&mut self, buffer: &mut [u8]) -> std::io::Result<Option<usize>> {
+        while !self.finished {
+            let datagram = self
+                .reader
+                .next_datagram()
+                .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+            match datagram {
+                Some(datagram) if datagram.dst.port() == self.port => {
+                    let len = datagram.payload.len().min(buffer.len());
+                    buffer[..len].copy_from_slice(&datagram.payload[..len]);
+                    return Ok(Some(len));
+                }
+                Some(_) => {}
+                None => {
+                    info!("pcap replay finished");
+                    self.finished = true;
+                }
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::io::Cursor;
+
+    fn datagram(payload: &[u8]) -> CapturedDatagram {
+        CapturedDatagram {
+            timestamp: Duration::new(1_700_000_000, 123_456_000),
+            src: SocketAddr::from(([192, 168, 1, 3], 65000)),
+            dst: SocketAddr::from(([192, 168, 1, 50], 50001)),
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_write_and_read() {
+        let mut writer = PcapWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_datagram(&datagram(&[0xAA, 0x01])).unwrap();
+        writer.write_datagram(&datagram(&[])).unwrap();
+        let file = writer.finish().unwrap().into_inner();
+        // IPv4 header checksum of the first packet
+        assert_eq!(&file[24 + 16 + 10..24 + 16 + 12], &[0xB7, 0x49]);
+
+        let mut reader = PcapReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(
+            reader.next_datagram().unwrap(),
+            Some(datagram(&[0xAA, 0x01]))
+        );
+        assert_eq!(reader.next_datagram().unwrap(), Some(datagram(&[])));
+        assert_eq!(reader.next_datagram().unwrap(), None);
+    }
+
+    #[test]
+    fn test_reject_oversized_records() {
+        let mut file = PcapWriter::new(Cursor::new(Vec::new()))
+            .unwrap()
+            .finish()
+            .unwrap()
+            .into_inner();
+        file.extend([0; 8]); // timestamp
+        file.extend(u32::MAX.to_le_bytes()); // included length
+        file.extend(u32::MAX.to_le_bytes()); // original length
+        let mut reader = PcapReader::new(Cursor::new(file)).unwrap();
+        assert!(matches!(reader.next_datagram(), Err(LivoxError::Decode(_))));
+
+        let mut file = PCAPNG_SECTION_HEADER.to_le_bytes().to_vec();
+        file.extend(u32::MAX.to_le_bytes());
+        file.extend(PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        assert!(matches!(
+            PcapReader::new(Cursor::new(file)),
+            Err(LivoxError::Decode(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_pcapng_ethernet() {
+        // IPv4 and UDP headers of a packet written by `PcapWriter`, behind an Ethernet header
+        let mut raw = PcapWriter::new(Cursor::new(Vec::new())).unwrap();
+        raw.write_datagram(&datagram(&[0xAA, 0x01])).unwrap();
+        let raw = raw.finish().unwrap().into_inner();
+        let mut frame = vec![0xFF; 12];
+        frame.extend(ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend(&raw[24 + 16..]);
+        frame.extend([0, 0]); // padding, ignored
+
+        let block = |block_type: u32, body: &[u8]| {
+            let len = (12 + body.len()) as u32;
+            let mut block = block_type.to_le_bytes().to_vec();
+            block.extend(len.to_le_bytes());
+            block.extend(body);
+            block.extend(len.to_le_bytes());
+            block
+        };
+        let mut section = PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        section.extend([1, 0, 0, 0]); // version 1.0
+        section.extend(u64::MAX.to_le_bytes()); // section length unknown
+        let mut interface = (LINKTYPE_ETHERNET as u16).to_le_bytes().to_vec();
+        interface.extend([0, 0]);
+        interface.extend(SNAPLEN.to_le_bytes());
+        interface.extend(PCAPNG_IF_TSRESOL.to_le_bytes());
+        interface.extend([1, 0, 9, 0, 0, 0]); // nanoseconds, padded
+        interface.extend([0, 0, 0, 0]); // end of options
+        let units = 1_700_000_000_123_456_000u64;
+        let mut packet = 0u32.to_le_bytes().to_vec();
+        packet.extend(((units >> 32) as u32).to_le_bytes());
+        packet.extend((units as u32).to_le_bytes());
+        packet.extend((frame.len() as u32).to_le_bytes());
+        packet.extend((frame.len() as u32).to_le_bytes());
+        packet.extend(&frame);
+        packet.resize(packet.len().div_ceil(4) * 4, 0);
+
+        let mut file = block(PCAPNG_SECTION_HEADER, &section);
+        file.extend(block(PCAPNG_INTERFACE_DESCRIPTION, &interface));
+        file.extend(block(PCAPNG_ENHANCED_PACKET, &packet));
+
+        let mut reader = PcapReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(
+            reader.next_datagram().unwrap(),
+            Some(datagram(&[0xAA, 0x01]))
+        );
+        assert_eq!(reader.next_datagram().unwrap(), None);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::frames::{deserialize_data, ImuSample, PointPacket};
+use log::{debug, info, log_enabled, warn};
+use std::net::UdpSocket;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub type WorkerHandle = thread::JoinHandle<Result<()>>;
+
+type PointCallback = Box<dyn FnMut(&PointPacket<'_>) + Send>;
+type ImuCallback = Box<dyn FnMut(&ImuSample) + Send>;
+
+/// Upper bound of a datagram on data port and IMU port
+const DATAGRAM_CAPACITY: usize = 1500;
+
+/// Socket read timeout, bounds how long a receiver takes to notice sig_term
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Where a receiver takes datagrams from, a socket or a recording
+pub trait DatagramSource: Send + 'static {
+    /// wait for the next datagram for about `POLL_INTERVAL` at most,
+    /// None if nothing arrived in time
+    fn recv_datagram(&mut self, buffer: &mut [u8]) -> std::io::Result<Option<usize>>;
+}
+
+/// socket is expected to have its read timeout set to `POLL_INTERVAL`
+impl DatagramSource for UdpSocket {
+    fn recv_datagram(&mut self, buffer: &mut [u8]) -> std::io::Result<Option<usize>> {
+        match self.recv_from(buffer) {
+            Ok((size, _)) => Ok(Some(size)),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// launch a thread receiving datagrams from source and handing them to `on_datagram`
+fn receiver_launch<S, F>(
+    name: &'static str,
+    mut source: S,
+    mut on_datagram: F,
+) -> Result<(WorkerHandle, mpsc::Sender<()>)>
+where
+    S: DatagramSource,
+    F: FnMut(&[u8]) + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+
+    let handle: WorkerHandle = thread::spawn(move || {
+        let mut buffer = [0; DATAGRAM_CAPACITY];
+        loop {
+            if rx.try_recv().is_ok() {
+                info!("received sig_term, {} exiting...", name);
+                return Ok(());
+            }
+            match source.recv_datagram(&mut buffer) {
+                Ok(Some(size)) => on_datagram(&buffer[..size]),
+                Ok(None) => {}
+                Err(e) => {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("error occurred when receiving data in {}: {}", name, e);
+                    }
+                    // a source failing for good would otherwise be polled in a hot loop
+                    thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+    });
+    debug!("{} started ✅", name);
+    Ok((handle, tx))
+}
+
+/// Receives point cloud packets from data port and dispatches them to subscribers
+pub struct PointReceiver {
+    subscribers: Arc<Mutex<Vec<PointCallback>>>,
+    handle: WorkerHandle,
+    term_sender: mpsc::Sender<()>,
+}
+
+impl PointReceiver {
+    /// start receiving on data socket
+    pub fn launch(data_socket: UdpSocket) -> Result<Self> {
+        data_socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        Self::launch_from(data_socket)
+    }
+
+    /// start receiving from any source of data port datagrams, such as a recording
+    pub fn launch_from(source: impl DatagramSource) -> Result<Self> {
+        let subscribers: Arc<Mutex<Vec<PointCallback>>> = Arc::new(Mutex::new(Vec::new()));
+        let duplicated_subscribers = subscribers.clone();
+
+        let (handle, term_sender) =
+            receiver_launch(
+                "point receiver",
+                source,
+                move |datagram| match deserialize_data(datagram) {
+                    Ok(packet) => duplicated_subscribers
+                        .lock()
+                        .unwrap()
+                        .iter_mut()
+                        .for_each(|callback| callback(&packet)),
+                    Err(e) => {
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("error occurred when deserializing point packet: {}", e);
+                        }
+                    }
+                },
+            )?;
+
+        Ok(PointReceiver {
+            subscribers,
+            handle,
+            term_sender,
+        })
+    }
+
+    /// register callback invoked with every point packet received
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: FnMut(&PointPacket<'_>) + Send + 'static,
+    {
+        self.subscribers.lock().unwrap().push(Box::new(callback));
+    }
+
+    /// stop receiving and wait for receiver thread to exit
+    pub fn terminate(self) -> Result<()> {
+        terminate("point receiver", self.handle, self.term_sender)
+    }
+}
+
+/// Receives IMU packets from IMU port and dispatches samples to subscribers
+pub struct ImuReceiver {
+    subscribers: Arc<Mutex<Vec<ImuCallback>>>,
+    handle: WorkerHandle,
+    term_sender: mpsc::Sender<()>,
+}
+
+impl ImuReceiver {
+    /// start receiving on IMU socket
+    pub fn launch(imu_socket: UdpSocket) -> Result<Self> {
+        imu_socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        Self::launch_from(imu_socket)
+    }
+
+    /// start receiving from any source of IMU port datagrams, such as a recording
+    pub fn launch_from(source: impl DatagramSource) -> Result<Self> {
+        let subscribers: Arc<Mutex<Vec<ImuCallback>>> = Arc::new(Mutex::new(Vec::new()));
+        let duplicated_subscribers = subscribers.clone();
+
+        let (handle, term_sender) =
+            receiver_launch(
+                "imu receiver",
+                source,
+                move |datagram| match deserialize_data(datagram) {
+                    Ok(packet) => {
+                        let mut subscribers = duplicated_subscribers.lock().unwrap();
+                        packet.imu_samples().for_each(|sample| {
+                            subscribers
+                                .iter_mut()
+                                .for_each(|callback| callback(&sample))
+                        });
+                    }
+                    Err(e) => {
+                        if log_enabled!(log::Level::Warn) {
+                            warn!("error occurred when deserializing imu packet: {}", e);
+                        }
+                    }
+                },
+            )?;
+
+        Ok(ImuReceiver {
+            subscribers,
+            handle,
+            term_sender,
+        })
+    }
+
+    /// register callback invoked with every IMU sample received
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: FnMut(&ImuSample) + Send + 'static,
+    {
+        self.subscribers.lock().unwrap().push(Box::new(callback));
+    }
+
+    /// stop receiving and wait for receiver thread to exit
+    pub fn terminate(self) -> Result<()> {
+        terminate("imu receiver", self.handle, self.term_sender)
+    }
+}
+
+pub(crate) fn terminate(
+    name: &'static str,
+    handle: WorkerHandle,
+    term_sender: mpsc::Sender<()>,
+) -> Result<()> {
+    if let Err(e) = term_sender.send(()) {
+        if log_enabled!(log::Level::Warn) {
+            warn!("error occurred when sending sig_term to {}: {}", name, e);
+        }
+    }
+    handle
+        .join()
+        .map_err(|_| LivoxError::WorkerPanicked(name))?
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FailingSource(Arc<AtomicUsize>);
+
+    impl DatagramSource for FailingSource {
+        fn recv_datagram(&mut self, _buffer: &mut [u8]) -> std::io::Result<Option<usize>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn test_failing_source_is_polled_at_poll_interval() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let receiver = PointReceiver::launch_from(FailingSource(calls.clone())).unwrap();
+        thread::sleep(POLL_INTERVAL * 3);
+        receiver.terminate().unwrap();
+        assert!((1..=5).contains(&calls.load(Ordering::SeqCst)));
+    }
+}
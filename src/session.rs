@@ -0,0 +1,414 @@
+use crate::accumulator::{Frame, FrameAccumulator, Integration};
+use crate::cloud::CloudPoint;
+use crate::connection::{ConnectionState, ConnectionSupervisor};
+use crate::discovery::BroadcastListener;
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::cfg::{LidarConfig, SessionConfig};
+use crate::lidar_frame::frames::PointPacket;
+use crate::receiver::PointReceiver;
+use crate::transform::Transform;
+use log::{info, log_enabled, warn};
+use std::collections::VecDeque;
+use std::net::UdpSocket;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+type TaggedCallback = Box<dyn FnMut(LidarTag, &PointPacket<'_>) + Send>;
+type MergedCallback = Box<dyn FnMut(&MergedFrame) + Send>;
+
+/// Which lidar of a session a packet or frame came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LidarTag {
+    /// position of lidar in `SessionConfig::lidars`
+    pub device: usize,
+    /// slot id reported in data packet header
+    pub slot_id: u8,
+    /// lidar id reported in data packet header
+    pub lidar_id: u8,
+}
+
+impl LidarTag {
+    fn new(device: usize, packet: &PointPacket<'_>) -> Self {
+        LidarTag {
+            device,
+            slot_id: packet.header.slot_id,
+            lidar_id: packet.header.lidar_id,
+        }
+    }
+}
+
+/// Clouds of several lidars merged into the same coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedFrame {
+    /// lidar time of the first packet in frame
+    pub start: u64,
+    /// lidar time of the last packet in frame
+    pub end: u64,
+    /// lidars which contributed a frame, silent lidars are left out
+    pub lidars: Vec<LidarTag>,
+    pub points: Vec<CloudPoint>,
+}
+
+/// Sockets of a lidar in a session, bound by caller
+pub struct LidarSockets {
+    pub control: UdpSocket,
+    pub data: UdpSocket,
+}
+
+/// Connection and point receiver of a lidar in a session
+struct Lidar {
+    config: LidarConfig,
+    supervisor: ConnectionSupervisor,
+    receiver: PointReceiver,
+}
+
+/// Frames of every lidar, merged with the frames of other lidars overlapping them in lidar
+/// time, so lidars need synchronized clocks for their clouds to be merged
+struct Merger {
+    /// span of a frame in nanoseconds
+    window: u64,
+    /// frames of each lidar not merged yet, in order
+    pending: Vec<VecDeque<(LidarTag, Frame)>>,
+    /// lidar time each lidar has reached, end of its latest frame
+    reached: Vec<Option<u64>>,
+    subscribers: Vec<MergedCallback>,
+}
+
+impl Merger {
+    fn new(window: Duration, lidar_num: usize) -> Self {
+        Merger {
+            window: window.as_nanos() as u64,
+            pending: vec![VecDeque::new(); lidar_num],
+            reached: vec![None; lidar_num],
+            subscribers: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, tag: LidarTag, frame: Frame) {
+        self.reached[tag.device] = Some(frame.end);
+        self.pending[tag.device].push_back((tag, frame));
+        while let Some(merged) = self.next_merged() {
+            self.subscribers
+                .iter_mut()
+                .for_each(|callback| callback(&merged));
+        }
+    }
+
+    /// oldest pending frame with the frame of every other lidar starting within a window
+    /// of it, once every lidar got past that window or lags a whole window behind the rest
+    fn next_merged(&mut self) -> Option<MergedFrame> {
+        let start = self
+            .pending
+            .iter()
+            .filter_map(|frames| frames.front().map(|(_, frame)| frame.start))
+            .min()?;
+        let end = start + self.window;
+        let latest = self.reached.iter().flatten().max().copied().unwrap_or(0);
+        // frames of a lidar arrive in order, lidar past the window has nothing more for it
+        let settled = self
+            .reached
+            .iter()
+            .all(|reached| reached.is_some_and(|reached| reached >= end));
+        if !settled && latest < end + self.window {
+            return None;
+        }
+
+        let mut merged = MergedFrame {
+            start,
+            end: start,
+            lidars: Vec::new(),
+            points: Vec::new(),
+        };
+        for frames in &mut self.pending {
+            if frames.front().is_some_and(|(_, frame)| frame.start < end) {
+                let (tag, frame) = frames.pop_front()?;
+                merged.end = merged.end.max(frame.end);
+                merged.lidars.push(tag);
+                merged.points.extend(frame.points);
+            }
+        }
+        Some(merged)
+    }
+}
+
+/// Keeps several lidars connected at once, each supervised on its own so that
+/// losing one of them leaves the others streaming
+pub struct SessionManager {
+    lidars: Vec<Lidar>,
+    listener: BroadcastListener,
+    subscribers: Arc<Mutex<Vec<TaggedCallback>>>,
+    merger: Option<Arc<Mutex<Merger>>>,
+}
+
+impl SessionManager {
+    /// bind sockets given in config and start supervising every lidar
+    pub fn connect(config: SessionConfig) -> Result<Self> {
+        let broadcast_socket = UdpSocket::bind(config.broadcast_addr())?;
+        let sockets = config
+            .lidars
+            .iter()
+            .map(|lidar| {
+                Ok(LidarSockets {
+                    control: UdpSocket::bind(lidar.cmd_addr())?,
+                    data: UdpSocket::bind(lidar.data_addr())?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::launch(config, broadcast_socket, sockets)
+    }
+
+    /// start supervising every lidar through already bound sockets, given in the order of
+    /// `SessionConfig::lidars`
+    ///
+    /// when clouds are merged, extrinsics of each lidar are applied on host instead of
+    /// being written to lidar
+    pub fn launch(
+        config: SessionConfig,
+        broadcast_socket: UdpSocket,
+        sockets: Vec<LidarSockets>,
+    ) -> Result<Self> {
+        config.validate()?;
+        if sockets.len() != config.lidars.len() {
+            return Err(LivoxError::InvalidArgument(format!(
+                "Sockets given for {} lidars, session has {}",
+                sockets.len(),
+                config.lidars.len()
+            )));
+        }
+
+        let listener = BroadcastListener::launch(broadcast_socket)?;
+        let subscribers: Arc<Mutex<Vec<TaggedCallback>>> = Arc::new(Mutex::new(Vec::new()));
+        let merge_window = config.merge_window();
+        let merger = merge_window
+            .map(|window| Arc::new(Mutex::new(Merger::new(window, config.lidars.len()))));
+
+        let mut lidars = Vec::new();
+        for (device, (mut lidar_config, sockets)) in
+            config.lidars.into_iter().zip(sockets).enumerate()
+        {
+            let code = lidar_config.broadcast_code.clone().unwrap_or_default();
+            let extrinsics = if merger.is_some() {
+                lidar_config.extrinsics.take()
+            } else {
+                None
+            };
+
+            let receiver = PointReceiver::launch(sockets.data)?;
+            let duplicated_subscribers = subscribers.clone();
+            receiver.subscribe(move |packet| {
+                let tag = LidarTag::new(device, packet);
+                duplicated_subscribers
+                    .lock()
+                    .unwrap()
+                    .iter_mut()
+                    .for_each(|callback| callback(tag, packet));
+            });
+            if let (Some(merger), Some(window)) = (&merger, merge_window) {
+                let merger = merger.clone();
+                let transform = Transform::from(extrinsics.unwrap_or_default());
+                let mut accumulator = FrameAccumulator::new(Integration::TimeWindow(window))?;
+                receiver.subscribe(move |packet| {
+                    for mut frame in accumulator.push(packet) {
+                        transform.apply_cloud(&mut frame.points);
+                        merger
+                            .lock()
+                            .unwrap()
+                            .push(LidarTag::new(device, packet), frame);
+                    }
+                });
+            }
+
+            let supervisor = ConnectionSupervisor::launch_with(
+                lidar_config.clone(),
+                listener.discover(&code),
+                sockets.control,
+            )?;
+            info!("supervising lidar {} as device {} ✅", code, device);
+            lidars.push(Lidar {
+                config: lidar_config,
+                supervisor,
+                receiver,
+            });
+        }
+
+        Ok(Self {
+            lidars,
+            listener,
+            subscribers,
+            merger,
+        })
+    }
+
+    /// number of lidars in session
+    pub fn len(&self) -> usize {
+        self.lidars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lidars.is_empty()
+    }
+
+    /// configuration of lidar at `device`, extrinsics are absent if applied on host
+    pub fn config(&self, device: usize) -> Option<&LidarConfig> {
+        self.lidars.get(device).map(|lidar| &lidar.config)
+    }
+
+    /// supervisor of lidar at `device`, for its client and events
+    pub fn supervisor(&self, device: usize) -> Option<&ConnectionSupervisor> {
+        self.lidars.get(device).map(|lidar| &lidar.supervisor)
+    }
+
+    /// connection state of every lidar, in config order
+    pub fn states(&self) -> Vec<ConnectionState> {
+        self.lidars
+            .iter()
+            .map(|lidar| lidar.supervisor.state())
+            .collect()
+    }
+
+    /// register callback invoked with every point packet of every lidar
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: FnMut(LidarTag, &PointPacket<'_>) + Send + 'static,
+    {
+        self.subscribers.lock().unwrap().push(Box::new(callback));
+    }
+
+    /// register callback invoked with every merged frame, fails unless session merges clouds
+    pub fn subscribe_merged<F>(&self, callback: F) -> Result<()>
+    where
+        F: FnMut(&MergedFrame) + Send + 'static,
+    {
+        let merger = self.merger.as_ref().ok_or_else(|| {
+            LivoxError::InvalidArgument("Session does not merge clouds".to_string())
+        })?;
+        merger.lock().unwrap().subscribers.push(Box::new(callback));
+        Ok(())
+    }
+
+    /// write configured slot id to flash of every lidar holding another one,
+    /// return devices written, which need a reboot before reporting it
+    pub fn assign_slot_ids(&self) -> Result<Vec<usize>> {
+        let mut written = Vec::new();
+        for (device, lidar) in self.lidars.iter().enumerate() {
+            let Some(slot_id) = lidar.config.slot_id else {
+                continue;
+            };
+            let client = lidar
+                .supervisor
+                .client()
+                .ok_or_else(|| LivoxError::Closed(format!("Lidar {} is not connected", device)))?;
+            if client.assign_slot_id(slot_id)? {
+                info!(
+                    "slot id {} written to lidar {}, reboot it to apply 🔁",
+                    slot_id, device
+                );
+                written.push(device);
+            }
+        }
+        Ok(written)
+    }
+
+    /// ask every lidar to sample as soon as connected
+    pub fn start_sampling(&self) {
+        self.lidars
+            .iter()
+            .for_each(|lidar| lidar.supervisor.start_sampling());
+    }
+
+    /// ask every lidar to stop sampling
+    pub fn stop_sampling(&self) {
+        self.lidars
+            .iter()
+            .for_each(|lidar| lidar.supervisor.stop_sampling());
+    }
+
+    /// disconnect from every lidar, the first error is returned once all are stopped
+    pub fn terminate(self) -> Result<()> {
+        let mut result = Ok(());
+        for lidar in self.lidars {
+            for outcome in [lidar.supervisor.terminate(), lidar.receiver.terminate()] {
+                if let Err(e) = outcome {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("error occurred when terminating lidar session: {}", e);
+                    }
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        self.listener.terminate().and(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn frame(start: u64, x: f32) -> Frame {
+        Frame {
+            start,
+            end: start + 90,
+            points: vec![CloudPoint {
+                x,
+                y: 0.0,
+                z: 0.0,
+                reflectivity: 0,
+                tag: 0,
+                timestamp: start,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_merger() {
+        let merged = Arc::new(Mutex::new(Vec::new()));
+        let duplicated_merged = merged.clone();
+        let mut merger = Merger::new(Duration::from_nanos(100), 2);
+        merger
+            .subscribers
+            .push(Box::new(move |frame: &MergedFrame| {
+                duplicated_merged.lock().unwrap().push(frame.clone())
+            }));
+        let tag = |device| LidarTag {
+            device,
+            slot_id: device as u8 + 1,
+            lidar_id: 1,
+        };
+        let merged_starts = || {
+            merged
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|frame| {
+                    let starts: Vec<_> = frame.points.iter().map(|p| p.timestamp).collect();
+                    (frame.lidars.clone(), starts)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // second lidar windows are offset and its frames arrive a frame late
+        merger.push(tag(0), frame(0, 1.0));
+        merger.push(tag(0), frame(100, 2.0));
+        assert!(merged.lock().unwrap().is_empty());
+        merger.push(tag(1), frame(40, 3.0));
+        merger.push(tag(1), frame(140, 4.0));
+        merger.push(tag(0), frame(200, 5.0));
+        assert_eq!(
+            merged_starts(),
+            vec![
+                (vec![tag(0), tag(1)], vec![0, 40]),
+                (vec![tag(0), tag(1)], vec![100, 140]),
+            ]
+        );
+        assert_eq!(merged.lock().unwrap()[0].end, 130);
+
+        // second lidar went silent, first one is merged alone once a window ahead
+        merger.push(tag(0), frame(300, 6.0));
+        assert_eq!(merged.lock().unwrap().len(), 2);
+        merger.push(tag(0), frame(400, 7.0));
+        assert_eq!(merged_starts()[2], (vec![tag(0)], vec![200]));
+    }
+}
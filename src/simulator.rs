@@ -0,0 +1,907 @@
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::cfg::Extrinsics;
+use crate::lidar_frame::frames::{
+    encode_broadcast_code, points_per_packet, serialize_data, AbnormalStatusMsg, Broadcast,
+    CartesianPoint, CommonResp, ControlFrame, CoordinateType, DataFrame, DeviceInfoResp,
+    DeviceType, DualCartesianPoint, DualSphericalPoint, GetCmd, GetFanStateResp,
+    GetImuPushFrequencyResp, GetReturnModeResp, HeartbeatResp, HubLidarInfo, HubLidarResult,
+    HubLidarResultsResp, HubLidarValueResult, HubLidarValuesResp, HubQueryLidarInfoResp, ImuPoint,
+    ImuPushFrequency, IpInfoResp, IpMode, Point, ReadFlashResp, ReadOuterParametersResp, Request,
+    Response, ReturnMode, SphericalPoint, StandardCartesianPoint, StandardSphericalPoint,
+    TripleCartesianPoint, TripleSphericalPoint, WriteFlashResp,
+};
+use crate::receiver::WorkerHandle;
+use crate::time_sync::{decode_utc, encode_utc};
+use crate::transform::{cartesian_to_spherical, spherical_to_cartesian, Transform};
+use log::{debug, info, log_enabled, warn};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Settings of a simulated lidar
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorConfig {
+    pub broadcast_code: String,
+    pub dev_type: DeviceType,
+    /// address command socket binds to, broadcast frames are sent from it as well
+    pub cmd_addr: SocketAddr,
+    /// address broadcast frames are sent to, broadcast port of driver
+    pub broadcast_target: SocketAddr,
+    pub broadcast_interval: Duration,
+    /// layout of points streamed to data port, see `Points`
+    pub data_type: u8,
+    pub packet_interval: Duration,
+    pub imu_interval: Duration,
+    pub firmware_version: [u8; 4],
+    /// status code carried by every data packet
+    pub status_code: u32,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        SimulatorConfig {
+            broadcast_code: "0SIMULATOR00001".to_string(),
+            dev_type: DeviceType::Horizon,
+            cmd_addr: SocketAddr::from(([127, 0, 0, 1], 65000)),
+            broadcast_target: SocketAddr::from(([127, 0, 0, 1], 55000)),
+            broadcast_interval: Duration::from_millis(1000),
+            data_type: CartesianPoint::DATA_TYPE,
+            packet_interval: Duration::from_millis(10),
+            imu_interval: Duration::from_millis(5),
+            firmware_version: [6, 4, 0, 0],
+            status_code: 0,
+        }
+    }
+}
+
+/// Host connected through handshake, data is streamed to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Host {
+    /// address handshake came from, replies and messages are sent to it
+    cmd_addr: SocketAddr,
+    ip: Ipv4Addr,
+    data_port: u16,
+    imu_port: u16,
+}
+
+/// Everything the simulated lidar remembers, lost on reboot except settings
+#[derive(Debug)]
+struct Device {
+    host: Option<Host>,
+    sampling: bool,
+    /// work state reported in heartbeat response
+    work_state: u8,
+    coordinate_type: CoordinateType,
+    return_mode: ReturnMode,
+    extrinsics: Extrinsics,
+    /// high sensitivity, repetitive scan and slot id kept in flash
+    flash: (bool, bool, u8),
+    rain_fog_suppression: bool,
+    fan: bool,
+    /// 0x00: no IMU data, 0x01: 200 Hz
+    imu_push_frequency: ImuPushFrequency,
+    status_code: u32,
+    /// UTC in nanoseconds pushed by host and when, timestamps are GPS synchronized from then on
+    utc_sync: Option<(u64, Instant)>,
+    /// silent until then, as if rebooting
+    down_until: Option<Instant>,
+    /// silent until set back, as if cable were unplugged
+    offline: bool,
+    dropped_replies: u32,
+    corrupted_replies: u32,
+    commands: u64,
+}
+
+impl Device {
+    fn is_down(&mut self, now: Instant) -> bool {
+        if self.down_until.is_some_and(|until| now >= until) {
+            info!("simulated lidar is up again");
+            self.down_until = None;
+        }
+        self.offline || self.down_until.is_some()
+    }
+}
+
+/// Simulated SDK1 lidar on loopback, broadcasting, answering commands and streaming data
+pub struct Simulator {
+    device: Arc<Mutex<Device>>,
+    cmd_socket: UdpSocket,
+    cmd_addr: SocketAddr,
+    handle: WorkerHandle,
+    term_sender: mpsc::Sender<()>,
+}
+
+impl Simulator {
+    /// bind sockets and start simulating
+    pub fn launch(config: SimulatorConfig) -> Result<Self> {
+        let cmd_socket = UdpSocket::bind(config.cmd_addr)?;
+        let data_socket = UdpSocket::bind(SocketAddr::new(config.cmd_addr.ip(), 0))?;
+        let cmd_addr = cmd_socket.local_addr()?;
+
+        let device = Arc::new(Mutex::new(Device {
+            host: None,
+            sampling: false,
+            work_state: 0x01,
+            coordinate_type: CoordinateType::Cartesian,
+            return_mode: ReturnMode::SingleFirst,
+            extrinsics: Extrinsics::default(),
+            flash: (true, false, 0x01),
+            rain_fog_suppression: false,
+            fan: true,
+            imu_push_frequency: ImuPushFrequency::Hz200,
+            status_code: config.status_code,
+            utc_sync: None,
+            down_until: None,
+            offline: false,
+            dropped_replies: 0,
+            corrupted_replies: 0,
+            commands: 0,
+        }));
+        let duplicated_device = device.clone();
+        let duplicated_cmd_socket = cmd_socket.try_clone()?;
+        let (tx, rx) = mpsc::channel();
+
+        let handle: WorkerHandle = thread::spawn(move || {
+            let mut simulation = Simulation {
+                config,
+                cmd_socket,
+                data_socket,
+                device: duplicated_device,
+                started: Instant::now(),
+                point_index: 0,
+            };
+            simulation.run(&rx)
+        });
+        info!("simulated lidar listening on {} ✅", cmd_addr);
+
+        Ok(Simulator {
+            device,
+            cmd_socket: duplicated_cmd_socket,
+            cmd_addr,
+            handle,
+            term_sender: tx,
+        })
+    }
+
+    /// address of command socket, driver sends commands here
+    pub fn cmd_addr(&self) -> SocketAddr {
+        self.cmd_addr
+    }
+
+    /// true if a host handshaked and did not disconnect since
+    pub fn is_connected(&self) -> bool {
+        self.device.lock().unwrap().host.is_some()
+    }
+
+    pub fn is_sampling(&self) -> bool {
+        self.device.lock().unwrap().sampling
+    }
+
+    pub fn return_mode(&self) -> ReturnMode {
+        self.device.lock().unwrap().return_mode
+    }
+
+    pub fn coordinate_type(&self) -> CoordinateType {
+        self.device.lock().unwrap().coordinate_type
+    }
+
+    pub fn extrinsics(&self) -> Extrinsics {
+        self.device.lock().unwrap().extrinsics
+    }
+
+    pub fn rain_fog_suppression(&self) -> bool {
+        self.device.lock().unwrap().rain_fog_suppression
+    }
+
+    /// number of commands answered or dropped so far
+    pub fn commands(&self) -> u64 {
+        self.device.lock().unwrap().commands
+    }
+
+    /// status code carried by data packets and heartbeat responses from now on
+    pub fn set_status_code(&self, status_code: u32) {
+        self.device.lock().unwrap().status_code = status_code;
+    }
+
+    /// silently drop the next `count` replies
+    pub fn drop_replies(&self, count: u32) {
+        self.device.lock().unwrap().dropped_replies = count;
+    }
+
+    /// send the next `count` replies with a broken CRC32
+    pub fn corrupt_replies(&self, count: u32) {
+        self.device.lock().unwrap().corrupted_replies = count;
+    }
+
+    /// stop sending and answering anything, like an unplugged cable, until set back
+    pub fn set_offline(&self, offline: bool) {
+        self.device.lock().unwrap().offline = offline;
+    }
+
+    /// forget connected host and stop sampling, staying silent for `downtime`
+    pub fn reboot(&self, downtime: Duration) {
+        let mut device = self.device.lock().unwrap();
+        info!("simulated lidar rebooting for {:?}", downtime);
+        device.host = None;
+        device.sampling = false;
+        device.utc_sync = None;
+        device.down_until = Some(Instant::now() + downtime);
+    }
+
+    /// push an abnormal status message to the connected host
+    pub fn push_abnormal_status(&self, status_code: u32) -> Result<()> {
+        let Some(host) = self.device.lock().unwrap().host else {
+            return Err(LivoxError::Closed(
+                "simulated lidar is not connected".to_string(),
+            ));
+        };
+        let frame = ControlFrame::msg(0, &AbnormalStatusMsg::new(status_code)).serialize()?;
+        send(&self.cmd_socket, &frame, host.cmd_addr);
+        Ok(())
+    }
+
+    /// stop simulating and wait for it to exit
+    pub fn terminate(self) -> Result<()> {
+        self.term_sender.send(()).map_err(|e| {
+            LivoxError::Closed(format!("failed to send sig_term to simulator: {}", e))
+        })?;
+        self.handle
+            .join()
+            .map_err(|_| LivoxError::WorkerPanicked("simulator"))?
+    }
+}
+
+/// Upper bound of how long the simulation sleeps, bounds how long it takes to notice sig_term
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// State owned by the simulation thread
+struct Simulation {
+    config: SimulatorConfig,
+    cmd_socket: UdpSocket,
+    data_socket: UdpSocket,
+    device: Arc<Mutex<Device>>,
+    started: Instant,
+    point_index: u64,
+}
+
+impl Simulation {
+    fn run(&mut self, term_receiver: &mpsc::Receiver<()>) -> Result<()> {
+        let mut next_broadcast = Instant::now();
+        let mut next_packet = Instant::now();
+        let mut next_imu = Instant::now();
+        let mut buffer = [0; 1024];
+
+        loop {
+            if term_receiver.try_recv().is_ok() {
+                info!("received sig_term, simulator exiting...");
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= next_broadcast {
+                self.broadcast()?;
+                next_broadcast = now + self.config.broadcast_interval;
+            }
+            if now >= next_packet {
+                self.stream_points()?;
+                next_packet = now + self.config.packet_interval;
+            }
+            if now >= next_imu {
+                self.stream_imu()?;
+                next_imu = now + self.config.imu_interval;
+            }
+
+            let next_due = next_broadcast.min(next_packet).min(next_imu);
+            let timeout = next_due
+                .saturating_duration_since(Instant::now())
+                .clamp(Duration::from_millis(1), POLL_INTERVAL);
+            self.cmd_socket.set_read_timeout(Some(timeout))?;
+            match self.cmd_socket.recv_from(&mut buffer) {
+                Ok((size, src)) => self.handle_command(&buffer[..size], src)?,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) => {}
+                Err(e) => {
+                    if log_enabled!(log::Level::Warn) {
+                        warn!("error occurred when receiving command in simulator: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// broadcast until a host connects
+    fn broadcast(&self) -> Result<()> {
+        {
+            let mut device = self.device.lock().unwrap();
+            if device.is_down(Instant::now()) || device.host.is_some() {
+                return Ok(());
+            }
+        }
+        let broadcast = Broadcast::new(&self.config.broadcast_code, self.config.dev_type);
+        let frame = ControlFrame::msg(0, &broadcast).serialize()?;
+        send(&self.cmd_socket, &frame, self.config.broadcast_target);
+        Ok(())
+    }
+
+    /// host to stream to and header of the next packet, None if not streaming
+    fn streaming(&self, data_type: u8) -> Option<(Host, DataFrame)> {
+        let mut device = self.device.lock().unwrap();
+        if device.is_down(Instant::now()) || !device.sampling {
+            return None;
+        }
+        let host = device.host?;
+        let (timestamp_type, timestamp) = match device.utc_sync {
+            Some((utc, synced)) => {
+                let time = UNIX_EPOCH + Duration::from_nanos(utc) + synced.elapsed();
+                (0x03, encode_utc(time).ok()?)
+            }
+            None => (0x00, self.started.elapsed().as_nanos() as u64),
+        };
+        Some((
+            host,
+            DataFrame {
+                version: 5,
+                // applied right away, lidar would only report it after reboot
+                slot_id: device.flash.2,
+                lidar_id: 1,
+                reserved: 0,
+                status_code: device.status_code,
+                timestamp_type,
+                data_type,
+                timestamp,
+            },
+        ))
+    }
+
+    fn stream_points(&mut self) -> Result<()> {
+        let Some((host, header)) = self.streaming(self.config.data_type) else {
+            return Ok(());
+        };
+        let count = points_per_packet(header.data_type).ok_or_else(|| {
+            LivoxError::InvalidArgument(format!(
+                "Simulator cannot stream point data type: {}",
+                header.data_type
+            ))
+        })?;
+        let transform = Transform::from(self.device.lock().unwrap().extrinsics);
+        let datagram = synthetic_points(&header, self.point_index, count, &transform)?;
+        self.point_index += count as u64;
+        send(
+            &self.data_socket,
+            &datagram,
+            SocketAddr::from((host.ip, host.data_port)),
+        );
+        Ok(())
+    }
+
+    fn stream_imu(&self) -> Result<()> {
+        let Some((host, header)) = self.streaming(ImuPoint::DATA_TYPE) else {
+            return Ok(());
+        };
+        if self.device.lock().unwrap().imu_push_frequency == ImuPushFrequency::Off {
+            return Ok(());
+        }
+        let sample = ImuPoint {
+            gyro_x: 0.0,
+            gyro_y: 0.0,
+            gyro_z: 0.01,
+            acc_x: 0.0,
+            acc_y: 0.0,
+            acc_z: 1.0,
+        };
+        let datagram = serialize_data(&header, &[sample])?;
+        send(
+            &self.data_socket,
+            &datagram,
+            SocketAddr::from((host.ip, host.imu_port)),
+        );
+        Ok(())
+    }
+
+    /// answer a command the way lidar does, ignoring everything but handshake until connected
+    fn handle_command(&mut self, datagram: &[u8], src: SocketAddr) -> Result<()> {
+        let (seq_num, request) = match ControlFrame::deserialize(datagram) {
+            Ok(frame) => frame,
+            Err(e) => {
+                if log_enabled!(log::Level::Warn) {
+                    warn!("simulator dropped command: {}", e);
+                }
+                return Ok(());
+            }
+        };
+        let cmd = request.cmd();
+
+        let mut device = self.device.lock().unwrap();
+        if device.is_down(Instant::now()) {
+            return Ok(());
+        }
+        if device.host.is_none() && !matches!(request, Request::Handshake(_)) {
+            if log_enabled!(log::Level::Debug) {
+                debug!("simulator not connected, ignored {:?}", cmd);
+            }
+            return Ok(());
+        }
+        device.commands += 1;
+        if log_enabled!(log::Level::Debug) {
+            debug!("simulator received {:?}, seq: {}", request, seq_num);
+        }
+
+        let ok = CommonResp { ret_code: 0 };
+        // a simulated hub has a single lidar behind it, sharing its broadcast code and settings
+        let is_hub = self.config.dev_type == DeviceType::Hub;
+        let code = encode_broadcast_code(&self.config.broadcast_code);
+        let response = match request {
+            Request::Handshake(req) => {
+                device.host = Some(Host {
+                    cmd_addr: src,
+                    ip: Ipv4Addr::from(req.user_ip),
+                    data_port: req.data_port,
+                    imu_port: req.imu_port,
+                });
+                info!("simulated lidar connected to {:?}", device.host);
+                Response::Handshake(ok)
+            }
+            Request::DeviceInfo(_) => Response::DeviceInfo(DeviceInfoResp {
+                ret_code: 0,
+                version: self.config.firmware_version,
+            }),
+            Request::Heartbeat(_) => Response::Heartbeat(HeartbeatResp {
+                ret_code: 0,
+                work_state: device.work_state,
+                feature_msg: 0,
+                ack_msg: device.status_code,
+            }),
+            Request::SampleCtrl(req) => {
+                device.sampling = req.sample_ctrl == 0x01;
+                Response::SampleCtrl(ok)
+            }
+            Request::ChangeCoordinate(req) => {
+                device.coordinate_type = req.coordinate_type;
+                Response::ChangeCoordinate(ok)
+            }
+            Request::Disconnect(_) => {
+                device.host = None;
+                device.sampling = false;
+                Response::Disconnect(ok)
+            }
+            // address of a simulated lidar is decided by whoever launches it
+            Request::IpConfig(_) => Response::IpConfig(CommonResp { ret_code: 0x01 }),
+            Request::IpInfo(_) => {
+                let ip_addr = match self.config.cmd_addr.ip() {
+                    IpAddr::V4(ip) => ip.octets(),
+                    IpAddr::V6(_) => [0; 4],
+                };
+                Response::IpInfo(IpInfoResp {
+                    ret_code: 0,
+                    ip_mode: IpMode::Static,
+                    ip_addr,
+                    net_mask: [255, 255, 255, 0],
+                    gw_addr: [ip_addr[0], ip_addr[1], ip_addr[2], 1],
+                })
+            }
+            // reboot after the given timeout in milliseconds, taking a second to come back
+            Request::Reboot(req) => {
+                device.host = None;
+                device.sampling = false;
+                device.utc_sync = None;
+                device.down_until =
+                    Some(Instant::now() + Duration::from_millis(u64::from(req.timeout) + 1000));
+                Response::Reboot(ok)
+            }
+            Request::WriteFlash(req) => {
+                device.flash = (req.high_sensitivity, req.repetitive_scan, req.slot_id);
+                Response::WriteFlash(WriteFlashResp {
+                    ret_code: 0,
+                    error_key: 0,
+                    error_code: 0,
+                })
+            }
+            Request::ReadFlash(_) => {
+                let (high_sensitivity, repetitive_scan, slot_id) = device.flash;
+                Response::ReadFlash(ReadFlashResp {
+                    ret_code: 0,
+                    error_key: 0,
+                    error_code: 0,
+                    high_sensitivity,
+                    repetitive_scan,
+                    slot_id,
+                })
+            }
+            Request::ModeSwitch(req) => {
+                device.work_state = req.mode.into();
+                Response::ModeSwitch(ok)
+            }
+            Request::WriteOuterParameters(req) => {
+                device.extrinsics = Extrinsics {
+                    roll: req.roll,
+                    pitch: req.pitch,
+                    yaw: req.yaw,
+                    x: req.x,
+                    y: req.y,
+                    z: req.z,
+                };
+                Response::WriteOuterParameters(ok)
+            }
+            Request::ReadOuterParameters(_) => {
+                let Extrinsics {
+                    roll,
+                    pitch,
+                    yaw,
+                    x,
+                    y,
+                    z,
+                } = device.extrinsics;
+                Response::ReadOuterParameters(ReadOuterParametersResp {
+                    ret_code: 0,
+                    roll,
+                    pitch,
+                    yaw,
+                    x,
+                    y,
+                    z,
+                })
+            }
+            Request::SetReturnMode(req) => {
+                device.return_mode = req.mode;
+                Response::SetReturnMode(ok)
+            }
+            Request::GetReturnMode(_) => Response::GetReturnMode(GetReturnModeResp {
+                ret_code: 0,
+                mode: device.return_mode,
+            }),
+            Request::RainFogSuppression(req) => {
+                device.rain_fog_suppression = req.state != 0;
+                Response::RainFogSuppression(ok)
+            }
+            Request::FanControl(req) => {
+                device.fan = req.state != 0;
+                Response::FanControl(ok)
+            }
+            Request::GetFanState(_) => Response::GetFanState(GetFanStateResp {
+                ret_code: 0,
+                state: device.fan.into(),
+            }),
+            Request::SetImuPushFrequency(req) => {
+                device.imu_push_frequency = req.frequency;
+                Response::SetImuPushFrequency(ok)
+            }
+            Request::GetImuPushFrequency(_) => {
+                Response::GetImuPushFrequency(GetImuPushFrequencyResp {
+                    ret_code: 0,
+                    frequency: device.imu_push_frequency,
+                })
+            }
+            Request::UpdateUtcSyncTime(req) => {
+                let timestamp = u64::from_le_bytes([
+                    req.year,
+                    req.month,
+                    req.day,
+                    req.hour,
+                    req.microsecond as u8,
+                    (req.microsecond >> 8) as u8,
+                    (req.microsecond >> 16) as u8,
+                    (req.microsecond >> 24) as u8,
+                ]);
+                match decode_utc(timestamp) {
+                    Ok(utc) => {
+                        device.utc_sync = Some((utc, Instant::now()));
+                        Response::UpdateUtcSyncTime(ok)
+                    }
+                    Err(_) => Response::UpdateUtcSyncTime(CommonResp { ret_code: 0x01 }),
+                }
+            }
+            Request::HubQueryLidarInfo(_) if is_hub => {
+                Response::HubQueryLidarInfo(HubQueryLidarInfoResp {
+                    ret_code: 0,
+                    lidars: vec![HubLidarInfo {
+                        broadcast_code: code,
+                        dev_type: DeviceType::Horizon,
+                        version: self.config.firmware_version,
+                        slot: device.flash.2,
+                        id: 1,
+                    }]
+                    .into(),
+                })
+            }
+            Request::HubFanControl(req) if is_hub => {
+                let lidars = req
+                    .lidars
+                    .0
+                    .iter()
+                    .map(|lidar| {
+                        let known = lidar.broadcast_code == code;
+                        if known {
+                            device.fan = lidar.value != 0;
+                        }
+                        HubLidarResult {
+                            ret_code: (!known).into(),
+                            broadcast_code: lidar.broadcast_code,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                Response::HubFanControl(HubLidarResultsResp {
+                    ret_code: 0,
+                    lidars: lidars.into(),
+                })
+            }
+            Request::HubGetFanState(req) if is_hub => {
+                let lidars = req
+                    .broadcast_codes
+                    .0
+                    .iter()
+                    .map(|broadcast_code| {
+                        let known = *broadcast_code == code;
+                        HubLidarValueResult {
+                            ret_code: (!known).into(),
+                            broadcast_code: *broadcast_code,
+                            value: (known && device.fan).into(),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                Response::HubGetFanState(HubLidarValuesResp {
+                    ret_code: 0,
+                    lidars: lidars.into(),
+                })
+            }
+            // a lidar leaves hub commands unanswered
+            _ => {
+                if log_enabled!(log::Level::Debug) {
+                    debug!("simulator is not a hub, ignored {:?}", cmd);
+                }
+                return Ok(());
+            }
+        };
+
+        if device.dropped_replies > 0 {
+            device.dropped_replies -= 1;
+            if log_enabled!(log::Level::Debug) {
+                debug!("simulator dropped reply to {:?}", cmd);
+            }
+            return Ok(());
+        }
+        let mut frame = response.serialize(seq_num)?;
+        if device.corrupted_replies > 0 {
+            device.corrupted_replies -= 1;
+            if let Some(last) = frame.last_mut() {
+                *last ^= 0xFF;
+            }
+            if log_enabled!(log::Level::Debug) {
+                debug!("simulator corrupted reply to {:?}", cmd);
+            }
+        }
+        drop(device);
+
+        send(&self.cmd_socket, &frame, src);
+        Ok(())
+    }
+}
+
+/// send datagram, a lost datagram is nothing lidar would care about
+fn send(socket: &UdpSocket, datagram: &[u8], target: SocketAddr) {
+    if let Err(e) = socket.send_to(datagram, target) {
+        if log_enabled!(log::Level::Warn) {
+            warn!(
+                "error occurred when sending to {} in simulator: {}",
+                target, e
+            );
+        }
+    }
+}
+
+/// Direction and range of a synthetic point, sweeping horizontally at a fixed rate
+struct Sample {
+    depth: u32,   // millimeters
+    zenith: u16,  // 0.01 degree
+    azimuth: u16, // 0.01 degree
+    reflectivity: u8,
+}
+
+impl Sample {
+    fn new(index: u64) -> Self {
+        Sample {
+            depth: 10_000 + (index % 100) as u32 * 10,
+            zenith: 8_750 + (index % 50) as u16 * 10,
+            azimuth: ((index * 20) % 36_000) as u16,
+            reflectivity: (index % 256) as u8,
+        }
+    }
+
+    /// point of a return at `depth` as sent by lidar, extrinsics applied, in millimeters
+    fn point(&self, depth: u32, transform: &Transform) -> [f64; 3] {
+        let sensor = spherical_to_cartesian(depth, self.zenith, self.azimuth);
+        transform
+            .apply(sensor.map(|v| v / 1000.0))
+            .map(|v| v * 1000.0)
+    }
+
+    fn cartesian(&self, depth: u32, transform: &Transform) -> (i32, i32, i32) {
+        let [x, y, z] = self.point(depth, transform).map(|v| v.round() as i32);
+        (x, y, z)
+    }
+
+    /// depth, zenith and azimuth of a return at `depth`, extrinsics applied
+    fn spherical(&self, depth: u32, transform: &Transform) -> (u32, u16, u16) {
+        cartesian_to_spherical(self.point(depth, transform))
+    }
+}
+
+/// data packet of `count` synthetic points starting from `first`, in layout of `header.data_type`,
+/// transformed by extrinsics the way lidar does; further returns of a spherical point
+/// share the direction of the first one
+fn synthetic_points(
+    header: &DataFrame,
+    first: u64,
+    count: usize,
+    transform: &Transform,
+) -> Result<Vec<u8>> {
+    // further returns of the same direction are one meter apart
+    const RETURN_GAP: u32 = 1_000;
+    let samples: Vec<Sample> = (first..first + count as u64).map(Sample::new).collect();
+
+    match header.data_type {
+        StandardCartesianPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (x, y, z) = s.cartesian(s.depth, transform);
+                    StandardCartesianPoint {
+                        x,
+                        y,
+                        z,
+                        reflectivity: s.reflectivity,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        StandardSphericalPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (depth, zenith, azimuth) = s.spherical(s.depth, transform);
+                    StandardSphericalPoint {
+                        depth,
+                        zenith,
+                        azimuth,
+                        reflectivity: s.reflectivity,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        CartesianPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (x, y, z) = s.cartesian(s.depth, transform);
+                    CartesianPoint {
+                        x,
+                        y,
+                        z,
+                        reflectivity: s.reflectivity,
+                        tag: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        SphericalPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (depth, zenith, azimuth) = s.spherical(s.depth, transform);
+                    SphericalPoint {
+                        depth,
+                        zenith,
+                        azimuth,
+                        reflectivity: s.reflectivity,
+                        tag: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        DualCartesianPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (x1, y1, z1) = s.cartesian(s.depth, transform);
+                    let (x2, y2, z2) = s.cartesian(s.depth + RETURN_GAP, transform);
+                    DualCartesianPoint {
+                        x1,
+                        y1,
+                        z1,
+                        reflectivity1: s.reflectivity,
+                        tag1: 0,
+                        x2,
+                        y2,
+                        z2,
+                        reflectivity2: s.reflectivity / 2,
+                        tag2: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        DualSphericalPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (depth1, zenith, azimuth) = s.spherical(s.depth, transform);
+                    let (depth2, _, _) = s.spherical(s.depth + RETURN_GAP, transform);
+                    DualSphericalPoint {
+                        zenith,
+                        azimuth,
+                        depth1,
+                        reflectivity1: s.reflectivity,
+                        tag1: 0,
+                        depth2,
+                        reflectivity2: s.reflectivity / 2,
+                        tag2: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        TripleCartesianPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (x1, y1, z1) = s.cartesian(s.depth, transform);
+                    let (x2, y2, z2) = s.cartesian(s.depth + RETURN_GAP, transform);
+                    let (x3, y3, z3) = s.cartesian(s.depth + 2 * RETURN_GAP, transform);
+                    TripleCartesianPoint {
+                        x1,
+                        y1,
+                        z1,
+                        reflectivity1: s.reflectivity,
+                        tag1: 0,
+                        x2,
+                        y2,
+                        z2,
+                        reflectivity2: s.reflectivity / 2,
+                        tag2: 0,
+                        x3,
+                        y3,
+                        z3,
+                        reflectivity3: s.reflectivity / 4,
+                        tag3: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        TripleSphericalPoint::DATA_TYPE => {
+            let points: Vec<_> = samples
+                .iter()
+                .map(|s| {
+                    let (depth1, zenith, azimuth) = s.spherical(s.depth, transform);
+                    let (depth2, _, _) = s.spherical(s.depth + RETURN_GAP, transform);
+                    let (depth3, _, _) = s.spherical(s.depth + 2 * RETURN_GAP, transform);
+                    TripleSphericalPoint {
+                        zenith,
+                        azimuth,
+                        depth1,
+                        reflectivity1: s.reflectivity,
+                        tag1: 0,
+                        depth2,
+                        reflectivity2: s.reflectivity / 2,
+                        tag2: 0,
+                        depth3,
+                        reflectivity3: s.reflectivity / 4,
+                        tag3: 0,
+                    }
+                })
+                .collect();
+            serialize_data(header, &points)
+        }
+        _ => Err(LivoxError::InvalidArgument(format!(
+            "Simulator cannot stream point data type: {}",
+            header.data_type
+        ))),
+    }
+}
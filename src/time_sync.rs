@@ -0,0 +1,411 @@
+use crate::error::{LivoxError, Result};
+use crate::lidar_frame::frames::{DataFrame, UpdateUtcSyncTime};
+use crate::receiver::PointReceiver;
+use log::{info, log_enabled, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Source lidar synchronized its clock to, as given by `timestamp_type` of every packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimestampType {
+    /// nanoseconds since lidar powered on
+    NoSync,
+    /// nanoseconds since epoch of the PTP master
+    Ptp,
+    /// UTC set by `UpdateUtcSyncTime`, aligned on PPS of a GPS receiver
+    Gps,
+    /// nanoseconds, aligned on PPS
+    Pps,
+    Unknown(u8),
+}
+
+impl From<u8> for TimestampType {
+    fn from(timestamp_type: u8) -> Self {
+        match timestamp_type {
+            0x00 => TimestampType::NoSync,
+            0x01 => TimestampType::Ptp,
+            0x03 => TimestampType::Gps,
+            0x04 => TimestampType::Pps,
+            _ => TimestampType::Unknown(timestamp_type),
+        }
+    }
+}
+
+/// days since 1970-01-01 of a date of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// year, month and day of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// UTC sync request carrying `time`, lidar only accepts years 2000 to 2255
+pub fn utc_sync_req(time: SystemTime) -> Result<UpdateUtcSyncTime> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).map_err(|_| {
+        LivoxError::InvalidArgument("Cannot sync lidar to a time before 1970".to_string())
+    })?;
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let year = u8::try_from(year - 2000).map_err(|_| {
+        LivoxError::InvalidArgument(format!(
+            "Cannot sync lidar to year {}, out of 2000 to 2255",
+            year
+        ))
+    })?;
+    let hour = (seconds % 86_400 / 3600) as u8;
+    let microsecond = (seconds % 3600 * 1_000_000) as u32 + since_epoch.subsec_micros();
+    Ok(UpdateUtcSyncTime::new(
+        year,
+        month as u8,
+        day as u8,
+        hour,
+        microsecond,
+    ))
+}
+
+/// UTC timestamp of `time` the way lidar sends it, see [`decode_utc`]
+pub(crate) fn encode_utc(time: SystemTime) -> Result<u64> {
+    let req = utc_sync_req(time)?;
+    let mut timestamp = [req.year, req.month, req.day, req.hour, 0, 0, 0, 0];
+    timestamp[4..].copy_from_slice(&req.microsecond.to_le_bytes());
+    Ok(u64::from_le_bytes(timestamp))
+}
+
+/// nanoseconds since 1970 of a UTC timestamp, laid out as year since 2000, month, day,
+/// hour and microsecond within hour in little endian
+pub(crate) fn decode_utc(timestamp: u64) -> Result<u64> {
+    let [year, month, day, hour, microsecond @ ..] = timestamp.to_le_bytes();
+    let microsecond = u32::from_le_bytes(microsecond);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 {
+        return Err(LivoxError::Decode(format!(
+            "Invalid UTC timestamp: {}-{}-{} {}h",
+            2000 + u32::from(year),
+            month,
+            day,
+            hour
+        )));
+    }
+    let days = days_from_civil(2000 + i64::from(year), month.into(), day.into()) as u64;
+    let seconds = days * 86_400 + u64::from(hour) * 3600;
+    Ok(seconds * 1_000_000_000 + u64::from(microsecond) * 1000)
+}
+
+impl DataFrame {
+    /// timestamp in nanoseconds whatever format lidar sent it in,
+    /// since 1970 for GPS synchronized lidar
+    pub fn lidar_time(&self) -> Result<u64> {
+        match TimestampType::from(self.timestamp_type) {
+            TimestampType::Gps => decode_utc(self.timestamp),
+            TimestampType::Unknown(timestamp_type) => Err(LivoxError::Decode(format!(
+                "Unknown timestamp type: {}",
+                timestamp_type
+            ))),
+            _ => Ok(self.timestamp),
+        }
+    }
+}
+
+/// Relation between lidar clock and host monotonic clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockEstimate {
+    pub timestamp_type: TimestampType,
+    /// lidar time, in nanoseconds, and the host time it was read at
+    pub lidar_reference: u64,
+    pub host_reference: Instant,
+    /// parts per million lidar clock runs faster than host clock, negative if slower
+    pub drift_ppm: f64,
+    /// lidar time covered by packets the estimate is based on
+    pub span: Duration,
+}
+
+impl ClockEstimate {
+    /// host time lidar read `lidar_time`, None if out of range of `Instant`
+    pub fn to_host(&self, lidar_time: u64) -> Option<Instant> {
+        let lidar_elapsed = lidar_time as i128 - self.lidar_reference as i128;
+        let host_elapsed = (lidar_elapsed as f64 / (1.0 + self.drift_ppm * 1e-6)).round();
+        let host_elapsed = Duration::from_nanos(host_elapsed.abs() as u64);
+        if lidar_elapsed >= 0 {
+            self.host_reference.checked_add(host_elapsed)
+        } else {
+            self.host_reference.checked_sub(host_elapsed)
+        }
+    }
+}
+
+/// lidar time covered by a single sample, only the packet least delayed by network is kept
+const BUCKET_SPAN: u64 = 100_000_000;
+/// lidar time covered by samples the estimate is fit on
+const WINDOW: u64 = 10_000_000_000;
+/// packet arriving this long before it was expected means lidar clock jumped forward
+const MAX_JUMP: Duration = Duration::from_millis(100);
+/// drift beyond which lidar is assumed to have adjusted its clock rather than drifted
+const MAX_DRIFT_PPM: f64 = 1000.0;
+
+/// Lidar time of a packet and host time it arrived at, in nanoseconds since `TimeSync` creation
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    bucket: u64,
+    lidar: u64,
+    host: u64,
+}
+
+impl Sample {
+    /// how late packet arrived compared with others, up to a constant
+    fn delay(&self) -> i128 {
+        self.host as i128 - self.lidar as i128
+    }
+}
+
+#[derive(Debug)]
+struct SyncState {
+    timestamp_type: Option<TimestampType>,
+    samples: VecDeque<Sample>,
+    estimate: Option<ClockEstimate>,
+}
+
+/// Estimates offset and drift of lidar clock against host monotonic clock
+/// from arrival time of packets, so that points can be put on the host clock
+/// shared with cameras and other sensors
+#[derive(Debug)]
+pub struct TimeSync {
+    epoch: Instant,
+    state: Mutex<SyncState>,
+}
+
+impl Default for TimeSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSync {
+    pub fn new() -> Self {
+        TimeSync {
+            epoch: Instant::now(),
+            state: Mutex::new(SyncState {
+                timestamp_type: None,
+                samples: VecDeque::new(),
+                estimate: None,
+            }),
+        }
+    }
+
+    /// observe every packet received by `receiver`, stamped with the time it was handed over
+    pub fn attach(self: &Arc<Self>, receiver: &PointReceiver) {
+        let sync = self.clone();
+        receiver.subscribe(move |packet| sync.observe(&packet.header, Instant::now()));
+    }
+
+    /// record that packet with `header` arrived at `received`
+    pub fn observe(&self, header: &DataFrame, received: Instant) {
+        let lidar = match header.lidar_time() {
+            Ok(lidar) => lidar,
+            Err(e) => {
+                if log_enabled!(log::Level::Warn) {
+                    warn!("packet dropped from time sync: {}", e);
+                }
+                return;
+            }
+        };
+        let timestamp_type = TimestampType::from(header.timestamp_type);
+        let sample = Sample {
+            bucket: lidar / BUCKET_SPAN,
+            lidar,
+            host: received.saturating_duration_since(self.epoch).as_nanos() as u64,
+        };
+        let mut state = self.state.lock().unwrap();
+
+        // lidar clock was synchronized, lost its sync source, rebooted or jumped
+        let jumped = state.samples.back().is_some_and(|last| lidar < last.lidar)
+            || state.estimate.is_some_and(|estimate| {
+                estimate
+                    .to_host(lidar)
+                    .is_some_and(|expected| received + MAX_JUMP < expected)
+            });
+        if state.timestamp_type != Some(timestamp_type) || jumped {
+            if state.timestamp_type.is_some() {
+                info!(
+                    "lidar clock restarted as {:?}, resetting time sync",
+                    timestamp_type
+                );
+            }
+            state.timestamp_type = Some(timestamp_type);
+            state.samples.clear();
+            state.estimate = None;
+        }
+
+        match state.samples.back_mut() {
+            Some(last) if last.bucket == sample.bucket => {
+                if sample.delay() < last.delay() {
+                    *last = sample;
+                }
+            }
+            _ => state.samples.push_back(sample),
+        }
+        while state
+            .samples
+            .front()
+            .is_some_and(|first| lidar - first.lidar > WINDOW)
+        {
+            state.samples.pop_front();
+        }
+        state.estimate = Some(self.fit(timestamp_type, &state.samples));
+    }
+
+    /// fit drift on samples by least squares, then offset on the least delayed sample
+    fn fit(&self, timestamp_type: TimestampType, samples: &VecDeque<Sample>) -> ClockEstimate {
+        let first = samples[0];
+        let elapsed = |sample: &Sample| {
+            (
+                (sample.lidar - first.lidar) as f64,
+                (sample.host as i128 - first.host as i128) as f64,
+            )
+        };
+        let count = samples.len() as f64;
+        let (mean_lidar, mean_host) = samples.iter().map(elapsed).fold((0.0, 0.0), |sum, e| {
+            (sum.0 + e.0 / count, sum.1 + e.1 / count)
+        });
+        let (covariance, variance) = samples.iter().map(elapsed).fold((0.0, 0.0), |sum, e| {
+            (
+                sum.0 + (e.0 - mean_lidar) * (e.1 - mean_host),
+                sum.1 + (e.0 - mean_lidar) * (e.0 - mean_lidar),
+            )
+        });
+        let drift_ppm = match covariance / variance {
+            rate if variance > 0.0 && rate > 0.0 => (1.0 / rate - 1.0) * 1e6,
+            _ => 0.0,
+        }
+        .clamp(-MAX_DRIFT_PPM, MAX_DRIFT_PPM);
+        let rate = 1.0 / (1.0 + drift_ppm * 1e-6);
+
+        // network only ever delays packets, the earliest arrival is the closest to lidar time
+        let offset = samples
+            .iter()
+            .map(elapsed)
+            .map(|(lidar, host)| host - rate * lidar)
+            .fold(f64::INFINITY, f64::min);
+        let host = self.epoch + Duration::from_nanos(first.host);
+        let host_reference = if offset >= 0.0 {
+            host + Duration::from_nanos(offset as u64)
+        } else {
+            host.checked_sub(Duration::from_nanos(-offset as u64))
+                .unwrap_or(host)
+        };
+        ClockEstimate {
+            timestamp_type,
+            lidar_reference: first.lidar,
+            host_reference,
+            drift_ppm,
+            span: Duration::from_nanos(samples[samples.len() - 1].lidar - first.lidar),
+        }
+    }
+
+    /// current estimate, None until a packet was observed
+    pub fn estimate(&self) -> Option<ClockEstimate> {
+        self.state.lock().unwrap().estimate
+    }
+
+    /// host time lidar read `lidar_time` in its current clock, such as timestamp of a point
+    pub fn to_host(&self, lidar_time: u64) -> Option<Instant> {
+        self.estimate()?.to_host(lidar_time)
+    }
+
+    /// host time packet was stamped by lidar, None if its clock is not the one estimated
+    pub fn host_time(&self, header: &DataFrame) -> Option<Instant> {
+        let estimate = self.estimate()?;
+        if estimate.timestamp_type != TimestampType::from(header.timestamp_type) {
+            return None;
+        }
+        estimate.to_host(header.lidar_time().ok()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn header(timestamp_type: u8, timestamp: u64) -> DataFrame {
+        DataFrame {
+            version: 5,
+            slot_id: 1,
+            lidar_id: 1,
+            reserved: 0,
+            status_code: 0,
+            timestamp_type,
+            data_type: 0,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_utc_timestamp() {
+        // 2024-03-01 13:20:34.567891 UTC
+        let time = UNIX_EPOCH + Duration::from_micros(1_709_299_234_567_891);
+        let req = utc_sync_req(time).unwrap();
+        assert_eq!(
+            (req.year, req.month, req.day, req.hour, req.microsecond),
+            (24, 3, 1, 13, 20 * 60_000_000 + 34_567_891)
+        );
+
+        // same layout in data packets, little endian
+        let header = header(3, encode_utc(time).unwrap());
+        assert_eq!(header.timestamp.to_le_bytes()[..4], [24, 3, 1, 13]);
+        assert_eq!(header.lidar_time().unwrap(), 1_709_299_234_567_891_000);
+        assert!(decode_utc(u64::from_le_bytes([24, 13, 1, 0, 0, 0, 0, 0])).is_err());
+        assert!(utc_sync_req(UNIX_EPOCH).is_err());
+        assert_eq!(
+            civil_from_days(days_from_civil(2100, 2, 28) + 1),
+            (2100, 3, 1)
+        );
+    }
+
+    #[test]
+    fn test_clock_estimate() {
+        let sync = TimeSync::new();
+        let epoch = sync.epoch;
+        // lidar clock 50 ppm fast and powered on 1 s before host, packets delayed
+        // by 0.2 to 1 ms, every second packet by 5 ms more
+        for index in 0..2000u64 {
+            let host = Duration::from_millis(index * 5);
+            let lidar = 1_000_000_000 + host.as_nanos() as u64 * 1_000_050 / 1_000_000;
+            let delay = Duration::from_micros(200 + index * 7919 % 800 + index % 2 * 5000);
+            sync.observe(&header(0, lidar), epoch + host + delay);
+        }
+        let estimate = sync.estimate().unwrap();
+        assert_eq!(estimate.timestamp_type, TimestampType::NoSync);
+        assert!((estimate.drift_ppm - 50.0).abs() < 5.0, "{:?}", estimate);
+        let host = sync.to_host(1_000_000_000 + 9_000_450_000).unwrap();
+        let error = host.duration_since(epoch).as_secs_f64() - 9.0;
+        assert!((0.0..0.0005).contains(&error), "{}", error);
+
+        // synchronized, lidar clock changed its meaning
+        assert_eq!(sync.host_time(&header(1, 0)), None);
+        sync.observe(&header(1, 500), epoch + Duration::from_secs(11));
+        let host = sync.host_time(&header(1, 1_000_500)).unwrap();
+        assert_eq!(host, epoch + Duration::from_millis(11_001));
+    }
+}
@@ -0,0 +1,249 @@
+use crate::cloud::CloudPoint;
+use crate::lidar_frame::cfg::Extrinsics;
+use crate::lidar_frame::frames::{CartesianPoint, SphericalPoint};
+
+/// cartesian coordinates in millimeters of a return at `depth` millimeters,
+/// zenith measured from z axis and azimuth from x axis towards y axis, both in 0.01 degree
+pub fn spherical_to_cartesian(depth: u32, zenith: u16, azimuth: u16) -> [f64; 3] {
+    let zenith = (f64::from(zenith) / 100.0).to_radians();
+    let azimuth = (f64::from(azimuth) / 100.0).to_radians();
+    let depth = f64::from(depth);
+    [
+        depth * zenith.sin() * azimuth.cos(),
+        depth * zenith.sin() * azimuth.sin(),
+        depth * zenith.cos(),
+    ]
+}
+
+/// depth in millimeters, zenith and azimuth in 0.01 degree of a point given in millimeters,
+/// azimuth in [0, 360) degrees
+pub fn cartesian_to_spherical([x, y, z]: [f64; 3]) -> (u32, u16, u16) {
+    let depth = (x * x + y * y + z * z).sqrt();
+    if depth == 0.0 {
+        return (0, 0, 0);
+    }
+    let zenith = (z / depth).clamp(-1.0, 1.0).acos().to_degrees();
+    let azimuth = y.atan2(x).to_degrees().rem_euclid(360.0);
+    (
+        depth.round() as u32,
+        (zenith * 100.0).round() as u16,
+        ((azimuth * 100.0).round() as u16) % 36_000,
+    )
+}
+
+impl SphericalPoint {
+    pub fn to_cartesian(&self) -> CartesianPoint {
+        let [x, y, z] = spherical_to_cartesian(self.depth, self.zenith, self.azimuth);
+        CartesianPoint {
+            x: x.round() as i32,
+            y: y.round() as i32,
+            z: z.round() as i32,
+            reflectivity: self.reflectivity,
+            tag: self.tag,
+        }
+    }
+}
+
+impl CartesianPoint {
+    pub fn to_spherical(&self) -> SphericalPoint {
+        let (depth, zenith, azimuth) =
+            cartesian_to_spherical([self.x, self.y, self.z].map(f64::from));
+        SphericalPoint {
+            depth,
+            zenith,
+            azimuth,
+            reflectivity: self.reflectivity,
+            tag: self.tag,
+        }
+    }
+}
+
+/// Rigid transform applying rotation then translation, built from the same
+/// roll/pitch/yaw/x/y/z lidar takes as extrinsic parameters, so that the host can
+/// do what `WriteOuterParameters` would make lidar do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// rotation about z by yaw, then y by pitch, then x by roll, in that matrix order
+    rotation: [[f64; 3]; 3],
+    /// meters
+    translation: [f64; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Extrinsics> for Transform {
+    fn from(e: Extrinsics) -> Self {
+        Transform::from_euler(
+            e.roll.into(),
+            e.pitch.into(),
+            e.yaw.into(),
+            [e.x, e.y, e.z].map(|v| f64::from(v) / 1000.0),
+        )
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+        translation: [0.0; 3],
+    };
+
+    /// roll, pitch and yaw in degrees about x, y and z axes, translation in meters
+    pub fn from_euler(roll: f64, pitch: f64, yaw: f64, translation: [f64; 3]) -> Self {
+        let (sr, cr) = roll.to_radians().sin_cos();
+        let (sp, cp) = pitch.to_radians().sin_cos();
+        let (sy, cy) = yaw.to_radians().sin_cos();
+        Transform {
+            rotation: [
+                [cp * cy, sr * sp * cy - cr * sy, cr * sp * cy + sr * sy],
+                [cp * sy, sr * sp * sy + cr * cy, cr * sp * sy - sr * cy],
+                [-sp, sr * cp, cr * cp],
+            ],
+            translation,
+        }
+    }
+
+    pub fn rotation(&self) -> [[f64; 3]; 3] {
+        self.rotation
+    }
+
+    pub fn translation(&self) -> [f64; 3] {
+        self.translation
+    }
+
+    /// transform undoing this one
+    pub fn inverse(&self) -> Self {
+        let r = &self.rotation;
+        let rotation = [0, 1, 2].map(|i| [r[0][i], r[1][i], r[2][i]]);
+        let t = self.translation;
+        let translation = rotation.map(|row| -(row[0] * t[0] + row[1] * t[1] + row[2] * t[2]));
+        Transform {
+            rotation,
+            translation,
+        }
+    }
+
+    /// transform applying this one, then `next`, such as lidar to vehicle then vehicle to map
+    pub fn then(&self, next: &Transform) -> Self {
+        let (a, b) = (&next.rotation, &self.rotation);
+        let rotation =
+            [0, 1, 2].map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()));
+        Transform {
+            rotation,
+            translation: next.apply(self.translation),
+        }
+    }
+
+    /// transform a point given in meters
+    pub fn apply(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+        let r = &self.rotation;
+        let t = &self.translation;
+        [
+            r[0][0] * x + r[0][1] * y + r[0][2] * z + t[0],
+            r[1][0] * x + r[1][1] * y + r[1][2] * z + t[1],
+            r[2][0] * x + r[2][1] * y + r[2][2] * z + t[2],
+        ]
+    }
+
+    /// transform points in place
+    pub fn apply_cloud(&self, points: &mut [CloudPoint]) {
+        for point in points {
+            let [x, y, z] = self.apply([point.x, point.y, point.z].map(f64::from));
+            (point.x, point.y, point.z) = (x as f32, y as f32, z as f32);
+        }
+    }
+
+    /// transform points in place, rounded to the millimeter as lidar would send them
+    pub fn apply_cartesian(&self, points: &mut [CartesianPoint]) {
+        // points are in millimeters, so is translation here
+        let t = self.translation.map(|v| v * 1000.0);
+        let r = &self.rotation;
+        for point in points {
+            let [x, y, z] = [point.x, point.y, point.z].map(f64::from);
+            point.x = (r[0][0] * x + r[0][1] * y + r[0][2] * z + t[0]).round() as i32;
+            point.y = (r[1][0] * x + r[1][1] * y + r[1][2] * z + t[1]).round() as i32;
+            point.z = (r[2][0] * x + r[2][1] * y + r[2][2] * z + t[2]).round() as i32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn assert_near(a: [f64; 3], b: [f64; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_extrinsic_semantics() {
+        // lidar rotates by roll about x first, then pitch about y, then yaw about z,
+        // and translates last
+        let transform = Transform::from(Extrinsics {
+            roll: 90.0,
+            yaw: 90.0,
+            x: 1000,
+            ..Extrinsics::default()
+        });
+        assert_near(transform.apply([0.0, 1.0, 0.0]), [1.0, 0.0, 1.0]);
+        assert_near(transform.apply([0.0, 0.0, 1.0]), [2.0, 0.0, 0.0]);
+        let pitched = Transform::from_euler(0.0, 90.0, 0.0, [0.0; 3]);
+        assert_near(pitched.apply([1.0, 0.0, 0.0]), [0.0, 0.0, -1.0]);
+
+        let other = Transform::from_euler(10.0, -20.0, 30.0, [0.5, -1.0, 2.0]);
+        let point = [1.0, 2.0, 3.0];
+        assert_near(
+            transform.then(&other).apply(point),
+            other.apply(transform.apply(point)),
+        );
+        assert_near(other.inverse().apply(other.apply(point)), point);
+
+        let mut points = [CartesianPoint {
+            x: 0,
+            y: 1000,
+            z: 0,
+            reflectivity: 7,
+            tag: 0,
+        }];
+        transform.apply_cartesian(&mut points);
+        assert_eq!((points[0].x, points[0].y, points[0].z), (1000, 0, 1000));
+        assert_eq!(points[0].reflectivity, 7);
+    }
+
+    #[test]
+    fn test_spherical_conversion() {
+        let point = SphericalPoint {
+            depth: 10_000,
+            zenith: 9_000,
+            azimuth: 27_000,
+            reflectivity: 1,
+            tag: 2,
+        };
+        let cartesian = point.to_cartesian();
+        assert_eq!((cartesian.x, cartesian.y, cartesian.z), (0, -10_000, 0));
+        assert_eq!(cartesian.to_spherical(), point);
+
+        let point = SphericalPoint {
+            depth: 12_345,
+            zenith: 8_765,
+            azimuth: 12_345,
+            ..point
+        };
+        let (depth, zenith, azimuth) = cartesian_to_spherical(spherical_to_cartesian(
+            point.depth,
+            point.zenith,
+            point.azimuth,
+        ));
+        assert_eq!((depth, zenith, azimuth), (12_345, 8_765, 12_345));
+    }
+}
@@ -0,0 +1,591 @@
+use livox_lidar_rs::client::LivoxClient;
+use livox_lidar_rs::connection::{ConnectionState, ConnectionSupervisor};
+use livox_lidar_rs::discovery::{discover_on, BroadcastListener, Discover};
+use livox_lidar_rs::error::LivoxError;
+use livox_lidar_rs::event::LidarEvent;
+use livox_lidar_rs::lidar_frame::cfg::{Extrinsics, LidarConfig, SessionConfig};
+use livox_lidar_rs::lidar_frame::frames::{
+    deserialize_data, deserialize_resp, DeviceType, GetCmd, HubFanControlReq, HubGetFanStateReq,
+    HubLidarValue, ImuPushFrequency, ReturnMode, UpdateUtcSyncTime, WriteFlashReq,
+    HUB_QUERY_LIDAR_INFO, SAMPLE_START_REQ,
+};
+use livox_lidar_rs::pcap::{Capture, PcapReader, PcapSource};
+use livox_lidar_rs::receiver::{DatagramSource, ImuReceiver, PointReceiver};
+use livox_lidar_rs::session::{LidarSockets, SessionManager};
+use livox_lidar_rs::simulator::{Simulator, SimulatorConfig};
+use livox_lidar_rs::time_sync::{TimeSync, TimestampType};
+use livox_lidar_rs::transform::Transform;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Driver side sockets bound to free loopback ports, with a config pointing at them
+struct Host {
+    config: LidarConfig,
+    broadcast_socket: UdpSocket,
+    control_socket: UdpSocket,
+    data_socket: UdpSocket,
+    imu_socket: UdpSocket,
+}
+
+fn bind_loopback() -> UdpSocket {
+    UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap()
+}
+
+fn host() -> Host {
+    let broadcast_socket = bind_loopback();
+    let control_socket = bind_loopback();
+    let data_socket = bind_loopback();
+    let imu_socket = bind_loopback();
+    let port = |socket: &UdpSocket| socket.local_addr().unwrap().port();
+
+    let config = LidarConfig {
+        host_ip: Ipv4Addr::LOCALHOST,
+        data_port: port(&data_socket),
+        cmd_port: port(&control_socket),
+        imu_port: port(&imu_socket),
+        broadcast_port: port(&broadcast_socket),
+        heartbeat_interval_ms: 100,
+        command_timeout_ms: 100,
+        command_retries: 2,
+        ..LidarConfig::default()
+    };
+    Host {
+        config,
+        broadcast_socket,
+        control_socket,
+        data_socket,
+        imu_socket,
+    }
+}
+
+fn simulator(host: &Host) -> Simulator {
+    Simulator::launch(SimulatorConfig {
+        cmd_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        broadcast_target: host.broadcast_socket.local_addr().unwrap(),
+        broadcast_interval: Duration::from_millis(50),
+        ..SimulatorConfig::default()
+    })
+    .unwrap()
+}
+
+/// wait until `condition` holds, false if it did not within `timeout`
+fn wait_for(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    condition()
+}
+
+#[test]
+fn test_client_against_simulator() {
+    let host = host();
+    let simulator = simulator(&host);
+
+    let devices = discover_on(&host.broadcast_socket, Duration::from_millis(200)).unwrap();
+    assert_eq!(devices.len(), 1);
+    assert_eq!(devices[0].broadcast_code, "0SIMULATOR00001");
+    assert_eq!(devices[0].addr, simulator.cmd_addr());
+
+    let client =
+        LivoxClient::new(host.config.clone(), devices[0].addr, host.control_socket).unwrap();
+    client.handshake().unwrap();
+    assert!(simulator.is_connected());
+    assert_eq!(client.device_info().unwrap().version, [6, 4, 0, 0]);
+    assert_eq!(client.heartbeat().unwrap().work_state, 0x01);
+
+    client.set_return_mode(ReturnMode::Dual).unwrap();
+    assert_eq!(client.get_return_mode().unwrap().mode, ReturnMode::Dual);
+    let extrinsics = Extrinsics {
+        yaw: 90.0,
+        x: 100,
+        z: -50,
+        ..Extrinsics::default()
+    };
+    client.set_extrinsics(extrinsics).unwrap();
+    assert_eq!(simulator.extrinsics(), extrinsics);
+    assert_eq!(client.read_extrinsics().unwrap().yaw, 90.0);
+
+    // lidar answers an impossible date with a non-zero return code
+    let rejected = client.command_execute(UpdateUtcSyncTime::new(24, 13, 1, 0, 0));
+    assert!(matches!(
+        rejected,
+        Err(LivoxError::DeviceRejected { ret_code: 0x01, .. })
+    ));
+
+    client.set_rain_fog_suppression(true).unwrap();
+    assert!(simulator.rain_fog_suppression());
+    client.set_fan(false).unwrap();
+    assert_eq!(client.fan_state().unwrap().state, 0x00);
+    client
+        .set_imu_push_frequency(ImuPushFrequency::Off)
+        .unwrap();
+    assert_eq!(
+        client.imu_push_frequency().unwrap().frequency,
+        ImuPushFrequency::Off
+    );
+    client
+        .set_imu_push_frequency(ImuPushFrequency::Hz200)
+        .unwrap();
+    client
+        .write_flash(WriteFlashReq::new(false, true, 4).unwrap())
+        .unwrap();
+    let flash = client.read_flash().unwrap();
+    assert_eq!(
+        (flash.high_sensitivity, flash.repetitive_scan, flash.slot_id),
+        (false, true, 4)
+    );
+
+    // replies lost or broken on the way are recovered by retransmission
+    simulator.drop_replies(1);
+    client.heartbeat().unwrap();
+    simulator.corrupt_replies(1);
+    client.heartbeat().unwrap();
+
+    let events = client.subscribe_events();
+    simulator.push_abnormal_status(0x40).unwrap();
+    assert!(matches!(
+        events.recv_timeout(Duration::from_secs(1)),
+        Ok(LidarEvent::AbnormalStatus(health)) if health.status_code == 0x40
+    ));
+
+    let point_receiver = PointReceiver::launch(host.data_socket).unwrap();
+    let (point_sender, points) = mpsc::channel();
+    point_receiver.subscribe(move |packet| {
+        let _ = point_sender.send((packet.header.data_type, packet.points.len()));
+    });
+    let imu_receiver = ImuReceiver::launch(host.imu_socket).unwrap();
+    let (imu_sender, imu_samples) = mpsc::channel();
+    imu_receiver.subscribe(move |sample| {
+        let _ = imu_sender.send(sample.acc_z);
+    });
+
+    client.start_sampling().unwrap();
+    assert_eq!(
+        points.recv_timeout(Duration::from_secs(1)).unwrap(),
+        (0x02, 96)
+    );
+    assert_eq!(
+        imu_samples.recv_timeout(Duration::from_secs(1)).unwrap(),
+        1.0
+    );
+    client.stop_sampling().unwrap();
+    assert!(!simulator.is_sampling());
+
+    client.disconnect().unwrap();
+    assert!(!simulator.is_connected());
+
+    point_receiver.terminate().unwrap();
+    imu_receiver.terminate().unwrap();
+    client.terminate().unwrap();
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_hub_commands_against_simulator() {
+    let host = host();
+    let simulator = Simulator::launch(SimulatorConfig {
+        broadcast_code: "0SIMULATORHUB01".to_string(),
+        dev_type: DeviceType::Hub,
+        cmd_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+        broadcast_target: host.broadcast_socket.local_addr().unwrap(),
+        ..SimulatorConfig::default()
+    })
+    .unwrap();
+
+    let client = LivoxClient::new(
+        host.config.clone(),
+        simulator.cmd_addr(),
+        host.control_socket,
+    )
+    .unwrap();
+    client.handshake().unwrap();
+
+    let info = client.command_execute(HUB_QUERY_LIDAR_INFO).unwrap();
+    assert_eq!(info.lidars.0.len(), 1);
+
+    let results = client
+        .command_execute(HubFanControlReq::new(vec![
+            HubLidarValue::new("0SIMULATORHUB01", false),
+            HubLidarValue::new("0UNKNOWNLIDAR01", true),
+        ]))
+        .unwrap();
+    let ret_codes: Vec<_> = results
+        .lidars
+        .0
+        .iter()
+        .map(|lidar| lidar.ret_code)
+        .collect();
+    assert_eq!(ret_codes, vec![0, 1]);
+
+    let fan = client
+        .command_execute(HubGetFanStateReq::new(&["0SIMULATORHUB01"]))
+        .unwrap();
+    assert_eq!(fan.lidars.0[0].value, 0x00);
+
+    client.disconnect().unwrap();
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_supervisor_reconnects_after_reboot() {
+    let host = host();
+    let simulator = simulator(&host);
+
+    let supervisor =
+        ConnectionSupervisor::launch(host.config, host.broadcast_socket, host.control_socket)
+            .unwrap();
+    let events = supervisor.subscribe_events();
+    supervisor.start_sampling();
+
+    assert!(wait_for(Duration::from_secs(5), || supervisor.state()
+        == ConnectionState::Sampling));
+    assert!(simulator.is_sampling());
+
+    simulator.reboot(Duration::from_millis(500));
+    assert!(wait_for(Duration::from_secs(5), || supervisor.state()
+        != ConnectionState::Sampling));
+    assert!(wait_for(Duration::from_secs(10), || supervisor.state()
+        == ConnectionState::Sampling));
+    assert!(simulator.is_sampling());
+
+    let states: Vec<_> = events
+        .try_iter()
+        .filter_map(|event| match event {
+            LidarEvent::StateChanged(state) => Some(state),
+            _ => None,
+        })
+        .collect();
+    assert!(states.contains(&ConnectionState::Lost));
+    assert_eq!(states.last(), Some(&ConnectionState::Sampling));
+
+    supervisor.terminate().unwrap();
+    assert!(!simulator.is_connected());
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_supervisor_survives_discovery_error() {
+    let host = host();
+    let simulator = simulator(&host);
+
+    let broadcast_socket = host.broadcast_socket;
+    let mut failed = false;
+    let discover: Discover = Box::new(move |window| {
+        if !failed {
+            failed = true;
+            return Err(LivoxError::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "link down",
+            )));
+        }
+        discover_on(&broadcast_socket, window)
+    });
+    let supervisor =
+        ConnectionSupervisor::launch_with(host.config, discover, host.control_socket).unwrap();
+
+    assert!(wait_for(Duration::from_secs(5), || matches!(
+        supervisor.state(),
+        ConnectionState::Connected(_)
+    )));
+    assert!(simulator.is_connected());
+
+    supervisor.terminate().unwrap();
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_capture_and_replay() {
+    let host = host();
+    let simulator = simulator(&host);
+    let path = std::env::temp_dir().join(format!("livox_capture_{}.pcap", std::process::id()));
+    let capture = Capture::create(&path).unwrap();
+
+    let lidar_addr = simulator.cmd_addr();
+    let client = LivoxClient::new(host.config.clone(), lidar_addr, host.control_socket).unwrap();
+    client.set_capture(capture.clone());
+    let point_receiver =
+        PointReceiver::launch_from(capture.tap(host.data_socket).unwrap()).unwrap();
+    let (point_sender, points) = mpsc::channel();
+    point_receiver.subscribe(move |packet| {
+        let _ = point_sender.send(packet.header.timestamp);
+    });
+
+    client.handshake().unwrap();
+    client.start_sampling().unwrap();
+    let timestamps: Vec<_> = (0..5)
+        .map(|_| points.recv_timeout(Duration::from_secs(1)).unwrap())
+        .collect();
+    client.stop_sampling().unwrap();
+    client.disconnect().unwrap();
+    point_receiver.terminate().unwrap();
+    client.terminate().unwrap();
+    simulator.terminate().unwrap();
+    capture.flush().unwrap();
+
+    // every reply captured on control port passes through the response parser again
+    let mut reader = PcapReader::open(&path).unwrap();
+    let mut replies = Vec::new();
+    while let Some(datagram) = reader.next_datagram().unwrap() {
+        if datagram.src == lidar_addr {
+            replies.push(deserialize_resp(&datagram.payload).unwrap().1);
+        }
+    }
+    // handshake, start and stop sampling, disconnect
+    assert!(replies.len() >= 4);
+    assert!(replies.contains(&SAMPLE_START_REQ.cmd()));
+
+    // and packets captured on data port through the point parser
+    let mut source = PcapSource::new(PcapReader::open(&path).unwrap(), host.config.data_port);
+    let mut buffer = [0u8; 1500];
+    let mut replayed = Vec::new();
+    while let Some(size) = source.recv_datagram(&mut buffer).unwrap() {
+        replayed.push(deserialize_data(&buffer[..size]).unwrap().header.timestamp);
+    }
+    assert!(replayed.starts_with(&timestamps));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_host_transform_undoes_device_extrinsics() {
+    let host = host();
+    let simulator = simulator(&host);
+    let client = LivoxClient::new(
+        host.config.clone(),
+        simulator.cmd_addr(),
+        host.control_socket,
+    )
+    .unwrap();
+    let point_receiver = PointReceiver::launch(host.data_socket).unwrap();
+    let (point_sender, clouds) = mpsc::channel();
+    point_receiver.subscribe(move |packet| {
+        let _ = point_sender.send(packet.cloud_points(Duration::ZERO));
+    });
+
+    let extrinsics = Extrinsics {
+        roll: 90.0,
+        pitch: 30.0,
+        yaw: -45.0,
+        x: 1000,
+        y: -500,
+        z: 2000,
+    };
+    client.handshake().unwrap();
+    client.set_extrinsics(extrinsics).unwrap();
+    client.start_sampling().unwrap();
+    let mut cloud = clouds.recv_timeout(Duration::from_secs(1)).unwrap();
+    client.stop_sampling().unwrap();
+
+    // simulated scan lies in a band around the horizontal plane of lidar, 10 to 11 meters away,
+    // which extrinsics applied on device move elsewhere and the host side inverse brings back
+    let in_band = |cloud: &[livox_lidar_rs::cloud::CloudPoint]| {
+        cloud.iter().all(|p| {
+            let depth = (p.x * p.x + p.y * p.y + p.z * p.z).sqrt();
+            (9.99..11.01).contains(&depth) && p.z.abs() < 0.5
+        })
+    };
+    assert!(!in_band(&cloud));
+    Transform::from(extrinsics)
+        .inverse()
+        .apply_cloud(&mut cloud);
+    assert!(in_band(&cloud));
+
+    point_receiver.terminate().unwrap();
+    client.terminate().unwrap();
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_utc_sync_and_host_time() {
+    let mut host = host();
+    host.config.utc_sync_interval_ms = Some(200);
+    let simulator = simulator(&host);
+    let point_receiver = PointReceiver::launch(host.data_socket).unwrap();
+    let time_sync = Arc::new(TimeSync::new());
+    time_sync.attach(&point_receiver);
+    let (header_sender, headers) = mpsc::channel();
+    point_receiver.subscribe(move |packet| {
+        let _ = header_sender.send((packet.header, Instant::now(), SystemTime::now()));
+    });
+    let supervisor =
+        ConnectionSupervisor::launch(host.config, host.broadcast_socket, host.control_socket)
+            .unwrap();
+    supervisor.start_sampling();
+
+    // lidar switches to UTC once host pushed it
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let (header, received, now) = loop {
+        let (header, received, now) = headers.recv_timeout(Duration::from_secs(5)).unwrap();
+        if header.timestamp_type == 0x03 || Instant::now() > deadline {
+            break (header, received, now);
+        }
+    };
+    assert_eq!(header.timestamp_type, 0x03);
+    let utc = Duration::from_nanos(header.lidar_time().unwrap());
+    let now = now.duration_since(UNIX_EPOCH).unwrap();
+    assert!(now.abs_diff(utc) < Duration::from_millis(100));
+
+    // later packets land on host clock close to when they arrived
+    std::thread::sleep(Duration::from_millis(300));
+    let estimate = time_sync.estimate().unwrap();
+    assert_eq!(estimate.timestamp_type, TimestampType::Gps);
+    let (header, received_later, _) = headers.try_iter().last().unwrap();
+    assert!(received_later > received);
+    let host_time = time_sync.host_time(&header).unwrap();
+    assert!(
+        host_time.max(received_later) - host_time.min(received_later) < Duration::from_millis(50)
+    );
+
+    supervisor.terminate().unwrap();
+    point_receiver.terminate().unwrap();
+    simulator.terminate().unwrap();
+}
+
+#[test]
+fn test_session_isolates_lidars() {
+    let hosts = [host(), host()];
+    let simulators: Vec<_> = ["0SIMULATOR00001", "0SIMULATOR00002"]
+        .into_iter()
+        .map(|code| {
+            Simulator::launch(SimulatorConfig {
+                broadcast_code: code.to_string(),
+                cmd_addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+                broadcast_target: hosts[0].broadcast_socket.local_addr().unwrap(),
+                broadcast_interval: Duration::from_millis(50),
+                ..SimulatorConfig::default()
+            })
+            .unwrap()
+        })
+        .collect();
+
+    let [first, second] = hosts;
+    let config = SessionConfig {
+        merge_window_ms: Some(50),
+        lidars: vec![
+            LidarConfig {
+                broadcast_code: Some("0SIMULATOR00001".to_string()),
+                slot_id: Some(1),
+                ..first.config
+            },
+            LidarConfig {
+                broadcast_code: Some("0SIMULATOR00002".to_string()),
+                slot_id: Some(2),
+                extrinsics: Some(Extrinsics {
+                    x: 1000,
+                    ..Extrinsics::default()
+                }),
+                ..second.config
+            },
+        ],
+        ..SessionConfig::default()
+    };
+    let session = SessionManager::launch(
+        config,
+        first.broadcast_socket,
+        vec![
+            LidarSockets {
+                control: first.control_socket,
+                data: first.data_socket,
+            },
+            LidarSockets {
+                control: second.control_socket,
+                data: second.data_socket,
+            },
+        ],
+    )
+    .unwrap();
+    // extrinsics are applied on host when merging
+    assert_eq!(session.config(1).unwrap().extrinsics, None);
+
+    let packets = Arc::new(Mutex::new([0usize; 2]));
+    let duplicated_packets = packets.clone();
+    session.subscribe(move |tag, packet| {
+        assert_eq!(tag.slot_id as usize, tag.device + 1);
+        assert_eq!(packet.header.slot_id, tag.slot_id);
+        duplicated_packets.lock().unwrap()[tag.device] += 1;
+    });
+    let (tx, merged) = mpsc::channel();
+    session
+        .subscribe_merged(move |frame| {
+            let _ = tx.send(
+                frame
+                    .lidars
+                    .iter()
+                    .map(|tag| tag.device)
+                    .collect::<Vec<_>>(),
+            );
+        })
+        .unwrap();
+
+    // slot ids are written on request only, simulator starts with slot 1 in flash and
+    // applies a new one without a reboot
+    assert!(wait_for(Duration::from_secs(5), || session
+        .states()
+        .iter()
+        .all(|state| matches!(state, ConnectionState::Connected(_)))));
+    assert_eq!(session.assign_slot_ids().unwrap(), vec![1]);
+    assert!(session.assign_slot_ids().unwrap().is_empty());
+    session.start_sampling();
+
+    assert!(wait_for(Duration::from_secs(5), || session
+        .states()
+        .iter()
+        .all(|state| *state == ConnectionState::Sampling)));
+    assert!(wait_for(Duration::from_secs(5), || packets
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|count| *count > 0)));
+    assert!(wait_for(Duration::from_secs(5), || merged
+        .try_iter()
+        .any(|devices| devices == vec![0, 1])));
+
+    // losing the second lidar leaves the first one streaming
+    simulators[1].set_offline(true);
+    assert!(wait_for(Duration::from_secs(5), || session.states()[1]
+        != ConnectionState::Sampling));
+    assert_eq!(session.states()[0], ConnectionState::Sampling);
+    let streamed = packets.lock().unwrap()[0];
+    assert!(wait_for(Duration::from_secs(2), || packets.lock().unwrap()
+        [0]
+        > streamed));
+    merged.try_iter().count();
+    assert!(wait_for(Duration::from_secs(2), || merged
+        .try_iter()
+        .any(|devices| devices == vec![0])));
+
+    session.terminate().unwrap();
+    for simulator in simulators {
+        simulator.terminate().unwrap();
+    }
+}
+
+#[test]
+fn test_broadcast_listener_forgets_silent_lidar() {
+    let host = host();
+    let simulator = simulator(&host);
+    let listener = BroadcastListener::launch(host.broadcast_socket).unwrap();
+    let mut discover = listener.discover("0SIMULATOR00001");
+
+    let window = Duration::from_millis(200);
+    let found = discover(window).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].addr, simulator.cmd_addr());
+    assert_eq!(listener.devices(window).len(), 1);
+
+    // lidar stopped broadcasting, the entry heard before is stale
+    simulator.set_offline(true);
+    std::thread::sleep(window * 2);
+    assert!(discover(window).unwrap().is_empty());
+    assert!(listener.devices(window).is_empty());
+
+    simulator.set_offline(false);
+    assert_eq!(discover(window).unwrap().len(), 1);
+
+    listener.terminate().unwrap();
+    simulator.terminate().unwrap();
+}